@@ -1,15 +1,53 @@
-use std::{collections::HashMap, ops::Deref, path::PathBuf};
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashMap, HashSet},
+    ffi::OsStr,
+    fs::File,
+    io,
+    num::NonZeroUsize,
+    ops::Deref,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
-use anyhow::Result;
-use camino::Utf8PathBuf;
-use clap::Parser;
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use anyhow::{anyhow, Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use clap::{Parser, ValueEnum};
+use git2::{Oid, Repository};
+use globset::{Glob, GlobSet, GlobSetBuilder};
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
+use regex::Regex;
 use rusqlite::params;
-use walkdir::WalkDir;
+use serde_json::json;
+use tempfile::{NamedTempFile, TempDir, TempPath};
+use tokio::{sync::Semaphore, task::JoinSet};
 
-use crate::{config::Config, repository::GitRepository};
+#[cfg(feature = "scripting")]
+use crate::script::Script;
+use crate::{
+    bundle,
+    config::{Config, ConfigFormat, RepositoryConfig},
+    database::{self, OnConflict, TempStore, Tuning},
+    date, dot, identity,
+    log::{EffortFormula, DEFAULT_MAX_SUMMARY_WIDTH},
+    output::{JsonWriter, OutputFormat},
+    progress::{self, Progress, ProgressMode},
+    repository::{
+        AnalyzeOptions, BlameScope, Catalog, DateSource, GitRepository, MaxChurnMode, NameSource,
+        SizeScope,
+    },
+    scan,
+    summary::{
+        Activity, ActivityPeriod, AuthorCadence, Collaboration, ExtensionStats, FileFirstSeen,
+        FileLatest, LogRow, RepositoryHealth,
+    },
+};
 
 /// A git repository analyzer. To prevent the impossible operation from executing (i.e. run analysis
 /// before setting up the database, etc.), the analyzer must be successfully constructed before
@@ -33,90 +71,876 @@ impl<S> Deref for GitRepositoryAnalyzer<S> {
 #[clap(about, version)]
 pub struct Uninitialized {
     /// Path to the root directory to scan
-    #[arg()]
-    pub root: Utf8PathBuf,
+    #[arg(required_unless_present = "single")]
+    pub root: Option<Utf8PathBuf>,
 
     /// Recursively scan the root directory
     #[arg(short, long)]
     pub recursive: bool,
 
-    /// Max depth of the recursive scan
+    /// Max depth of the recursive scan. 0 means unlimited
     #[arg(short, long, default_value = "1")]
     pub max_depth: usize,
 
-    /// Path to the database
+    /// Path to the database. Use `-` to write the database to stdout
     #[arg(short, long, default_value = "repositories.db")]
     pub database: Utf8PathBuf,
 
-    /// Path to TOML configuration file
+    /// Path to the configuration file, in TOML, JSON (.json), or YAML (.yaml, .yml)
     #[arg(short = 'f', long, default_value = "config.toml")]
     pub config: Utf8PathBuf,
 
-    /// Delete all records from the database before scanning
-    #[arg(short, long)]
+    /// Format of the configuration file, instead of detecting it from the extension
+    #[arg(long, value_enum)]
+    pub config_format: Option<ConfigFormat>,
+
+    /// Delete all records from the database before scanning. Same as --on-exists clear
+    #[arg(short, long, conflicts_with = "on_exists")]
     pub clear: bool,
 
+    /// What to do when the database file already exists
+    #[arg(long, value_enum, default_value_t)]
+    pub on_exists: OnExists,
+
+    /// Delete from the database the repositories whose stored path no longer exists on disk,
+    /// with all their rows, before scanning
+    #[arg(long)]
+    pub prune_missing: bool,
+
     /// Number of worker threads
     #[arg(short, long, default_value = "8")]
     pub num_threads: usize,
+
+    /// Maximum number of repositories analyzed and stored at once, independently of the number
+    /// of worker threads, e.g. to reduce the contention of the writers of the database. No limit
+    /// but the number of worker threads if unset
+    #[arg(long)]
+    pub max_concurrent_repos: Option<NonZeroUsize>,
+
+    /// Number of threads discovering the directories to scan with --recursive, to overlap the
+    /// latency of network filesystems. 1 walks them serially, in the order of the filesystem
+    #[arg(long, default_value = "1")]
+    pub discovery_threads: NonZeroUsize,
+
+    /// Formula to compute the approximate effort score of each commit
+    #[arg(long, value_enum, default_value_t)]
+    pub effort_formula: EffortFormula,
+
+    /// Skip computing the summary tables, such as extension_stats
+    #[arg(long)]
+    pub skip_summaries: bool,
+
+    /// Only store the repositories and the summary tables computed from their logs, such as
+    /// activity, without the logs and the changed files, for a much smaller database
+    #[arg(long, conflicts_with_all = ["skip_summaries", "catalog", "build_collaboration"])]
+    pub output_stats_only: bool,
+
+    /// Format of the output. With json or jsonl, the logs are written to stdout, or to --output,
+    /// with the name and the URL of their repository, instead of being stored into the database
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        conflicts_with_all = ["shards", "output_stats_only", "catalog"]
+    )]
+    pub output_format: OutputFormat,
+
+    /// Write the logs of --output-format json or jsonl to this file, replacing it, instead of
+    /// stdout
+    #[arg(long, value_name = "PATH")]
+    pub output: Option<Utf8PathBuf>,
+
+    /// Revision (branch, tag, or commit) to start the analysis from, instead of HEAD. Can be
+    /// overridden per repository in the configuration file
+    #[arg(short, long)]
+    pub branch: Option<String>,
+
+    /// Stop at the first repository which fails to be analyzed or stored, instead of skipping it
+    #[arg(long)]
+    pub fail_fast: bool,
+
+    /// Debug mode: analyze and store the repository at the path, instead of scanning a root
+    /// directory, synchronously without the worker threads and progress bars, printing each stage
+    /// to stderr and stopping at the first error
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["root", "recursive", "sample", "include_submodule_repos", "deadline"]
+    )]
+    pub single: Option<Utf8PathBuf>,
+
+    /// Also analyze the submodules of each repository as separate repositories, named
+    /// `super/submodule`
+    #[arg(long)]
+    pub include_submodule_repos: bool,
+
+    /// Create a full-text search index (FTS5) over the commit messages, as the logs_fts table
+    #[arg(long)]
+    pub fts: bool,
+
+    /// Which time of the commit populates commit_datetime
+    #[arg(long, value_enum, default_value_t)]
+    pub date: DateSource,
+
+    /// Appearance of the progress reporting
+    #[arg(long, value_enum, default_value_t)]
+    pub progress_style: ProgressMode,
+
+    /// Hide the progress reporting, including the spinner of the discovery of the repositories
+    #[arg(short, long, conflicts_with = "progress_style")]
+    pub quiet: bool,
+
+    /// Skip checking whether each repository has uncommitted changes (is_dirty)
+    #[arg(long)]
+    pub skip_dirty_check: bool,
+
+    /// Source of the repository name. Falls back to dir when the source is unavailable
+    #[arg(long, value_enum, default_value_t)]
+    pub name_from: NameSource,
+
+    /// Maximum churn (insertions + deletions) of a commit, such as vendored dependency bumps, to
+    /// keep it from distorting the churn aggregates
+    #[arg(long)]
+    pub max_churn: Option<usize>,
+
+    /// What to do with a commit over --max-churn
+    #[arg(long, value_enum, default_value_t, requires = "max_churn")]
+    pub max_churn_mode: MaxChurnMode,
+
+    /// Count the files in the tree of each commit (tree_file_count), to chart the repository size
+    /// over time. Slower, as the trees are walked
+    #[arg(long)]
+    pub count_tree_files: bool,
+
+    /// Annotate each commit with the earliest tag containing it (nearest_tag), to tell which
+    /// release shipped it. Slower, as the history of every tag is walked
+    #[arg(long)]
+    pub describe_commits: bool,
+
+    /// When scanning into an existing database, record the stored commits which are no longer
+    /// reachable, e.g. rewritten by a force push, in the rewritten_commits table
+    #[arg(long)]
+    pub detect_rewrites: bool,
+
+    /// Also send the progress events as newline-delimited JSON to a TCP address (host:port) or a
+    /// Unix socket (unix:/path/to/socket), e.g. for an external dashboard
+    #[arg(long)]
+    pub progress_socket: Option<String>,
+
+    /// Only analyze the commits whose commit_datetime is on or after the date (YYYY-MM-DD, UTC),
+    /// or the date time (RFC 3339, e.g. 2024-01-31T12:00:00+09:00)
+    #[arg(long, value_parser = parse_date)]
+    pub since: Option<i64>,
+
+    /// Only analyze the commits whose commit_datetime is on or before the date (YYYY-MM-DD, UTC,
+    /// the whole day included), or the date time (RFC 3339)
+    #[arg(long, value_parser = parse_end_date)]
+    pub until: Option<i64>,
+
+    /// Only analyze the commits whose commit_datetime is within the last N days. Same as --since
+    /// with the date N days ago
+    #[arg(long, conflicts_with = "since")]
+    pub since_days: Option<u64>,
+
+    /// Only analyze the commits of the calendar year, in the local time of each commit, e.g. 2023
+    /// for an annual report
+    #[arg(
+        long,
+        value_name = "YYYY",
+        value_parser = clap::value_parser!(i64).range(1970..=9999),
+        conflicts_with_all = ["since", "since_days", "until"]
+    )]
+    pub year: Option<i64>,
+
+    /// Also store the author and committer lines of each commit exactly as git stored them
+    /// (raw_author, raw_committer), for auditing
+    #[arg(long)]
+    pub store_raw_identity: bool,
+
+    /// Wall-clock budget of the analysis in seconds. Once exceeded, no more repository is started,
+    /// while the ones in progress are finished
+    #[arg(long)]
+    pub deadline: Option<u64>,
+
+    /// Also store the reflog of the ref (e.g. HEAD or refs/heads/main) into the reflog table, to
+    /// audit force pushes and resets. Repositories without the reflog are skipped
+    #[arg(long, value_name = "REF")]
+    pub reflog: Option<String>,
+
+    /// Also store the local branches of each repository into the branches table, with the merge
+    /// base of each with the default branch, to estimate how far they diverged. A merge base is
+    /// computed per branch, which walks the history of both tips
+    #[arg(long)]
+    pub store_branches: bool,
+
+    /// Only analyze a fraction (0.0 to 1.0) of the discovered repositories, to estimate the totals
+    /// of a large collection quickly. The selection is deterministic, by the hash of the path
+    #[arg(long, value_parser = parse_fraction)]
+    pub sample: Option<f64>,
+
+    /// Seed of the --sample selection. A different seed selects a different sample
+    #[arg(long, default_value_t = 0, requires = "sample")]
+    pub sample_seed: u64,
+
+    /// Compare author_name and author_email case-insensitively (COLLATE NOCASE) in the logs table,
+    /// so GROUP BY and DISTINCT merge case variants without changing the stored values. Only
+    /// applies when the logs table is created
+    #[arg(long)]
+    pub nocase_authors: bool,
+
+    /// Split the output across N database files, e.g. repositories.0.db to repositories.2.db for
+    /// 3, by the hash of the repository name, to reduce the write contention of very large
+    /// scans
+    #[arg(long, default_value_t = 1)]
+    pub shards: usize,
+
+    /// Access up to this many bytes of the database with memory-mapped I/O (PRAGMA mmap_size),
+    /// which speeds up large ingests at the cost of memory pressure. SQLite caps it to its
+    /// compile-time maximum, usually 2 GB [default: SQLite's default, usually 0]
+    #[arg(long, value_name = "BYTES", value_parser = clap::value_parser!(u64).range(0..=i64::MAX as u64))]
+    pub mmap_size: Option<u64>,
+
+    /// Where SQLite stores its temporary tables and indices (PRAGMA temp_store)
+    #[arg(long, value_enum, default_value_t)]
+    pub temp_store: TempStore,
+
+    /// Number of context lines of the diff hunks [default: 3]
+    #[arg(long)]
+    pub context_lines: Option<u32>,
+
+    /// Maximum number of lines between diff hunks to merge them [default: 0]
+    #[arg(long)]
+    pub interhunk_lines: Option<u32>,
+
+    /// Ignore whitespace changes, which reduces the reported churn
+    #[arg(long)]
+    pub ignore_whitespace: bool,
+
+    /// Similarity (0 to 100) for a file to be detected as renamed or copied. Only exact matches
+    /// are detected by default
+    #[arg(long, value_parser = clap::value_parser!(u16).range(0..=100))]
+    pub rename_threshold: Option<u16>,
+
+    /// Commit every N logs and record the last stored commit in the ingest_checkpoints table, so
+    /// an interrupted ingest of a huge repository resumes after the checkpoint when run again,
+    /// without analyzing the stored commits again
+    #[arg(long)]
+    pub checkpoint_every: Option<NonZeroUsize>,
+
+    /// Also store the identity of the signer of signed commits (signer_email): the user ID
+    /// declared in an OpenPGP signature, or the public key of an SSH signature
+    #[arg(long)]
+    pub store_signer: bool,
+
+    /// Order in which the logs of each repository are inserted. It doesn't change the query
+    /// results, only the insert order and the changed_files ids
+    #[arg(long, value_enum, default_value_t)]
+    pub write_order: WriteOrder,
+
+    /// Flag the added or modified files whose new blob exceeds the size in bytes (large_blob),
+    /// with the blob_size and is_binary of the blob, to find the commits which bloated the
+    /// repository
+    #[arg(long, value_name = "BYTES")]
+    pub flag_large_blobs: Option<usize>,
+
+    /// Share of the churn (over 0.0, up to 1.0) the authors counted in the bus factor of each
+    /// repository (repository_health) account for
+    #[arg(long, default_value_t = 0.5, value_parser = parse_share)]
+    pub bus_factor_threshold: f64,
+
+    /// Periods of the buckets of the activity summary table: commits, churn, and authors of each
+    /// repository per period. Comma-separated
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "day,week,month")]
+    pub activity_periods: Vec<ActivityPeriod>,
+
+    /// Build the collaboration table: pairs of authors of each repository who changed the same
+    /// files, with the number of shared files. O(files x authors), so it can be slow on large
+    /// repositories
+    #[arg(long)]
+    pub build_collaboration: bool,
+
+    /// Store the current line ownership of the files at the analyzed revision (line_ownership):
+    /// the number of lines of each file last changed by each author, with git blame. Blaming is
+    /// expensive, as it walks the history of every file, so scope it with --blame-path
+    #[arg(long)]
+    pub compute_blame: bool,
+
+    /// Glob of the paths of the files to blame, relative to the root of the repository, e.g.
+    /// 'src/**/*.rs'. Can be repeated. All files by default
+    #[arg(long, value_name = "GLOB", requires = "compute_blame")]
+    pub blame_path: Vec<Glob>,
+
+    /// Maximum size in bytes of the files to blame. Larger files are skipped, as well as binary
+    /// files
+    #[arg(long, value_name = "BYTES", default_value_t = 1_000_000, requires = "compute_blame")]
+    pub blame_max_bytes: u64,
+
+    /// Width in characters above which the summary of a commit is flagged as too long
+    /// (summary_too_long)
+    #[arg(long, default_value_t = DEFAULT_MAX_SUMMARY_WIDTH)]
+    pub max_summary_width: usize,
+
+    /// Only analyze the merge commits, diffed against their first parent, as pull requests, with
+    /// the pull request number extracted from the summary (pr_number)
+    #[arg(long)]
+    pub merges_only: bool,
+
+    /// Also analyze the merge commits, diffed against their first parent, along with the other
+    /// commits
+    #[arg(long, conflicts_with = "merges_only")]
+    pub include_merges: bool,
+
+    /// With --merges-only, also analyze the commits whose summary matches the regular expression,
+    /// for squash merge workflows, e.g. '\(#(\d+)\)$'. The first capture group is the pull request
+    /// number
+    #[arg(long, value_name = "REGEX", requires = "merges_only", value_parser = Regex::new)]
+    pub pr_pattern: Option<Regex>,
+
+    /// File of path globs to exclude from the changed files and the churn of each commit, one per
+    /// line, e.g. `vendor/**`. Blank lines and lines starting with `#` are skipped
+    #[arg(long, value_name = "FILE")]
+    pub exclude_paths_from: Option<Utf8PathBuf>,
+
+    /// Write the commit graph of each repository as a GraphViz file, `<DIR>/<name>.dot`
+    #[arg(long, value_name = "DIR")]
+    pub export_dot: Option<Utf8PathBuf>,
+
+    /// Skip the GraphViz export of the repositories with more commits, as their graphs are
+    /// unusably large
+    #[arg(long, default_value_t = 1000, requires = "export_dot")]
+    pub export_dot_max_commits: usize,
+
+    /// Rhai script run on each commit before it is stored, to set the key/value attributes of
+    /// the commit_attributes table
+    #[cfg(feature = "scripting")]
+    #[arg(long, value_name = "FILE")]
+    pub script: Option<Utf8PathBuf>,
+
+    /// Number of leading path components defining the directory touched by each changed file
+    /// (commit_directories), e.g. `src` for `src/cli/main.rs` at 1, or `src/cli` at 2
+    #[arg(long, default_value = "1")]
+    pub dir_depth: NonZeroUsize,
+
+    /// Measure the size of each repository on disk (size_bytes): the git directory only (git-dir),
+    /// or the whole working tree including it (worktree)
+    #[arg(long, value_name = "SCOPE", num_args = 0..=1, default_missing_value = "git-dir")]
+    pub measure_size: Option<SizeScope>,
+
+    /// Also store the changed files of each commit as a JSON array in logs (changed_files_json),
+    /// for queries with the JSON functions of SQLite instead of joins
+    #[arg(long)]
+    pub files_as_json: bool,
+
+    /// What to do with the commits already stored, e.g. when scanning the same repositories again
+    /// without --clear: keep them (ignore), overwrite them (replace), or fail the repository
+    /// (fail)
+    #[arg(long, value_enum, default_value_t)]
+    pub on_conflict: OnConflict,
+
+    /// Analyze the whole history of the repositories already stored, instead of only the commits
+    /// since the commit their last scan started from
+    #[arg(long)]
+    pub full: bool,
+
+    /// Skip the repositories with fewer analyzed commits, e.g. trivial or template repositories.
+    /// They are not stored at all
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub min_commits: usize,
+
+    /// Only catalog the repositories: store their metadata (path, url, default branch, HEAD
+    /// commit, and commit count) into the repositories table, without analyzing the commits
+    #[arg(long)]
+    pub catalog: bool,
+
+    /// Store the repositories without any commit (unborn HEAD) in the repositories table, with no
+    /// logs, instead of skipping them, for a complete inventory
+    #[arg(long)]
+    pub include_empty_repos: bool,
+
+    /// Extra filter of the directories to scan, for library users
+    #[arg(skip)]
+    pub directory_filter: Option<DirectoryFilter>,
 }
 
+/// Predicate of the directories to scan. A directory is scanned only if it returns `true`.
+pub type DirectoryFilter = Box<dyn Fn(&Path) -> bool + Send + Sync>;
+
 pub struct Prepared {
     /// Number of worker threads
     pub num_threads: usize,
 
-    /// Database connection pool
-    pub pool: Pool<SqliteConnectionManager>,
+    /// Maximum number of repositories analyzed at once, if limited
+    pub max_concurrent_repos: Option<NonZeroUsize>,
+
+    /// Stop at the first repository which fails
+    pub fail_fast: bool,
+
+    /// Analyze the single repository synchronously, for debugging
+    pub single: bool,
+
+    /// Appearance of the progress reporting
+    pub progress_style: ProgressMode,
+
+    /// Address to send the progress events to
+    pub progress_socket: Option<String>,
+
+    /// Wall-clock budget of the analysis in seconds, after which no more repository is started
+    pub deadline: Option<u64>,
+
+    /// Database connection pools, one per shard
+    pub pools: Vec<Pool<SqliteConnectionManager>>,
+
+    /// Temporary database file to be written to stdout after the analysis, if the database is
+    /// `-`. The file is removed when dropped.
+    pub temp_database: Option<TempPath>,
+
+    /// Temporary directory of the repository cloned from the root, if it is a bundle. The
+    /// directory is removed when dropped.
+    pub unbundled: Option<TempDir>,
 
     /// List of directories to scan
     pub directories: Vec<PathBuf>,
 
+    /// Directories of `directories` which look like repositories, found once with them, to size
+    /// the overall progress without opening them
+    pub repositories: HashSet<PathBuf>,
+
+    /// Repository names overriding the directory name, such as `super/submodule` for submodules
+    pub names: HashMap<PathBuf, String>,
+
     /// List of ignored repositories
     pub ignored_repositories: Vec<String>,
 
-    /// Email address and user name map to normalize the author name
-    pub author_map: Option<HashMap<String, String>>,
+    /// Repositories deleted from the database as their path no longer exists, with
+    /// `--prune-missing`
+    pub pruned_repositories: Vec<String>,
+
+    /// Options to analyze each repository
+    pub analyze_options: AnalyzeOptions,
+
+    /// Options to store each analyzed repository
+    pub store_options: StoreOptions,
+
+    /// Per-repository overrides, keyed by the directory name of the repository
+    pub repository_configs: HashMap<String, RepositoryConfig>,
+}
+
+/// Options to control how an analyzed repository is stored into the database.
+#[derive(Debug, Clone)]
+pub struct StoreOptions {
+    /// Formula to compute the approximate effort score of each commit
+    pub effort_formula: EffortFormula,
+
+    /// Skip computing the summary tables
+    pub skip_summaries: bool,
+
+    /// Store the summary tables without the logs
+    pub stats_only: bool,
+
+    /// Commit the logs and record a checkpoint every N logs. All at once if `None`.
+    pub checkpoint_every: Option<usize>,
+
+    /// Order in which the logs are inserted
+    pub write_order: WriteOrder,
+
+    /// Share of the churn (0.0 to 1.0) the authors counted in the bus factor account for
+    pub bus_factor_threshold: f64,
+
+    /// Periods of the buckets of the activity table
+    pub activity_periods: Vec<ActivityPeriod>,
+
+    /// Build the collaboration table after storing the logs
+    pub build_collaboration: bool,
+
+    /// Minimum number of commits of a repository to store it
+    pub min_commits: usize,
+
+    /// Also store the changed files of each log as a JSON array
+    pub files_as_json: bool,
+
+    /// What to do with the commits already stored
+    pub on_conflict: OnConflict,
+
+    /// Only analyze the commits of each repository since the commit its last scan started from
+    pub incremental: bool,
+
+    /// Directory to write the GraphViz file of the commit graph of each repository into, if any
+    pub export_dot: Option<Utf8PathBuf>,
+
+    /// Maximum number of commits of a repository to export its commit graph
+    pub export_dot_max_commits: usize,
+
+    /// Script setting the attributes of each commit, if any
+    #[cfg(feature = "scripting")]
+    pub script: Option<Arc<Script>>,
+
+    /// Writer of the logs as JSON, instead of storing them, if any
+    pub output: Option<Arc<JsonWriter>>,
+
+    /// Bundle file the repository was cloned from, if any, stored as its path instead of the
+    /// temporary clone
+    pub bundle: Option<String>,
+}
+
+/// Order in which the logs of a repository are inserted.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum WriteOrder {
+    /// Oldest commits first, e.g. to build cumulative summaries as the logs are inserted
+    OldestFirst,
+    /// Newest commits first, in the order of the history walk, so early readers see the recent
+    /// activity
+    #[default]
+    NewestFirst,
+}
+
+/// What to do when the database file already exists.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OnExists {
+    /// Add the repositories to the existing database
+    #[default]
+    Append,
+    /// Delete all records from the existing database, keeping the file
+    Clear,
+    /// Refuse to touch the existing database
+    Fail,
+    /// Rename the existing database to `<path>.<UNIX epoch>.bak` and create a fresh one
+    Backup,
+}
+
+/// Outcome of the analysis.
+#[derive(Debug)]
+pub struct AnalysisReport {
+    /// Elapsed time in seconds
+    pub elapsed: f64,
+
+    /// Names of the repositories in the database
+    pub analyzed_repositories: Vec<String>,
+
+    /// Directories which failed to be analyzed or stored, with the error which stopped them
+    pub skipped_directories: Vec<(String, anyhow::Error)>,
+
+    /// Directories which were not started as the deadline was exceeded
+    pub not_started_directories: Vec<String>,
+
+    /// Directories which were not stored as they have fewer commits than the minimum
+    pub too_few_commits_directories: Vec<String>,
+
+    /// Repositories with stored commits which are no longer reachable, with their number, with
+    /// `--detect-rewrites`
+    pub rewritten_repositories: Vec<(String, usize)>,
+}
+
+/// Directories which were not stored, by reason.
+#[derive(Default)]
+struct Unstored {
+    /// Failed to be analyzed or stored, with the error
+    failed: HashMap<PathBuf, anyhow::Error>,
+    /// Not started as the deadline was exceeded
+    not_started: HashSet<PathBuf>,
+    /// Fewer commits than the minimum
+    too_few_commits: HashSet<PathBuf>,
+}
+
+/// Outcome of a repository analyzed without error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    /// Stored into the database
+    Stored,
+    /// Not stored, as it has fewer commits than the minimum
+    TooFewCommits,
+}
+
+impl Default for GitRepositoryAnalyzer<Uninitialized> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<Uninitialized> for GitRepositoryAnalyzer<Uninitialized> {
+    fn from(state: Uninitialized) -> Self {
+        Self::from_options(state)
+    }
 }
 
 impl GitRepositoryAnalyzer<Uninitialized> {
+    /// Sets a filter of the directories to scan, applied after the `ignored_repositories` of the
+    /// configuration, e.g. to scan only the repositories in an internal registry.
+    pub fn with_directory_filter(
+        mut self,
+        filter: impl Fn(&Path) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.state.directory_filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Creates the analyzer from the command line arguments of the process.
     pub fn new() -> Self {
-        GitRepositoryAnalyzer { state: Uninitialized::parse() }
+        Self::from_options(Uninitialized::parse())
+    }
+
+    /// Creates the analyzer from options built by the caller, e.g. with
+    /// `Uninitialized::try_parse_from` on arguments of its own, without reading the command line
+    /// of the process.
+    pub fn from_options(options: Uninitialized) -> Self {
+        GitRepositoryAnalyzer { state: options }
     }
 
     pub fn try_prepare(self) -> Result<GitRepositoryAnalyzer<Prepared>> {
-        let (directories, ignored_repositories, author_map) = self.get_directories_to_scan();
-        let pool = Pool::new(SqliteConnectionManager::file(&self.database))?;
-        self.prepare_database(&pool)?;
+        if let (Some(since), Some(until)) = (self.since, self.until) {
+            anyhow::ensure!(since <= until, "--since must not be after --until");
+        }
+        anyhow::ensure!(
+            self.output.is_none() || self.output_format != OutputFormat::Sqlite,
+            "--output needs --output-format json or jsonl, the database is written to --database"
+        );
+        let config = self.get_config()?;
+        let excluded_messages = config.excluded_messages()?;
+        let normalize_emails = config.normalize_emails.unwrap_or(true);
+        let excluded_paths = self
+            .exclude_paths_from
+            .as_ref()
+            .map(read_excluded_paths)
+            .transpose()?
+            .unwrap_or_default();
+        let analysis_options = self.analysis_options(&config, &excluded_paths);
+        let unbundled = bundle::is_bundle(self.root())
+            .then(|| bundle::unbundle(self.root()))
+            .transpose()?;
+        let bundle = unbundled
+            .is_some()
+            .then(|| self.root().canonicalize_utf8().map(|path| path.to_string()))
+            .transpose()?;
+        let (mut directories, ignored_repositories) = match &unbundled {
+            Some((_, repo)) => (vec![repo.clone()], vec![]),
+            None => self.get_directories_to_scan(&config),
+        };
+        let mut names = HashMap::new();
+        if self.include_submodule_repos {
+            add_submodules(&mut directories, &mut names);
+        }
+        let repositories = directories
+            .iter()
+            .filter(|path| scan::is_repository(path))
+            .cloned()
+            .collect();
+        // SQLite can't write to a non-seekable stream, so build the database in a temporary file
+        // and stream it to stdout after the analysis. The JSON output only needs the repositories
+        // of the report from it.
+        let temp_database = (self.database == "-" || self.output_format != OutputFormat::Sqlite)
+            .then(|| NamedTempFile::new().map(|file| file.into_temp_path()))
+            .transpose()?;
+        let database = match &temp_database {
+            Some(path) => path.to_path_buf(),
+            None => self.database.clone().into(),
+        };
+        let databases = match self.shards {
+            0 => anyhow::bail!("--shards must be at least 1"),
+            1 => vec![database],
+            _ if temp_database.is_some() => anyhow::bail!("--shards can't write to stdout"),
+            n => (0..n).map(|i| shard_path(&self.database, i).into()).collect(),
+        };
+        let mut options_changed = false;
+        let pools = databases
+            .into_iter()
+            .map(|database| {
+                if temp_database.is_none() {
+                    self.check_existing_database(&database)?;
+                }
+                let tuning = Tuning {
+                    mmap_size: self.mmap_size,
+                    temp_store: self.temp_store,
+                };
+                let manager = SqliteConnectionManager::file(database)
+                    .with_init(move |conn| tuning.init_connection(conn));
+                let pool = Pool::new(manager)?;
+                self.prepare_database(&pool)?;
+                let conn = pool.get()?;
+                let changed = database::replace_analysis_options(&conn, &analysis_options)?;
+                // a cleared database has no commits stored with the previous options
+                options_changed |= changed && !(self.clear || self.on_exists == OnExists::Clear);
+                drop(conn);
+                Ok(pool)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if options_changed && !self.full {
+            eprintln!(
+                "warning: the analysis options changed since the previous scan, so the whole \
+                 history is analyzed again, replacing the stored commits"
+            );
+        }
+        let mut pruned_repositories = Vec::new();
+        if self.prune_missing {
+            for pool in &pools {
+                pruned_repositories.extend(database::prune_missing(&mut *pool.get()?)?);
+            }
+        }
 
         Ok(GitRepositoryAnalyzer {
             state: Prepared {
                 num_threads: self.num_threads,
-                pool,
+                max_concurrent_repos: self.max_concurrent_repos,
+                fail_fast: self.fail_fast,
+                single: self.single.is_some(),
+                progress_style: self.progress_mode(),
+                progress_socket: self.progress_socket.clone(),
+                deadline: self.deadline,
+                pools,
+                temp_database,
+                unbundled: unbundled.map(|(dir, _)| dir),
                 directories,
+                repositories,
+                names,
                 ignored_repositories,
-                author_map,
+                pruned_repositories,
+                analyze_options: AnalyzeOptions {
+                    // the emails are looked up normalized, so normalize the keys as well
+                    author_map: config.author_map.map(|map| match normalize_emails {
+                        true => map
+                            .into_iter()
+                            .map(|(email, name)| (identity::normalize_email(&email), name))
+                            .collect(),
+                        false => map,
+                    }),
+                    name_from_email: config.name_from_email.unwrap_or_default(),
+                    normalize_emails,
+                    rev: self.branch.clone(),
+                    date: self.date,
+                    working_hours: config.working_hours.unwrap_or_default(),
+                    check_dirty: !self.skip_dirty_check,
+                    name_from: self.name_from,
+                    max_churn: self.max_churn,
+                    max_churn_mode: self.max_churn_mode,
+                    count_tree_files: self.count_tree_files,
+                    describe_commits: self.describe_commits,
+                    detect_rewrites: self.detect_rewrites,
+                    previous_commit: None,
+                    stored_commits: HashSet::new(),
+                    since: self.since.or(self.since_days.map(|days| {
+                        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+                        now.as_secs() as i64 - days as i64 * 86400
+                    })),
+                    until: self.until,
+                    year: self.year.map(|year| {
+                        date::days_from_civil(year, 1, 1) * 86400
+                            ..date::days_from_civil(year + 1, 1, 1) * 86400
+                    }),
+                    store_raw_identity: self.store_raw_identity,
+                    reflog: self.reflog.clone(),
+                    branches: self.store_branches,
+                    context_lines: self.context_lines,
+                    interhunk_lines: self.interhunk_lines,
+                    ignore_whitespace: self.ignore_whitespace,
+                    rename_threshold: self.rename_threshold,
+                    store_signer: self.store_signer,
+                    large_blob_threshold: self.flag_large_blobs,
+                    max_summary_width: Some(self.max_summary_width),
+                    catalog: self.catalog,
+                    include_empty: self.include_empty_repos,
+                    blame: self
+                        .compute_blame
+                        .then(|| {
+                            let paths = (!self.blame_path.is_empty())
+                                .then(|| build_glob_set(&self.blame_path))
+                                .transpose()?;
+                            anyhow::Ok(BlameScope { paths, max_bytes: self.blame_max_bytes })
+                        })
+                        .transpose()?,
+                    merges_only: self.merges_only,
+                    include_merges: self.include_merges,
+                    pr_pattern: self.pr_pattern.clone(),
+                    excluded_paths: (!excluded_paths.is_empty())
+                        .then(|| build_glob_set(&excluded_paths))
+                        .transpose()?,
+                    measure_size: self.measure_size,
+                    dir_depth: Some(self.dir_depth.get()),
+                    excluded_message_body: config.excluded_message_body.unwrap_or_default(),
+                    excluded_messages,
+                },
+                store_options: StoreOptions {
+                    effort_formula: self.effort_formula,
+                    skip_summaries: self.skip_summaries || self.catalog,
+                    stats_only: self.output_stats_only,
+                    checkpoint_every: self.checkpoint_every.map(NonZeroUsize::get),
+                    write_order: self.write_order,
+                    bus_factor_threshold: self.bus_factor_threshold,
+                    activity_periods: self.activity_periods.clone(),
+                    build_collaboration: self.build_collaboration && !self.catalog,
+                    min_commits: self.min_commits,
+                    files_as_json: self.files_as_json,
+                    // the commits stored with other options are analyzed again with these ones
+                    on_conflict: match options_changed {
+                        true => OnConflict::Replace,
+                        false => self.on_conflict,
+                    },
+                    // replacing the stored commits, or storing no logs, needs the whole history
+                    incremental: !self.full
+                        && !options_changed
+                        && self.on_conflict != OnConflict::Replace
+                        && !self.output_stats_only,
+                    export_dot: self.export_dot.clone().filter(|_| !self.catalog),
+                    export_dot_max_commits: self.export_dot_max_commits,
+                    #[cfg(feature = "scripting")]
+                    script: self.script.as_deref().map(Script::load).transpose()?.map(Arc::new),
+                    output: (self.output_format != OutputFormat::Sqlite)
+                        .then(|| JsonWriter::new(self.output_format, self.output.as_deref()))
+                        .transpose()?
+                        .map(Arc::new),
+                    bundle,
+                },
+                repository_configs: config.repositories.unwrap_or_default(),
             },
         })
     }
 
-    fn get_directories_to_scan(
-        &self,
-    ) -> (Vec<PathBuf>, Vec<String>, Option<HashMap<String, String>>) {
+    /// The root directory to scan, or the repository given to --single.
+    fn root(&self) -> &Utf8Path {
+        // clap requires one of them
+        self.single
+            .as_deref()
+            .or(self.root.as_deref())
+            .expect("the root is required")
+    }
+
+    /// The appearance of the progress reporting, hidden by --quiet.
+    fn progress_mode(&self) -> ProgressMode {
+        if self.quiet {
+            ProgressMode::Quiet
+        } else {
+            self.progress_style
+        }
+    }
+
+    fn get_directories_to_scan(&self, config: &Config) -> (Vec<PathBuf>, Vec<String>) {
+        // the repository to debug is analyzed as is, without discovering or filtering anything
+        if let Some(path) = &self.single {
+            return (vec![path.clone().into()], vec![]);
+        }
         let mut ignored_repositories = Vec::new();
-        let config = &self.get_config();
 
         let directories = if self.recursive {
-            WalkDir::new(&self.root)
-                .max_depth(self.max_depth)
+            let spinner = progress::discovery_spinner(self.progress_mode());
+            let found = AtomicUsize::new(0);
+            // counts the visited entries, and prunes the git directories
+            let visit = |file_name: &OsStr| {
+                spinner.inc(1);
+                if file_name != ".git" {
+                    return true;
+                }
+                let found = found.fetch_add(1, Ordering::Relaxed) + 1;
+                spinner.set_message(format!("{found} repositories found"));
+                false // don't descend into git directories
+            };
+            let directories = scan::walk(
+                self.root().as_std_path(),
+                self.max_depth,
+                self.discovery_threads,
+                visit,
+            );
+            spinner.finish_and_clear();
+            directories
                 .into_iter()
-                .skip(1) // skip root directory
-                .filter_map(|e| e.ok())
-                .filter(|e| e.file_type().is_dir())
-                .filter(|e| {
-                    let name = e.file_name().to_string_lossy().to_string();
-                    if name == ".git" {
-                        return false;
-                    }
+                .filter(|path| {
+                    let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
                     if let Some(ir) = &config.ignored_repositories {
                         if ir.contains(&name) {
                             ignored_repositories.push(name);
@@ -125,72 +949,147 @@ impl GitRepositoryAnalyzer<Uninitialized> {
                     }
                     true
                 })
-                .map(|e| e.path().to_owned())
                 .collect::<Vec<_>>()
         } else {
-            vec![self.root.clone().into()]
+            vec![self.root().to_path_buf().into()]
+        };
+
+        let directories = match &self.directory_filter {
+            Some(filter) => directories.into_iter().filter(|path| filter(path)).collect(),
+            None => directories,
         };
 
-        (directories, ignored_repositories, config.author_map.clone())
+        let directories = match self.sample {
+            Some(fraction) => directories
+                .into_iter()
+                .filter(|path| {
+                    let relative = path.strip_prefix(self.root()).unwrap_or(path);
+                    let hash = stable_hash(relative.to_string_lossy().as_bytes(), self.sample_seed);
+                    (hash as f64 / u64::MAX as f64) < fraction
+                })
+                .collect(),
+            None => directories,
+        };
+
+        (directories, ignored_repositories)
     }
 
-    fn get_config(&self) -> Config {
+    /// Options which change the analyzed commits or their stored results, as a JSON object, to
+    /// tell whether the previous scan of the database used the same ones. `--since-days` is kept
+    /// as is, rather than the date it resolves to, which moves every day.
+    fn analysis_options(&self, config: &Config, excluded_paths: &[Glob]) -> String {
+        let author_map = config.author_map.iter().flatten().collect::<BTreeMap<_, _>>();
+        let revs = config
+            .repositories
+            .iter()
+            .flatten()
+            .map(|(name, repository)| (name, &repository.rev))
+            .collect::<BTreeMap<_, _>>();
+        json!({
+            "author_map": author_map,
+            "name_from_email": config.name_from_email,
+            "normalize_emails": config.normalize_emails,
+            "repositories": revs,
+            "working_hours": config.working_hours.as_ref().map(|hours| format!("{hours:?}")),
+            "excluded_message_patterns": config.excluded_message_patterns,
+            "excluded_message_anchored": config.excluded_message_anchored,
+            "excluded_message_body": config.excluded_message_body,
+            "excluded_paths": excluded_paths.iter().map(Glob::glob).collect::<Vec<_>>(),
+            "branch": self.branch,
+            "date": format!("{:?}", self.date),
+            "since": self.since,
+            "since_days": self.since_days,
+            "until": self.until,
+            "year": self.year,
+            "max_churn": self.max_churn,
+            "max_churn_mode": format!("{:?}", self.max_churn_mode),
+            "count_tree_files": self.count_tree_files,
+            "describe_commits": self.describe_commits,
+            "store_raw_identity": self.store_raw_identity,
+            "store_signer": self.store_signer,
+            "context_lines": self.context_lines,
+            "interhunk_lines": self.interhunk_lines,
+            "ignore_whitespace": self.ignore_whitespace,
+            "rename_threshold": self.rename_threshold,
+            "flag_large_blobs": self.flag_large_blobs,
+            "max_summary_width": self.max_summary_width,
+            "merges_only": self.merges_only,
+            "include_merges": self.include_merges,
+            "pr_pattern": self.pr_pattern.as_ref().map(Regex::as_str),
+            "dir_depth": self.dir_depth,
+            "effort_formula": format!("{:?}", self.effort_formula),
+            "files_as_json": self.files_as_json,
+            "skip_summaries": self.skip_summaries,
+        })
+        .to_string()
+    }
+
+    fn get_config(&self) -> Result<Config> {
         let config = &self.config;
         if config.exists() && config.is_file() {
-            toml::from_str(&std::fs::read_to_string(config).unwrap()).unwrap()
+            let format = self.config_format.unwrap_or_else(|| ConfigFormat::from_path(config));
+            Config::parse(&std::fs::read_to_string(config)?, format)
+                .with_context(|| format!("failed to parse {config}"))
         } else {
-            Config::default()
+            Ok(Config::default())
         }
     }
 
+    /// Applies --on-exists to the database file, before it is opened. The clear behavior is
+    /// applied in `prepare_database`, as it needs the tables.
+    fn check_existing_database(&self, database: &Path) -> Result<()> {
+        if !database.exists() {
+            return Ok(());
+        }
+        match self.on_exists {
+            OnExists::Append | OnExists::Clear => {}
+            OnExists::Fail => {
+                anyhow::bail!("{} already exists (--on-exists fail)", database.display())
+            }
+            OnExists::Backup => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+                let mut backup = database.as_os_str().to_owned();
+                backup.push(format!(".{}.bak", now.as_secs()));
+                std::fs::rename(database, &backup).with_context(|| {
+                    format!("failed to back up {} to {}", database.display(), backup.display())
+                })?;
+            }
+        }
+        Ok(())
+    }
+
     pub fn prepare_database(&self, pool: &Pool<SqliteConnectionManager>) -> Result<()> {
         let conn = pool.get()?;
 
-        conn.execute(
-            r#"
-        CREATE TABLE IF NOT EXISTS repositories (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            name TEXT NOT NULL,
-            url TEXT
-        )
-        "#,
-            [],
-        )?;
+        database::create_tables(&conn, self.nocase_authors)?;
+        database::write_metadata(&conn)?;
 
-        conn.execute(
-            r#"
-        CREATE TABLE IF NOT EXISTS logs (
-            commit_hash TEXT PRIMARY KEY,
-            author_name TEXT NOT NULL,
-            author_email TEXT NOT NULL,
-            message TEXT,
-            commit_datetime DATETIME NOT NULL,
-            insertions INTEGER,
-            deletions INTEGER,
-            repository_id INTEGER,
-            parent_hash TEXT,
-            FOREIGN KEY (repository_id) REFERENCES repositories (id)
-        )
-        "#,
-            [],
-        )?;
-
-        conn.execute(
-            r#"
-        CREATE TABLE IF NOT EXISTS changed_files (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            commit_hash TEXT NOT NULL,
-            file_path TEXT,
-            FOREIGN KEY (commit_hash) REFERENCES logs (commit_hash)
-        )
-        "#,
-            [],
-        )?;
-
-        if self.clear {
-            conn.execute("DELETE FROM repositories", [])?;
-            conn.execute("DELETE FROM logs", [])?;
+        // delete the referencing rows first to satisfy the foreign keys
+        if self.clear || self.on_exists == OnExists::Clear {
             conn.execute("DELETE FROM changed_files", [])?;
+            conn.execute("DELETE FROM commit_parents", [])?;
+            conn.execute("DELETE FROM commit_directories", [])?;
+            conn.execute("DELETE FROM commit_attributes", [])?;
+            conn.execute("DELETE FROM extension_stats", [])?;
+            conn.execute("DELETE FROM file_first_seen", [])?;
+            conn.execute("DELETE FROM file_latest", [])?;
+            conn.execute("DELETE FROM repository_health", [])?;
+            conn.execute("DELETE FROM activity", [])?;
+            conn.execute("DELETE FROM author_cadence", [])?;
+            conn.execute("DELETE FROM line_ownership", [])?;
+            conn.execute("DELETE FROM rewritten_commits", [])?;
+            conn.execute("DELETE FROM collaboration", [])?;
+            conn.execute("DELETE FROM ingest_checkpoints", [])?;
+            conn.execute("DELETE FROM reflog", [])?;
+            conn.execute("DELETE FROM branches", [])?;
+            conn.execute("DELETE FROM logs", [])?;
+            conn.execute("DELETE FROM repositories", [])?;
+        }
+
+        if self.fts {
+            if let Err(e) = database::create_fts(&conn) {
+                eprintln!("warning: full-text search index is not available: {e}");
+            }
         }
 
         Ok(())
@@ -198,156 +1097,640 @@ impl GitRepositoryAnalyzer<Uninitialized> {
 }
 
 impl GitRepositoryAnalyzer<Prepared> {
-    /// Analyze the git repositories and return the elapsed time in seconds, vec of analyzed repos,
-    /// and skipped directories
-    pub fn analyze(&self) -> Result<(f64, Vec<String>, Vec<String>)> {
-        let mut tasks = Vec::new();
-        let m = MultiProgress::new();
-
-        let overall_progress = m.add(ProgressBar::new(self.directories.len() as u64));
-        overall_progress.set_style(
-            ProgressStyle::with_template(
-                "{prefix:<30!.blue} [{bar:40.cyan/blue}] {pos:>3}/{len:3} [{elapsed_precise}]",
-            )
-            .unwrap()
-            .progress_chars("=> "),
-        );
-        overall_progress.set_prefix("OVERALL PROGRESS");
+    /// Analyze the git repositories and return the report of the analysis
+    pub fn analyze(&self) -> Result<AnalysisReport> {
+        let report = match self.single {
+            true => self.analyze_single(),
+            false => self.analyze_concurrently(),
+        };
+        // closed even if the analysis failed, e.g. with --fail-fast, so the output stays valid JSON
+        if let Some(output) = &self.store_options.output {
+            output.finish()?;
+        }
+        report
+    }
+
+    /// Analyzes and stores the repositories concurrently, on a runtime of its own.
+    fn analyze_concurrently(&self) -> Result<AnalysisReport> {
+        let deadline = self
+            .deadline
+            .map(|seconds| Instant::now() + Duration::from_secs(seconds));
+        // Size the overall progress to the directories which will actually be analyzed, as a
+        // recursive scan also lists every directory which is not a repository
+        let mut progress = Progress::new(self.progress_style, self.repositories.len() as u64);
+        if let Some(addr) = &self.progress_socket {
+            progress = progress.with_socket(addr);
+        }
 
-        tokio::runtime::Builder::new_multi_thread()
+        let unstored = tokio::runtime::Builder::new_multi_thread()
             .worker_threads(self.num_threads)
             .build()
             .unwrap()
             .block_on(async {
+                let semaphore = Arc::new(Semaphore::new(
+                    self.max_concurrent_repos
+                        .map_or(Semaphore::MAX_PERMITS, NonZeroUsize::get),
+                ));
+                let mut tasks = JoinSet::new();
                 for path in &self.directories {
-                    tasks.push(tokio::spawn(Self::exec(
-                        path.clone(),
-                        self.author_map.clone(),
-                        self.pool.clone(),
-                        m.clone(),
-                        overall_progress.clone(),
-                    )));
+                    let path = path.clone();
+                    let is_repository = self.repositories.contains(&path);
+                    let name = self.name_of(&path);
+                    let analyze_options = self.analyze_options_for(&path);
+                    let store_options = self.store_options.clone();
+                    let pools = self.pools.clone();
+                    let progress = progress.clone();
+                    let semaphore = semaphore.clone();
+                    // A task starts once a worker and a permit are free, so the deadline is
+                    // checked then, to let the in-flight tasks finish but start no more
+                    tasks.spawn(async move {
+                        let _permit = semaphore.acquire_owned().await.unwrap();
+                        match deadline {
+                            Some(deadline) if Instant::now() >= deadline => {
+                                if is_repository {
+                                    progress.unschedule();
+                                }
+                                (path, None)
+                            }
+                            _ => {
+                                // a panic, e.g. on an unexpected delta, fails the repository
+                                // instead of the whole scan
+                                let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                                    Self::exec(
+                                        path.clone(),
+                                        name,
+                                        analyze_options,
+                                        store_options,
+                                        pools,
+                                        progress.clone(),
+                                    )
+                                }))
+                                .unwrap_or_else(|payload| {
+                                    Err(anyhow!("panicked: {}", panic_message(&*payload)))
+                                });
+                                // whatever the outcome, the repository is done
+                                if is_repository {
+                                    progress.inc();
+                                }
+                                (path, Some(result))
+                            }
+                        }
+                    });
                 }
 
-                for task in tasks {
-                    task.await.unwrap();
+                // Dropping the join set on the first error aborts the remaining tasks
+                let mut unstored = Unstored::default();
+                while let Some(task) = tasks.join_next().await {
+                    let (path, result) = task.unwrap();
+                    match result {
+                        Some(Err(e)) if self.fail_fast => return Err(e),
+                        Some(Err(e)) => {
+                            unstored.failed.insert(path, e);
+                        }
+                        Some(Ok(Outcome::Stored)) => {}
+                        Some(Ok(Outcome::TooFewCommits)) => {
+                            unstored.too_few_commits.insert(path);
+                        }
+                        None => {
+                            unstored.not_started.insert(path);
+                        }
+                    }
                 }
-            });
+                Ok::<_, anyhow::Error>(unstored)
+            })?;
 
-        overall_progress.finish_and_clear();
-        let (analyzed_repositories, skipped_directories) = self.get_repositories()?;
-        Ok((
-            overall_progress.elapsed().as_millis() as f64 / 1000.0,
-            analyzed_repositories,
-            skipped_directories,
-        ))
+        self.report(&progress, unstored)
     }
 
-    /// Get the list of analyzed repositories and the list of directories ignored
-    fn get_repositories(&self) -> Result<(Vec<String>, Vec<String>)> {
-        let conn = self.pool.get()?;
-        let mut stmt = conn.prepare("SELECT name FROM repositories ORDER BY name")?;
-        let analyzed_repositories = stmt
-            .query_map(params![], |row| row.get::<_, String>(0))?
-            .filter_map(|name| name.ok())
-            .collect::<Vec<_>>();
+    /// Analyzes and stores the single repository synchronously, on the current thread. Each stage
+    /// is printed to stderr as a progress event, and the first error is returned as is, with its
+    /// full context, instead of skipping the repository.
+    fn analyze_single(&self) -> Result<AnalysisReport> {
+        let path = self.directories.first().context("no repository to analyze")?;
+        let progress = Progress::new(ProgressMode::Plain, 1);
+        let progress = match &self.progress_socket {
+            Some(addr) => progress.with_socket(addr),
+            None => progress.with_stderr(),
+        };
+        let outcome = Self::exec(
+            path.clone(),
+            self.name_of(path),
+            self.analyze_options_for(path),
+            self.store_options.clone(),
+            self.pools.clone(),
+            progress.clone(),
+        )?;
+        progress.inc();
+        let mut unstored = Unstored::default();
+        if outcome == Outcome::TooFewCommits {
+            unstored.too_few_commits.insert(path.clone());
+        }
+        self.report(&progress, unstored)
+    }
 
+    /// Finishes the progress, writes the database to stdout if requested, and reports the
+    /// repositories in the database and the directories which were not stored.
+    fn report(&self, progress: &Progress, mut unstored: Unstored) -> Result<AnalysisReport> {
+        let elapsed = progress.finish();
+        let analyzed_repositories = self.get_repositories()?;
+        let in_order = |paths: &HashSet<PathBuf>| {
+            self.directories
+                .iter()
+                .filter(|e| paths.contains(*e))
+                .map(|e| e.display().to_string())
+                .collect::<Vec<_>>()
+        };
         let skipped_directories = self
             .directories
             .iter()
-            .filter(|e| {
-                !analyzed_repositories
-                    .contains(&e.file_name().unwrap().to_string_lossy().to_string())
-            })
-            .map(|e| e.display().to_string())
+            .filter_map(|e| Some((e.display().to_string(), unstored.failed.remove(e)?)))
             .collect::<Vec<_>>();
+        let not_started_directories = in_order(&unstored.not_started);
+        let too_few_commits_directories = in_order(&unstored.too_few_commits);
+        let rewritten_repositories = match self.analyze_options.detect_rewrites {
+            true => self.get_rewritten_repositories()?,
+            false => Vec::new(),
+        };
+        // the JSON output only needed the repositories of the report from the database
+        if let (None, Some(path)) = (&self.store_options.output, &self.temp_database) {
+            io::copy(&mut File::open(path)?, &mut io::stdout().lock())?;
+        }
+        Ok(AnalysisReport {
+            elapsed,
+            analyzed_repositories,
+            skipped_directories,
+            not_started_directories,
+            too_few_commits_directories,
+            rewritten_repositories,
+        })
+    }
+
+    /// Whether the database, or the logs as JSON, are written to stdout, in which case any report
+    /// should go to stderr
+    pub fn writes_database_to_stdout(&self) -> bool {
+        match &self.store_options.output {
+            Some(output) => output.is_stdout(),
+            None => self.temp_database.is_some(),
+        }
+    }
+
+    /// Get the name of the repository at the path
+    fn name_of(&self, path: &Path) -> String {
+        match self.names.get(path) {
+            Some(name) => name.clone(),
+            None => path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+        }
+    }
+
+    /// Get the analyze options for the repository at the path, applying the per-repository
+    /// overrides from the configuration file
+    fn analyze_options_for(&self, path: &Path) -> AnalyzeOptions {
+        let mut options = self.analyze_options.clone();
+        if let Some(config) = self.repository_configs.get(&self.name_of(path)) {
+            if let Some(rev) = &config.rev {
+                options.rev = Some(rev.clone());
+            }
+        }
+        options
+    }
+
+    /// Get the list of analyzed repositories
+    fn get_repositories(&self) -> Result<Vec<String>> {
+        let mut analyzed_repositories = Vec::new();
+        for pool in &self.pools {
+            let conn = pool.get()?;
+            let mut stmt = conn.prepare("SELECT name FROM repositories")?;
+            analyzed_repositories.extend(
+                stmt.query_map(params![], |row| row.get::<_, String>(0))?
+                    .filter_map(|name| name.ok()),
+            );
+        }
+        analyzed_repositories.sort();
 
-        Ok((analyzed_repositories, skipped_directories))
+        Ok(analyzed_repositories)
     }
 
-    async fn exec(
+    /// Get the repositories with rewritten commits, with their number
+    fn get_rewritten_repositories(&self) -> Result<Vec<(String, usize)>> {
+        let mut rewritten_repositories = Vec::new();
+        for pool in &self.pools {
+            let conn = pool.get()?;
+            let mut stmt = conn.prepare(
+                r#"
+                SELECT repositories.name, COUNT(*) FROM rewritten_commits
+                JOIN repositories ON repositories.id = rewritten_commits.repository_id
+                GROUP BY repositories.name
+                "#,
+            )?;
+            rewritten_repositories.extend(
+                stmt.query_map(params![], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .filter_map(|row| row.ok()),
+            );
+        }
+        rewritten_repositories.sort();
+
+        Ok(rewritten_repositories)
+    }
+
+    /// Analyzes the repository at the path and stores it into its shard. Blocks the current thread,
+    /// so it is run as a task of its own in a parallel scan.
+    fn exec(
         path: PathBuf,
-        author_map: Option<HashMap<String, String>>,
-        pool: Pool<SqliteConnectionManager>,
-        m: MultiProgress,
-        overall_progress: ProgressBar,
-    ) {
-        let pb = m.add(ProgressBar::new(1));
-        pb.set_style(
-            ProgressStyle::with_template("{prefix:<30!} [{bar:40}] {pos:>3}/{len:3} {msg}")
-                .unwrap()
-                .progress_chars("-> "),
-        );
-        pb.set_prefix(format!("- {}", path.file_name().unwrap().to_string_lossy()));
+        name: String,
+        mut analyze_options: AnalyzeOptions,
+        store_options: StoreOptions,
+        pools: Vec<Pool<SqliteConnectionManager>>,
+        progress: Progress,
+    ) -> Result<Outcome> {
+        let pb = progress.add_repository(&name);
         pb.set_length(4); // opening, analyzing, storing (repo, logs), done
 
+        let display = path.display().to_string();
+        let event_name = name.clone();
         GitRepository::<crate::repository::Uninitialized>::try_new(path)
             .and_then(|uninitialized| {
                 pb.set_message("opening");
                 pb.inc(1);
-                uninitialized.open()
+                progress.event(&event_name, "opening", None);
+                uninitialized.with_name(name).open()
             })
             .and_then(|opened| {
                 pb.set_message("analyzing");
                 pb.inc(1);
-                opened.analyze(author_map)
+                progress.event(&event_name, "analyzing", None);
+                // in a block of its own, so the connection is not held during the analysis
+                {
+                    let name = opened.name(&analyze_options);
+                    let conn = pools[shard_of(&name, pools.len())].get()?;
+                    if store_options.incremental {
+                        analyze_options.previous_commit = database::analyzed_commit(&conn, &name)?
+                            .and_then(|hash| Oid::from_str(&hash).ok());
+                    }
+                    // resume an interrupted ingest after the commits it stored, without diffing
+                    // them again, unless they are to be replaced
+                    if store_options.output.is_none()
+                        && store_options.on_conflict != OnConflict::Replace
+                        && database::checkpoint(&conn, &name)?.is_some()
+                    {
+                        analyze_options.stored_commits = database::stored_commits(&conn, &name)?;
+                    }
+                }
+                opened.analyze(&analyze_options)
             })
             .and_then(|repo| {
-                overall_progress.inc(1);
+                let commit_count = repo.catalog().commit_count.unwrap_or(repo.logs().len());
+                let resumed = !analyze_options.stored_commits.is_empty();
+                // an incremental or resumed scan has no more than the new commits, of a repository
+                // which had enough before
+                if !repo.is_incremental() && !resumed && commit_count < store_options.min_commits {
+                    pb.finish_and_clear();
+                    progress.event(&event_name, "skipped", Some(commit_count));
+                    return Ok(Outcome::TooFewCommits);
+                }
                 pb.set_message("storing into repositories table");
                 pb.inc(1);
-                let mut conn = pool.get()?;
-                conn.execute(
-                    "INSERT OR IGNORE INTO repositories (name, url) VALUES (?1, ?2)",
-                    params![repo.name(), repo.url()],
-                )?;
+                // The shard is chosen by the final name, which may differ from the directory name
+                let mut conn = pools[shard_of(repo.name(), pools.len())].get()?;
+                database::insert_repository(&conn, repo.name(), repo.url(), repo.is_dirty())?;
+                match &store_options.bundle {
+                    // the clone is removed after the scan, unlike the bundle
+                    Some(bundle) => {
+                        let catalog = Catalog { path: bundle.clone(), ..repo.catalog().clone() };
+                        database::update_catalog(&conn, repo.name(), &catalog)?;
+                    }
+                    None => database::update_catalog(&conn, repo.name(), repo.catalog())?,
+                }
 
-                let tx = conn.transaction()?;
+                let logs = match store_options.write_order {
+                    WriteOrder::NewestFirst => repo.logs().iter().collect::<Vec<_>>(),
+                    WriteOrder::OldestFirst => repo.logs().iter().rev().collect(),
+                };
+                if let Some(output) = &store_options.output {
+                    pb.set_message(format!("writing {} logs", repo.logs().len()));
+                    pb.inc(1);
+                    output.write(repo.name(), repo.url(), &logs)?;
+                    pb.finish_and_clear();
+                    progress.event(&event_name, "done", Some(repo.logs().len()));
+                    return Ok(Outcome::Stored);
+                }
+
+                let mut tx = conn.transaction()?;
                 pb.set_message(format!("storing {} logs", repo.logs().len()));
                 pb.inc(1);
-                for log in repo.logs() {
-                    tx.execute(
-                        r#"
-                        INSERT INTO logs (
-                            commit_hash,
-                            parent_hash,
-                            author_name,
-                            author_email,
-                            commit_datetime,
-                            message,
-                            insertions,
-                            deletions,
-                            repository_id
-                        )
-                        VALUES (?, ?, ?, ?, ?, ?, ?, ?, (SELECT id FROM repositories WHERE name = ?));
-                        "#,
-                        params![
-                        log.commit_hash,
-                        log.parent_hash,
-                        log.author_name,
-                        log.author_email,
-                        log.commit_datetime,
-                        log.message,
-                        log.insertions as i64,
-                        log.deletions as i64,
-                        repo.name()
-                    ],
+                let mut extension_stats = ExtensionStats::default();
+                let mut file_first_seen = FileFirstSeen::default();
+                let mut file_latest = FileLatest::default();
+                // the commits stored before the interruption were not analyzed again, so they are
+                // summarized from the database, after the newer logs of the history walk
+                let stored_logs = match resumed && !store_options.skip_summaries {
+                    true => database::stored_logs(&tx, repo.name())?,
+                    false => Vec::new(),
+                };
+                for (i, log) in logs.into_iter().enumerate() {
+                    if !store_options.skip_summaries {
+                        extension_stats.add(log);
+                        file_first_seen.add(log);
+                        file_latest.add(log);
+                    }
+                    if store_options.stats_only {
+                        continue; // only summarized
+                    }
+                    if i % 100 == 0 {
+                        progress.event(&event_name, "storing", Some(i));
+                    }
+                    #[cfg(feature = "scripting")]
+                    let attributes = store_options
+                        .script
+                        .as_ref()
+                        .map(|script| script.attributes(log))
+                        .transpose()?;
+                    pb.set_message(format!("storing {} changed files", log.changed_files.len()));
+                    database::insert_log(
+                        &tx,
+                        repo.name(),
+                        log,
+                        store_options.effort_formula,
+                        store_options.files_as_json,
+                        store_options.on_conflict,
                     )?;
+                    #[cfg(feature = "scripting")]
+                    if let Some(attributes) = attributes {
+                        database::insert_commit_attributes(&tx, &log.commit_hash, &attributes)?;
+                    }
 
-                    pb.set_message(format!("storing {} changed files", log.changed_files.len()));
-                    for path in &log.changed_files {
-                        tx.execute(
-                            "INSERT INTO changed_files (commit_hash, file_path) VALUES (?1, ?2)",
-                            params![log.commit_hash, path],
-                        )?;
+                    if store_options.checkpoint_every.is_some_and(|n| (i + 1) % n == 0) {
+                        database::set_checkpoint(&tx, repo.name(), &log.commit_hash)?;
+                        tx.commit()?;
+                        tx = conn.transaction()?;
                     }
                 }
+                for log in &stored_logs {
+                    extension_stats.add(log);
+                    file_first_seen.add(log);
+                    file_latest.add(log);
+                }
+                database::clear_checkpoint(&tx, repo.name())?;
+                if (repo.is_incremental() || resumed) && !store_options.stats_only {
+                    database::flag_first_commits_for_authors(&tx, repo.name())?;
+                }
 
+                for entry in repo.reflog() {
+                    database::insert_reflog_entry(&tx, repo.name(), entry)?;
+                }
+                if analyze_options.branches && !analyze_options.catalog {
+                    database::replace_branches(&tx, repo.name(), repo.branches())?;
+                }
+                if analyze_options.blame.is_some() {
+                    database::replace_line_ownership(&tx, repo.name(), repo.line_ownership())?;
+                }
+                if let Some(reachable) = repo.reachable_commits() {
+                    database::record_rewritten_commits(&tx, repo.name(), reachable)?;
+                }
+
+                if !store_options.skip_summaries {
+                    pb.set_message("storing summaries");
+                    // a resumed scan summarized all the stored logs, including the previous ones
+                    let merge = repo.is_incremental() && !resumed;
+                    extension_stats.store(&tx, repo.name(), merge)?;
+                    // without the logs, from the logs in memory, and without the tables which
+                    // reference the logs
+                    let logs = match store_options.stats_only {
+                        true => repo.logs().iter().map(LogRow::of).collect(),
+                        false => {
+                            file_first_seen.store(&tx, repo.name(), merge)?;
+                            file_latest.store(&tx, repo.name(), merge)?;
+                            LogRow::stored(&tx, repo.name())?
+                        }
+                    };
+                    let threshold = store_options.bus_factor_threshold;
+                    RepositoryHealth::store(&tx, repo.name(), &logs, threshold)?;
+                    Activity::store(&tx, repo.name(), &logs, &store_options.activity_periods)?;
+                    AuthorCadence::store(&tx, repo.name(), &logs)?;
+                }
+                if store_options.build_collaboration {
+                    pb.set_message("building collaboration");
+                    Collaboration::store(&tx, repo.name())?;
+                }
+
+                // the next scan starts after this one, once it is complete
+                let analyzed_commit = repo
+                    .analyzed_commit()
+                    .filter(|_| !store_options.stats_only)
+                    .map(|oid| oid.to_string());
+                database::set_analyzed_commit(&tx, repo.name(), analyzed_commit.as_deref())?;
                 tx.commit()?;
+
+                if let Some(dir) = &store_options.export_dot {
+                    if repo.logs().len() <= store_options.export_dot_max_commits {
+                        pb.set_message("exporting commit graph");
+                        dot::write_dot(dir.as_std_path(), repo.name(), repo.logs()).with_context(
+                            || format!("failed to export the commit graph to {dir}"),
+                        )?;
+                    } else {
+                        pb.suspend(|| {
+                            eprintln!(
+                                "warning: skipped exporting the commit graph of {}: {} commits \
+                                 exceed --export-dot-max-commits",
+                                repo.name(),
+                                repo.logs().len()
+                            )
+                        });
+                    }
+                }
+
                 pb.set_message("done");
                 pb.finish_and_clear();
-                Ok(())
+                progress.event(&event_name, "done", Some(repo.logs().len()));
+                Ok(Outcome::Stored)
             })
-            .ok();
+            .inspect_err(|_| progress.event(&event_name, "failed", None))
+            .with_context(|| format!("failed to analyze {display}"))
+    }
+}
+
+/// Message of the payload of a panic, as passed to `panic!`.
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => payload
+            .downcast_ref::<String>()
+            .map_or("unknown cause", String::as_str),
+    }
+}
+
+/// Index of the shard of the repository with the name, among the shards.
+fn shard_of(name: &str, shards: usize) -> usize {
+    (stable_hash(name.as_bytes(), 0) % shards as u64) as usize
+}
+
+/// Path of the shard of the database, e.g. `repositories.0.db` for `repositories.db`.
+fn shard_path(database: &Utf8PathBuf, index: usize) -> Utf8PathBuf {
+    match database.extension() {
+        Some(extension) => database.with_extension(format!("{index}.{extension}")),
+        None => database.with_extension(index.to_string()),
+    }
+}
+
+/// Reads the path globs to exclude from a file, one per line. Blank lines and lines starting with
+/// `#` are skipped.
+fn read_excluded_paths(file: &Utf8PathBuf) -> Result<Vec<Glob>> {
+    let content =
+        std::fs::read_to_string(file).with_context(|| format!("failed to read {file}"))?;
+    let mut globs = Vec::new();
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        globs.push(Glob::new(line).with_context(|| format!("{file}:{}", number + 1))?);
+    }
+    Ok(globs)
+}
+
+/// Builds the set of the globs.
+fn build_glob_set(globs: &[Glob]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(glob.clone());
+    }
+    Ok(builder.build()?)
+}
+
+/// Parses a fraction between 0.0 and 1.0, inclusive.
+fn parse_fraction(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(fraction) if (0.0..=1.0).contains(&fraction) => Ok(fraction),
+        _ => Err(format!("invalid fraction `{s}`, expected 0.0 to 1.0")),
+    }
+}
+
+/// Parses a share over 0.0 and up to 1.0, such as the bus factor threshold, which counts no
+/// author at 0.0.
+pub(crate) fn parse_share(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(share) if share > 0.0 && share <= 1.0 => Ok(share),
+        _ => Err(format!("invalid share `{s}`, expected over 0.0 and up to 1.0")),
+    }
+}
+
+/// 64-bit FNV-1a hash of the seed and the bytes, with the MurmurHash3 finalizer to spread short
+/// inputs over the whole range. Unlike the hasher of the standard library, it is stable across Rust
+/// versions and platforms, so samples are reproducible.
+fn stable_hash(bytes: &[u8], seed: u64) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in seed.to_le_bytes().iter().chain(bytes) {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51afd7ed558ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ceb9fe1a85ec53);
+    hash ^ (hash >> 33)
+}
+
+/// Parses a `YYYY-MM-DD` date into the UNIX epoch of its midnight in UTC, or an RFC 3339 date
+/// time, such as `2024-01-31T12:00:00Z` or `2024-01-31T12:00:00.5+09:00`, into its UNIX epoch,
+/// truncated to the second.
+fn parse_date(s: &str) -> Result<i64, String> {
+    let invalid = || {
+        format!("invalid date `{s}`, expected YYYY-MM-DD or RFC 3339, e.g. 2024-01-31T12:00:00Z")
+    };
+    let (date, time) = match s.find(['T', 't', ' ']) {
+        Some(index) => (&s[..index], Some(&s[index + 1..])),
+        None => (s, None),
+    };
+    let seconds = match time {
+        Some(time) => parse_time(time).ok_or_else(invalid)?,
+        None => 0,
+    };
+    let mut parts = date
+        .splitn(3, '-')
+        .map(|part| part.parse::<i64>().map_err(|_| invalid()));
+    let (year, month, day) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(year), Some(month), Some(day)) => (year?, month?, day?),
+        _ => return Err(invalid()),
+    };
+    if !(1..=12).contains(&month) || !(1..=date::days_in_month(year, month)).contains(&day) {
+        return Err(invalid());
+    }
+    Ok(date::days_from_civil(year, month, day) * 86400 + seconds)
+}
+
+/// Same as [`parse_date`], but a date without time is the last second of the day in UTC, to
+/// include the whole day in a range.
+fn parse_end_date(s: &str) -> Result<i64, String> {
+    let epoch = parse_date(s)?;
+    match s.contains(['T', 't', ' ']) {
+        true => Ok(epoch),
+        false => Ok(epoch + 86399),
+    }
+}
+
+/// Parses the time of an RFC 3339 date time, `HH:MM:SS`, optionally with a fraction, then `Z` or
+/// an offset such as `+09:00`, into the seconds from the midnight in UTC. Negative, or over a day,
+/// when the offset crosses midnight.
+fn parse_time(s: &str) -> Option<i64> {
+    let (time, offset) = match s.find(['Z', 'z', '+', '-'])? {
+        index if s[index..].eq_ignore_ascii_case("z") => (&s[..index], 0),
+        index => {
+            let sign = if s[index..].starts_with('-') { -1 } else { 1 };
+            let (hours, minutes) = s[index + 1..].split_once(':')?;
+            let (hours, minutes) = (hours.parse::<i64>().ok()?, minutes.parse::<i64>().ok()?);
+            if !(0..=23).contains(&hours) || !(0..=59).contains(&minutes) {
+                return None;
+            }
+            (&s[..index], sign * (hours * 3600 + minutes * 60))
+        }
+    };
+    let time = time.split_once('.').map_or(time, |(time, _)| time); // drop the fraction
+    let mut parts = time.splitn(3, ':').map(|part| part.parse::<i64>().ok());
+    let (hours, minutes, seconds) = (parts.next()??, parts.next()??, parts.next()??);
+    if !(0..=23).contains(&hours) || !(0..=59).contains(&minutes) || !(0..=60).contains(&seconds) {
+        return None;
+    }
+    Some(hours * 3600 + minutes * 60 + seconds - offset)
+}
+
+/// Appends the submodules of the directories, recursively, as additional repositories named
+/// `super/submodule`. Repositories are deduplicated by their canonical path, which also prevents
+/// infinite recursion on cyclic submodule setups.
+fn add_submodules(directories: &mut Vec<PathBuf>, names: &mut HashMap<PathBuf, String>) {
+    let mut seen = directories
+        .iter()
+        .filter_map(|d| d.canonicalize().ok())
+        .collect::<HashSet<_>>();
+
+    let mut i = 0;
+    while i < directories.len() {
+        let directory = directories[i].clone();
+        i += 1;
+
+        let Ok(repo) = Repository::open(&directory) else {
+            continue;
+        };
+        let (Ok(submodules), Some(workdir)) = (repo.submodules(), repo.workdir()) else {
+            continue;
+        };
+        let parent = names.get(&directory).cloned().unwrap_or_else(|| {
+            directory
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string()
+        });
+
+        for submodule in submodules {
+            let path = workdir.join(submodule.path());
+            let Ok(canonical) = path.canonicalize() else {
+                continue; // not checked out
+            };
+            if Repository::open(&path).is_err() || !seen.insert(canonical) {
+                continue;
+            }
+            let name = submodule.path().file_name().unwrap_or_default().to_string_lossy();
+            names.insert(path.clone(), format!("{parent}/{name}"));
+            directories.push(path);
+        }
     }
 }
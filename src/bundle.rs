@@ -0,0 +1,69 @@
+/// Support for git bundles (`.bundle` files) as the root, to analyze archived repositories
+/// without a checkout. libgit2 can't read bundles, so they are cloned with the `git` command.
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::PathBuf,
+    process::Command,
+};
+
+use anyhow::{Context, Result};
+use camino::Utf8Path;
+use tempfile::TempDir;
+
+/// Whether the path is a git bundle, by its extension.
+pub fn is_bundle(path: &Utf8Path) -> bool {
+    path.extension() == Some("bundle") && path.is_file()
+}
+
+/// Clones the bundle into a bare repository in a temporary directory, named after the bundle,
+/// e.g. `widget` for `widget.bundle`. The repository is removed when the returned directory is
+/// dropped. Fails if the bundle is incremental, as its prerequisite commits are not available.
+pub fn unbundle(bundle: &Utf8Path) -> Result<(TempDir, PathBuf)> {
+    let prerequisites = prerequisites(bundle)?;
+    anyhow::ensure!(
+        prerequisites.is_empty(),
+        "{bundle} is an incremental bundle, which requires the prerequisite commits {} from \
+         another bundle or clone; create a complete bundle, e.g. with `git bundle create <file> \
+         --all`",
+        prerequisites.join(", ")
+    );
+
+    let dir = TempDir::new()?;
+    let repo = dir.path().join(bundle.file_stem().unwrap_or("bundle"));
+    let output = Command::new("git")
+        .args(["clone", "--bare", "--quiet"])
+        .arg(bundle.as_std_path())
+        .arg(&repo)
+        .output()
+        .context("failed to run git to unbundle")?;
+    anyhow::ensure!(
+        output.status.success(),
+        "failed to unbundle {bundle}: {}",
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    Ok((dir, repo))
+}
+
+/// Prerequisite commits listed in the header of the bundle, as `-<oid> <comment>` lines.
+fn prerequisites(bundle: &Utf8Path) -> Result<Vec<String>> {
+    let mut reader = BufReader::new(File::open(bundle)?);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    anyhow::ensure!(
+        line.starts_with("# v") && line.contains("git bundle"),
+        "{bundle} is not a git bundle"
+    );
+
+    let mut prerequisites = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+            break; // the header ends with an empty line before the pack
+        }
+        if let Some(prerequisite) = line.strip_prefix('-') {
+            prerequisites.extend(prerequisite.split_whitespace().next().map(str::to_string));
+        }
+    }
+    Ok(prerequisites)
+}
@@ -0,0 +1,35 @@
+use clap::{Parser, Subcommand};
+
+use crate::{
+    analyzer::Uninitialized, diff::DiffArgs, extract::ExtractArgs, import::ImportArgs,
+    remap::RemapArgs,
+};
+
+/// Command line interface. Without a command, scans the repositories under the root directory.
+#[derive(Parser)]
+#[command(about, version, args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub scan: Option<Uninitialized>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Import a `git log` dump in CSV or TSV format, without any git repository
+    Import(ImportArgs),
+
+    /// Re-attribute the logs of an existing database to canonical authors, without any git
+    /// repository
+    RemapAuthors(RemapArgs),
+
+    /// Copy the rows of a single repository from an existing database into a new database, e.g.
+    /// to share its history
+    Extract(ExtractArgs),
+
+    /// Compare the logs of two databases, e.g. a fresh scan against a previous one, and report the
+    /// added and removed commits, new authors, and churn delta of each repository
+    Diff(DiffArgs),
+}
@@ -1,6 +1,43 @@
 use std::collections::HashMap;
 
-use serde::Deserialize;
+use anyhow::{Context, Result};
+use camino::Utf8Path;
+use clap::ValueEnum;
+use regex::RegexSet;
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::date;
+
+/// Format of the configuration file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ConfigFormat {
+    /// TOML, the default
+    Toml,
+    /// JSON
+    Json,
+    /// YAML
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// Detects the format from the extension of the path. TOML if unknown.
+    pub fn from_path(path: &Utf8Path) -> Self {
+        match path.extension() {
+            Some("json") => Self::Json,
+            Some("yaml" | "yml") => Self::Yaml,
+            _ => Self::Toml,
+        }
+    }
+
+    /// Deserializes the string in the format.
+    pub fn deserialize<T: DeserializeOwned>(self, s: &str) -> Result<T> {
+        Ok(match self {
+            ConfigFormat::Toml => toml::from_str(s)?,
+            ConfigFormat::Json => serde_json::from_str(s)?,
+            ConfigFormat::Yaml => serde_yaml::from_str(s)?,
+        })
+    }
+}
 
 /// Configuration file structure
 #[derive(Debug, Default, Deserialize)]
@@ -10,4 +47,152 @@ pub struct Config {
 
     /// Email address and user name map to normalize the author name
     pub author_map: Option<HashMap<String, String>>,
+
+    /// Derive the author name from the email address when it is not in the `author_map`
+    pub name_from_email: Option<bool>,
+
+    /// Trim and lowercase the author and committer email addresses, and collapse the whitespace of
+    /// the names, before the `author_map` lookup and the storage
+    pub normalize_emails: Option<bool>,
+
+    /// Per-repository overrides, keyed by the directory name of the repository
+    pub repositories: Option<HashMap<String, RepositoryConfig>>,
+
+    /// Working hours window to classify commits made outside of it as off hours
+    pub working_hours: Option<WorkingHours>,
+
+    /// Regular expressions of the messages of the commits to skip, such as release commits
+    pub excluded_message_patterns: Option<Vec<String>>,
+
+    /// Whether the patterns must match the whole message, instead of any part of it
+    pub excluded_message_anchored: Option<bool>,
+
+    /// Whether the patterns are matched against the full message, instead of the summary only
+    pub excluded_message_body: Option<bool>,
+}
+
+impl Config {
+    /// Parses the configuration in the format.
+    pub fn parse(s: &str, format: ConfigFormat) -> Result<Self> {
+        format.deserialize(s)
+    }
+
+    /// Compiles the `excluded_message_patterns`, anchored to the whole message if
+    /// `excluded_message_anchored` is set. `None` if there are no patterns.
+    pub fn excluded_messages(&self) -> Result<Option<RegexSet>> {
+        let Some(patterns) = self.excluded_message_patterns.as_ref().filter(|p| !p.is_empty())
+        else {
+            return Ok(None);
+        };
+        let patterns = patterns.iter().map(|pattern| match self.excluded_message_anchored {
+            Some(true) => format!("^(?:{pattern})$"),
+            _ => pattern.clone(),
+        });
+        RegexSet::new(patterns)
+            .map(Some)
+            .context("invalid excluded_message_patterns")
+    }
+}
+
+/// Per-repository configuration, which takes precedence over the command line options
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct RepositoryConfig {
+    /// Revision (branch, tag, or commit) to start the analysis from
+    #[serde(alias = "branch")]
+    pub rev: Option<String>,
+}
+
+/// Working hours window in the local time of the commit. A commit is off hours if it is made
+/// before `start`, at or after `end`, or on a weekend day.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "UncheckedWorkingHours")]
+pub struct WorkingHours {
+    /// Hour of the day (0-23) when the working hours start
+    pub start: u32,
+
+    /// Hour of the day (1-24) when the working hours end
+    pub end: u32,
+
+    /// Days of the week which are not working days
+    pub weekend: Vec<Weekday>,
+}
+
+impl Default for WorkingHours {
+    fn default() -> Self {
+        Self {
+            start: 8,
+            end: 18,
+            weekend: vec![Weekday::Saturday, Weekday::Sunday],
+        }
+    }
+}
+
+/// Working hours as written in the configuration file, before their window is validated.
+#[derive(Deserialize)]
+#[serde(default)]
+struct UncheckedWorkingHours {
+    start: u32,
+    end: u32,
+    weekend: Vec<Weekday>,
+}
+
+impl Default for UncheckedWorkingHours {
+    fn default() -> Self {
+        let WorkingHours { start, end, weekend } = WorkingHours::default();
+        Self { start, end, weekend }
+    }
+}
+
+impl TryFrom<UncheckedWorkingHours> for WorkingHours {
+    type Error = String;
+
+    /// Rejects a window out of the hours of the day, or which ends before it starts.
+    fn try_from(hours: UncheckedWorkingHours) -> Result<Self, Self::Error> {
+        let UncheckedWorkingHours { start, end, weekend } = hours;
+        if start > 23 {
+            return Err(format!("working hours start at {start}, not an hour of the day (0-23)"));
+        }
+        if end > 24 {
+            return Err(format!("working hours end at {end}, not an hour of the day (1-24)"));
+        }
+        if start >= end {
+            return Err(format!("working hours end at {end}, not after their start at {start}"));
+        }
+        Ok(Self { start, end, weekend })
+    }
+}
+
+impl WorkingHours {
+    /// Whether the time, in UNIX epoch with the timezone offset in minutes, is off hours.
+    pub fn is_off_hours(&self, seconds: i64, offset_minutes: i32) -> bool {
+        let local = seconds + offset_minutes as i64 * 60;
+        let hour = (local.rem_euclid(86400) / 3600) as u32;
+        let weekday = date::weekday_from_days(local.div_euclid(86400));
+        self.weekend.contains(&weekday) || hour < self.start || hour >= self.end
+    }
+}
+
+/// Day of the week
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Weekday {
+    Sunday,
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+}
+
+impl Weekday {
+    pub(crate) const ALL: [Weekday; 7] = [
+        Self::Sunday,
+        Self::Monday,
+        Self::Tuesday,
+        Self::Wednesday,
+        Self::Thursday,
+        Self::Friday,
+        Self::Saturday,
+    ];
 }
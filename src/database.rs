@@ -0,0 +1,1113 @@
+/// Database schema and statements, shared by the analysis and the other commands.
+use std::{
+    collections::HashSet,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use git2::Oid;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::json;
+
+use crate::{
+    log::{Branch, ChangeStatus, ChangedFile, EffortFormula, GitLog, LineOwnership, ReflogEntry},
+    repository::Catalog,
+};
+
+/// Opens the database with the same settings as the pooled connections of the analysis.
+pub fn open(path: impl AsRef<Path>) -> Result<Connection> {
+    let mut conn = Connection::open(path)?;
+    init_connection(&mut conn)?;
+    Ok(conn)
+}
+
+/// Initializes each connection. SQLite doesn't enforce the declared foreign keys unless enabled
+/// per connection, so the inserts must respect the order repositories -> logs -> changed_files.
+pub fn init_connection(conn: &mut Connection) -> rusqlite::Result<()> {
+    conn.execute_batch("PRAGMA foreign_keys = ON;")
+}
+
+/// Where SQLite stores its temporary tables and indices, e.g. of the sorts of large queries.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TempStore {
+    /// Compile-time default of SQLite, usually a temporary file
+    #[default]
+    Default,
+    /// In memory
+    Memory,
+}
+
+/// What to do when a stored commit is inserted again, e.g. when the same repository is scanned
+/// again without `--clear`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OnConflict {
+    /// Keep the stored commit and skip the new one, so only the new commits are inserted
+    #[default]
+    Ignore,
+    /// Delete the stored commit, with its changed files, and insert the new one
+    Replace,
+    /// Fail the repository
+    Fail,
+}
+
+/// Performance pragmas of the pooled connections, for large ingests on machines with plenty of
+/// memory. Applied after [`init_connection`], before any insert.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Tuning {
+    /// Maximum number of bytes of the database to access with memory-mapped I/O. SQLite caps it
+    /// to its compile-time maximum, and leaves its default when `None`.
+    pub mmap_size: Option<u64>,
+    /// Where SQLite stores its temporary tables and indices. Only set when not the default, so
+    /// the compile-time choice of SQLite is kept otherwise.
+    pub temp_store: TempStore,
+}
+
+impl Tuning {
+    /// Initializes the connection, then applies the pragmas.
+    pub fn init_connection(&self, conn: &mut Connection) -> rusqlite::Result<()> {
+        init_connection(conn)?;
+        if let Some(mmap_size) = self.mmap_size {
+            // the pragma is an i64, which the CLI validates
+            conn.pragma_update(None, "mmap_size", mmap_size as i64)?;
+        }
+        if self.temp_store == TempStore::Memory {
+            conn.pragma_update(None, "temp_store", "MEMORY")?;
+        }
+        Ok(())
+    }
+}
+
+/// Creates the tables if they don't exist, and upgrades the existing ones with [`migrate`]. With
+/// `nocase_authors`, the author name and email columns of a newly created logs table compare
+/// case-insensitively. A column added to a table must also be added to the migrations.
+pub fn create_tables(conn: &Connection, nocase_authors: bool) -> Result<()> {
+    let author_collation = if nocase_authors { " COLLATE NOCASE" } else { "" };
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS repositories (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            url TEXT,
+            is_dirty BOOLEAN,
+            path TEXT,
+            default_branch TEXT,
+            head_commit TEXT,
+            commit_count INTEGER,
+            size_bytes INTEGER,
+            analyzed_commit TEXT
+        )
+        "#,
+        [],
+    )?;
+
+    conn.execute(
+        &format!(
+            r#"
+        CREATE TABLE IF NOT EXISTS logs (
+            commit_hash TEXT PRIMARY KEY,
+            short_hash TEXT,
+            author_name TEXT NOT NULL{author_collation},
+            author_email TEXT NOT NULL{author_collation},
+            message TEXT,
+            message_encoding TEXT,
+            message_char_length INTEGER,
+            message_line_count INTEGER,
+            summary_too_long BOOLEAN,
+            pr_number INTEGER,
+            commit_datetime DATETIME NOT NULL,
+            timezone_offset INTEGER,
+            time_skew_seconds INTEGER,
+            off_hours BOOLEAN,
+            is_first_commit_for_author BOOLEAN,
+            nearest_tag TEXT,
+            insertions INTEGER,
+            deletions INTEGER,
+            effort_score REAL,
+            churn_ratio REAL,
+            repository_id INTEGER,
+            parent_hash TEXT,
+            parent_count INTEGER,
+            over_max_churn BOOLEAN,
+            avg_rename_similarity REAL,
+            tree_file_count INTEGER,
+            generation INTEGER,
+            raw_author TEXT,
+            raw_committer TEXT,
+            signer_email TEXT,
+            changed_files_json TEXT,
+            FOREIGN KEY (repository_id) REFERENCES repositories (id)
+        )
+        "#
+        ),
+        [],
+    )?;
+
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS changed_files (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            commit_hash TEXT NOT NULL,
+            file_path TEXT,
+            status TEXT,
+            old_path TEXT,
+            insertions INTEGER,
+            deletions INTEGER,
+            is_new_file BOOLEAN,
+            large_blob BOOLEAN,
+            blob_size INTEGER,
+            is_binary BOOLEAN,
+            FOREIGN KEY (commit_hash) REFERENCES logs (commit_hash)
+        )
+        "#,
+        [],
+    )?;
+
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS commit_parents (
+            commit_hash TEXT NOT NULL,
+            parent_hash TEXT NOT NULL,
+            parent_index INTEGER NOT NULL,
+            PRIMARY KEY (commit_hash, parent_index),
+            FOREIGN KEY (commit_hash) REFERENCES logs (commit_hash)
+        )
+        "#,
+        [],
+    )?;
+
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS extension_stats (
+            repository_id INTEGER NOT NULL,
+            extension TEXT NOT NULL,
+            commit_count INTEGER NOT NULL,
+            insertions INTEGER NOT NULL,
+            deletions INTEGER NOT NULL,
+            PRIMARY KEY (repository_id, extension),
+            FOREIGN KEY (repository_id) REFERENCES repositories (id)
+        )
+        "#,
+        [],
+    )?;
+
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS file_first_seen (
+            repository_id INTEGER NOT NULL,
+            file_path TEXT NOT NULL,
+            commit_hash TEXT NOT NULL,
+            PRIMARY KEY (repository_id, file_path),
+            FOREIGN KEY (repository_id) REFERENCES repositories (id),
+            FOREIGN KEY (commit_hash) REFERENCES logs (commit_hash)
+        )
+        "#,
+        [],
+    )?;
+
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS file_latest (
+            repository_id INTEGER NOT NULL,
+            file_path TEXT NOT NULL,
+            last_commit_hash TEXT NOT NULL,
+            last_author TEXT NOT NULL,
+            last_datetime DATETIME NOT NULL,
+            PRIMARY KEY (repository_id, file_path),
+            FOREIGN KEY (repository_id) REFERENCES repositories (id),
+            FOREIGN KEY (last_commit_hash) REFERENCES logs (commit_hash)
+        )
+        "#,
+        [],
+    )?;
+
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS repository_health (
+            repository_id INTEGER PRIMARY KEY,
+            bus_factor INTEGER NOT NULL,
+            author_count INTEGER NOT NULL,
+            gini REAL NOT NULL,
+            FOREIGN KEY (repository_id) REFERENCES repositories (id)
+        )
+        "#,
+        [],
+    )?;
+
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS author_cadence (
+            repository_id INTEGER NOT NULL,
+            author TEXT NOT NULL,
+            median_gap_days REAL,
+            mean_gap_days REAL,
+            streak_max_days INTEGER NOT NULL,
+            PRIMARY KEY (repository_id, author),
+            FOREIGN KEY (repository_id) REFERENCES repositories (id)
+        )
+        "#,
+        [],
+    )?;
+
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS line_ownership (
+            repository_id INTEGER NOT NULL,
+            file_path TEXT NOT NULL,
+            author TEXT NOT NULL,
+            lines INTEGER NOT NULL,
+            PRIMARY KEY (repository_id, file_path, author),
+            FOREIGN KEY (repository_id) REFERENCES repositories (id)
+        )
+        "#,
+        [],
+    )?;
+
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS rewritten_commits (
+            repository_id INTEGER NOT NULL,
+            commit_hash TEXT NOT NULL,
+            detected_at DATETIME NOT NULL,
+            PRIMARY KEY (repository_id, commit_hash),
+            FOREIGN KEY (repository_id) REFERENCES repositories (id)
+        )
+        "#,
+        [],
+    )?;
+
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS activity (
+            repository_id INTEGER NOT NULL,
+            period_type TEXT NOT NULL,
+            period_start DATE NOT NULL,
+            commit_count INTEGER NOT NULL,
+            insertions INTEGER NOT NULL,
+            deletions INTEGER NOT NULL,
+            author_count INTEGER NOT NULL,
+            PRIMARY KEY (repository_id, period_type, period_start),
+            FOREIGN KEY (repository_id) REFERENCES repositories (id)
+        )
+        "#,
+        [],
+    )?;
+
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS commit_directories (
+            commit_hash TEXT NOT NULL,
+            directory TEXT NOT NULL,
+            file_count INTEGER NOT NULL,
+            PRIMARY KEY (commit_hash, directory),
+            FOREIGN KEY (commit_hash) REFERENCES logs (commit_hash)
+        )
+        "#,
+        [],
+    )?;
+
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS commit_attributes (
+            commit_hash TEXT NOT NULL,
+            key TEXT NOT NULL,
+            value TEXT,
+            PRIMARY KEY (commit_hash, key),
+            FOREIGN KEY (commit_hash) REFERENCES logs (commit_hash)
+        )
+        "#,
+        [],
+    )?;
+
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS collaboration (
+            repository_id INTEGER NOT NULL,
+            author_a TEXT NOT NULL,
+            author_b TEXT NOT NULL,
+            shared_files INTEGER NOT NULL,
+            PRIMARY KEY (repository_id, author_a, author_b),
+            FOREIGN KEY (repository_id) REFERENCES repositories (id)
+        )
+        "#,
+        [],
+    )?;
+
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS ingest_checkpoints (
+            repository_id INTEGER PRIMARY KEY,
+            last_commit_hash TEXT NOT NULL,
+            FOREIGN KEY (repository_id) REFERENCES repositories (id)
+        )
+        "#,
+        [],
+    )?;
+
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS reflog (
+            repository_id INTEGER NOT NULL,
+            ref TEXT NOT NULL,
+            old_oid TEXT NOT NULL,
+            new_oid TEXT NOT NULL,
+            committer_name TEXT,
+            committer_email TEXT,
+            timestamp DATETIME NOT NULL,
+            message TEXT,
+            FOREIGN KEY (repository_id) REFERENCES repositories (id)
+        )
+        "#,
+        [],
+    )?;
+
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS branches (
+            repository_id INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            tip_commit TEXT NOT NULL,
+            merge_base TEXT,
+            PRIMARY KEY (repository_id, name),
+            FOREIGN KEY (repository_id) REFERENCES repositories (id)
+        )
+        "#,
+        [],
+    )?;
+
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS metadata (
+            key TEXT PRIMARY KEY,
+            value TEXT
+        )
+        "#,
+        [],
+    )?;
+
+    migrate(conn)?;
+
+    // index the columns the reports join and filter on, which are not primary keys
+    conn.execute_batch(
+        r#"
+        CREATE INDEX IF NOT EXISTS idx_logs_repository_id ON logs (repository_id);
+        CREATE INDEX IF NOT EXISTS idx_logs_author_email ON logs (author_email);
+        CREATE INDEX IF NOT EXISTS idx_logs_commit_datetime ON logs (commit_datetime);
+        CREATE INDEX IF NOT EXISTS idx_changed_files_commit_hash ON changed_files (commit_hash);
+        "#,
+    )?;
+
+    Ok(())
+}
+
+/// Columns added to the tables since the first schema, in order, as (table, column definition).
+/// The schema version of a database is the number of them it has.
+const MIGRATIONS: &[(&str, &str)] = &[
+    ("logs", "effort_score REAL"),
+    ("logs", "parent_count INTEGER"),
+    ("logs", "timezone_offset INTEGER"),
+    ("logs", "off_hours BOOLEAN"),
+    ("repositories", "is_dirty BOOLEAN"),
+    ("logs", "over_max_churn BOOLEAN"),
+    ("logs", "tree_file_count INTEGER"),
+    ("logs", "generation INTEGER"),
+    ("logs", "raw_author TEXT"),
+    ("logs", "raw_committer TEXT"),
+    ("logs", "short_hash TEXT"),
+    ("logs", "message_char_length INTEGER"),
+    ("logs", "message_line_count INTEGER"),
+    ("changed_files", "is_new_file BOOLEAN"),
+    ("logs", "signer_email TEXT"),
+    ("changed_files", "large_blob BOOLEAN"),
+    ("changed_files", "blob_size INTEGER"),
+    ("changed_files", "is_binary BOOLEAN"),
+    ("logs", "summary_too_long BOOLEAN"),
+    ("repositories", "path TEXT"),
+    ("repositories", "default_branch TEXT"),
+    ("repositories", "head_commit TEXT"),
+    ("repositories", "commit_count INTEGER"),
+    ("logs", "time_skew_seconds INTEGER"),
+    ("logs", "pr_number INTEGER"),
+    ("logs", "avg_rename_similarity REAL"),
+    ("repositories", "size_bytes INTEGER"),
+    ("logs", "changed_files_json TEXT"),
+    ("logs", "is_first_commit_for_author BOOLEAN"),
+    ("logs", "message_encoding TEXT"),
+    ("logs", "churn_ratio REAL"),
+    ("logs", "nearest_tag TEXT"),
+    ("repositories", "analyzed_commit TEXT"),
+    ("changed_files", "insertions INTEGER"),
+    ("changed_files", "deletions INTEGER"),
+    ("changed_files", "status TEXT"),
+    ("changed_files", "old_path TEXT"),
+];
+
+/// Upgrades the tables of a database written by an earlier version in place, by adding the
+/// columns of the migrations after its schema version, and records the current version in the
+/// `schema_version` table. A database without a version, written before it was recorded, may have
+/// any of the columns already, so only the missing ones are added. Fails on a database written by
+/// a later version.
+pub fn migrate(conn: &Connection) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("CREATE TABLE IF NOT EXISTS main.schema_version (version INTEGER NOT NULL)", [])?;
+    let version = tx
+        .query_row("SELECT MAX(version) FROM main.schema_version", [], |row| {
+            row.get::<_, Option<usize>>(0)
+        })?
+        .unwrap_or(0);
+    anyhow::ensure!(
+        version <= MIGRATIONS.len(),
+        "the database has the schema version {version}, written by a later version than this one \
+         ({})",
+        MIGRATIONS.len()
+    );
+    for (table, column) in &MIGRATIONS[version..] {
+        let name = column.split_whitespace().next().unwrap_or_default();
+        let exists = tx
+            .prepare("SELECT 1 FROM pragma_table_info(?1, 'main') WHERE name = ?2")?
+            .exists(params![table, name])?;
+        if !exists {
+            tx.execute(&format!("ALTER TABLE main.{table} ADD COLUMN {column}"), [])?;
+        }
+    }
+    tx.execute("DELETE FROM main.schema_version", [])?;
+    tx.execute("INSERT INTO main.schema_version (version) VALUES (?1)", [MIGRATIONS.len() as i64])?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Stamps the database with the provenance of the run writing into it: `tool_version`,
+/// `libgit2_version`, and `args`, the command line arguments as a JSON array, replacing the ones of
+/// the previous run, and `created_at`, the UTC date time of the first run, which is kept.
+pub fn write_metadata(conn: &Connection) -> Result<()> {
+    let (major, minor, patch) = git2::Version::get().libgit2_version();
+    let args = serde_json::to_string(&std::env::args().collect::<Vec<_>>())?;
+    for (key, value) in [
+        ("tool_version", env!("CARGO_PKG_VERSION").to_string()),
+        ("libgit2_version", format!("{major}.{minor}.{patch}")),
+        ("args", args),
+    ] {
+        conn.execute(
+            "INSERT OR REPLACE INTO metadata (key, value) VALUES (?1, ?2)",
+            params![key, value],
+        )?;
+    }
+    conn.execute(
+        "INSERT OR IGNORE INTO metadata (key, value) VALUES ('created_at', datetime('now'))",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Records the options of the analysis as `analysis_options`, replacing the ones of the previous
+/// run. Returns whether a previous run recorded different ones.
+pub fn replace_analysis_options(conn: &Connection, options: &str) -> Result<bool> {
+    let previous = conn
+        .query_row("SELECT value FROM metadata WHERE key = 'analysis_options'", [], |row| {
+            row.get::<_, String>(0)
+        })
+        .optional()?;
+    conn.execute(
+        "INSERT OR REPLACE INTO metadata (key, value) VALUES ('analysis_options', ?)",
+        params![options],
+    )?;
+    Ok(previous.is_some_and(|previous| previous != options))
+}
+
+/// Creates the logs_fts FTS5 table mirroring the commit messages, kept in sync with the logs
+/// table by triggers. Fails if SQLite is built without FTS5.
+pub fn create_fts(conn: &Connection) -> Result<()> {
+    let exists = conn
+        .prepare("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'logs_fts'")?
+        .exists([])?;
+
+    conn.execute_batch(
+        r#"
+        CREATE VIRTUAL TABLE IF NOT EXISTS logs_fts USING fts5 (
+            commit_hash UNINDEXED,
+            message,
+            content = 'logs',
+            content_rowid = 'rowid'
+        );
+
+        CREATE TRIGGER IF NOT EXISTS logs_fts_insert AFTER INSERT ON logs BEGIN
+            INSERT INTO logs_fts (rowid, commit_hash, message)
+            VALUES (new.rowid, new.commit_hash, new.message);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS logs_fts_delete AFTER DELETE ON logs BEGIN
+            INSERT INTO logs_fts (logs_fts, rowid, commit_hash, message)
+            VALUES ('delete', old.rowid, old.commit_hash, old.message);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS logs_fts_update AFTER UPDATE ON logs BEGIN
+            INSERT INTO logs_fts (logs_fts, rowid, commit_hash, message)
+            VALUES ('delete', old.rowid, old.commit_hash, old.message);
+            INSERT INTO logs_fts (rowid, commit_hash, message)
+            VALUES (new.rowid, new.commit_hash, new.message);
+        END;
+        "#,
+    )?;
+
+    // index the logs stored before the table was created
+    if !exists {
+        conn.execute("INSERT INTO logs_fts (logs_fts) VALUES ('rebuild')", [])?;
+    }
+
+    Ok(())
+}
+
+/// Deletes the commit with the hash, if stored, with its rows in the per-commit tables and the
+/// files it first added in `file_first_seen` or last changed in `file_latest`, which are stored
+/// again with the summaries.
+fn delete_log(conn: &Connection, commit_hash: &str) -> Result<()> {
+    // delete the referencing rows first to satisfy the foreign keys
+    conn.execute("DELETE FROM file_latest WHERE last_commit_hash = ?", params![commit_hash])?;
+    for table in [
+        "changed_files",
+        "commit_parents",
+        "commit_directories",
+        "commit_attributes",
+        "file_first_seen",
+        "logs",
+    ] {
+        conn.execute(&format!("DELETE FROM {table} WHERE commit_hash = ?"), params![commit_hash])?;
+    }
+    Ok(())
+}
+
+/// Inserts the attributes of the stored commit, replacing the values of the existing keys.
+pub fn insert_commit_attributes(
+    conn: &Connection,
+    commit_hash: &str,
+    attributes: &[(String, String)],
+) -> Result<()> {
+    for (key, value) in attributes {
+        conn.execute(
+            "INSERT OR REPLACE INTO commit_attributes (commit_hash, key, value) VALUES (?1, ?2, ?3)",
+            params![commit_hash, key, value],
+        )?;
+    }
+    Ok(())
+}
+
+/// Inserts the repository, if a repository with the same name doesn't exist.
+pub fn insert_repository(
+    conn: &Connection,
+    name: &str,
+    url: &str,
+    is_dirty: Option<bool>,
+) -> Result<()> {
+    conn.execute(
+        r#"
+        INSERT INTO repositories (name, url, is_dirty)
+        SELECT ?1, ?2, ?3
+        WHERE NOT EXISTS (SELECT 1 FROM repositories WHERE name = ?1)
+        "#,
+        params![name, url, is_dirty],
+    )?;
+    Ok(())
+}
+
+/// Updates the metadata of the repository with the name.
+pub fn update_catalog(conn: &Connection, name: &str, catalog: &Catalog) -> Result<()> {
+    conn.execute(
+        r#"
+        UPDATE repositories
+        SET path = ?2, default_branch = ?3, head_commit = ?4, commit_count = ?5, size_bytes = ?6
+        WHERE name = ?1
+        "#,
+        params![
+            name,
+            catalog.path,
+            catalog.default_branch,
+            catalog.head_commit,
+            catalog.commit_count.map(|count| count as i64),
+            catalog.size_bytes.map(|size| size as i64)
+        ],
+    )?;
+    Ok(())
+}
+
+/// Inserts the log, its parent edges, and its changed files, linked to the repository with the
+/// name. A root commit has no parent edges. The churn of a log over the maximum churn is NULL.
+/// With `files_as_json`, the changed files are also stored as a JSON array on the log.
+pub fn insert_log(
+    conn: &Connection,
+    repository_name: &str,
+    log: &GitLog,
+    effort_formula: EffortFormula,
+    files_as_json: bool,
+    on_conflict: OnConflict,
+) -> Result<()> {
+    let has_churn = !log.over_max_churn;
+    let changed_files_json = files_as_json
+        .then(|| {
+            let files = log
+                .changed_files
+                .iter()
+                .map(|file| {
+                    json!({
+                        "path": file.path,
+                        "insertions": file.insertions,
+                        "deletions": file.deletions,
+                        "is_new": file.is_new,
+                    })
+                })
+                .collect::<Vec<_>>();
+            serde_json::to_string(&files)
+        })
+        .transpose()?;
+    if on_conflict == OnConflict::Replace {
+        // in the transaction of the caller, so the previous changed files are never orphaned nor
+        // mixed with the new ones
+        delete_log(conn, &log.commit_hash)?;
+    }
+    let or_ignore = if on_conflict == OnConflict::Ignore { "OR IGNORE " } else { "" };
+    let inserted = conn.execute(
+        &format!(
+            r#"
+        INSERT {or_ignore}INTO logs (
+            commit_hash,
+            parent_hash,
+            parent_count,
+            author_name,
+            author_email,
+            commit_datetime,
+            timezone_offset,
+            off_hours,
+            message,
+            insertions,
+            deletions,
+            effort_score,
+            churn_ratio,
+            over_max_churn,
+            tree_file_count,
+            generation,
+            raw_author,
+            raw_committer,
+            short_hash,
+            message_char_length,
+            message_line_count,
+            signer_email,
+            summary_too_long,
+            time_skew_seconds,
+            pr_number,
+            avg_rename_similarity,
+            changed_files_json,
+            is_first_commit_for_author,
+            message_encoding,
+            nearest_tag,
+            repository_id
+        )
+        VALUES (
+            ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?,
+            (SELECT id FROM repositories WHERE name = ?)
+        );
+        "#
+        ),
+        params![
+            log.commit_hash,
+            log.parent_hash,
+            log.parent_count() as i64,
+            log.author_name,
+            log.author_email,
+            log.commit_datetime,
+            log.timezone_offset,
+            log.off_hours,
+            log.message,
+            has_churn.then_some(log.insertions as i64),
+            has_churn.then_some(log.deletions as i64),
+            has_churn.then(|| log.effort_score(effort_formula)),
+            log.churn_ratio().filter(|_| has_churn),
+            log.over_max_churn,
+            log.tree_file_count.map(|count| count as i64),
+            log.generation.map(|generation| generation as i64),
+            log.raw_author,
+            log.raw_committer,
+            log.short_hash,
+            log.message_char_length as i64,
+            log.message_line_count as i64,
+            log.signer_email,
+            log.summary_too_long,
+            log.time_skew_seconds,
+            log.pr_number.map(|number| number as i64),
+            log.avg_rename_similarity,
+            changed_files_json,
+            log.is_first_commit_for_author,
+            log.message_encoding,
+            log.nearest_tag,
+            repository_name
+        ],
+    )?;
+    if inserted == 0 {
+        return Ok(()); // ignored, the stored commit keeps its rows
+    }
+
+    for (index, parent_hash) in log.parent_hashes.iter().enumerate() {
+        conn.execute(
+            "INSERT INTO commit_parents (commit_hash, parent_hash, parent_index) VALUES (?1, ?2, ?3)",
+            params![log.commit_hash, parent_hash, index as i64],
+        )?;
+    }
+
+    for (directory, file_count) in &log.directories {
+        conn.execute(
+            "INSERT INTO commit_directories (commit_hash, directory, file_count) VALUES (?1, ?2, ?3)",
+            params![log.commit_hash, directory, *file_count as i64],
+        )?;
+    }
+
+    for file in &log.changed_files {
+        conn.execute(
+            r#"
+            INSERT INTO changed_files (
+                commit_hash,
+                file_path,
+                status,
+                old_path,
+                insertions,
+                deletions,
+                is_new_file,
+                large_blob,
+                blob_size,
+                is_binary
+            )
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+            "#,
+            params![
+                log.commit_hash,
+                file.path,
+                file.status.map(|status| status.as_str()),
+                file.old_path,
+                has_churn.then_some(file.insertions as i64),
+                has_churn.then_some(file.deletions as i64),
+                file.is_new,
+                file.large_blob_size.is_some(),
+                file.large_blob_size.map(|size| size as i64),
+                file.large_blob_is_binary
+            ],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Inserts the reflog entry, linked to the repository with the name.
+pub fn insert_reflog_entry(
+    conn: &Connection,
+    repository_name: &str,
+    entry: &ReflogEntry,
+) -> Result<()> {
+    conn.execute(
+        r#"
+        INSERT INTO reflog (
+            repository_id,
+            ref,
+            old_oid,
+            new_oid,
+            committer_name,
+            committer_email,
+            timestamp,
+            message
+        )
+        VALUES ((SELECT id FROM repositories WHERE name = ?), ?, ?, ?, ?, ?, ?, ?);
+        "#,
+        params![
+            repository_name,
+            entry.refname,
+            entry.old_oid,
+            entry.new_oid,
+            entry.committer_name,
+            entry.committer_email,
+            entry.timestamp,
+            entry.message
+        ],
+    )?;
+    Ok(())
+}
+
+/// Replaces the branches of the repository with the name, as they are a snapshot of its refs at
+/// the time of the scan.
+pub fn replace_branches(
+    conn: &Connection,
+    repository_name: &str,
+    branches: &[Branch],
+) -> Result<()> {
+    conn.execute(
+        "DELETE FROM branches WHERE repository_id = (SELECT id FROM repositories WHERE name = ?)",
+        params![repository_name],
+    )?;
+    for branch in branches {
+        conn.execute(
+            r#"
+            INSERT INTO branches (repository_id, name, tip_commit, merge_base)
+            VALUES ((SELECT id FROM repositories WHERE name = ?), ?, ?, ?);
+            "#,
+            params![repository_name, branch.name, branch.tip_commit, branch.merge_base],
+        )?;
+    }
+    Ok(())
+}
+
+/// Replaces the line ownership of the repository with the name, as it is a snapshot of the
+/// analyzed revision.
+pub fn replace_line_ownership(
+    conn: &Connection,
+    repository_name: &str,
+    ownership: &[LineOwnership],
+) -> Result<()> {
+    conn.execute(
+        "DELETE FROM line_ownership WHERE repository_id = (SELECT id FROM repositories WHERE name = ?)",
+        params![repository_name],
+    )?;
+    for entry in ownership {
+        conn.execute(
+            r#"
+            INSERT INTO line_ownership (repository_id, file_path, author, lines)
+            VALUES ((SELECT id FROM repositories WHERE name = ?), ?, ?, ?);
+            "#,
+            params![repository_name, entry.file_path, entry.author, entry.lines as i64],
+        )?;
+    }
+    Ok(())
+}
+
+/// Records the stored commits of the repository with the name which are no longer reachable,
+/// e.g. rewritten by a force push, in the rewritten_commits table, keeping the time they were
+/// first detected, and forgets those reachable again. Their logs are kept. Returns the number of
+/// unreachable commits.
+pub fn record_rewritten_commits(
+    conn: &Connection,
+    repository_name: &str,
+    reachable: &HashSet<Oid>,
+) -> Result<usize> {
+    let stored = conn
+        .prepare(
+            "SELECT commit_hash FROM logs WHERE repository_id = (SELECT id FROM repositories WHERE name = ?)",
+        )?
+        .query_map(params![repository_name], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    let mut unreachable = 0;
+    for commit_hash in stored {
+        if Oid::from_str(&commit_hash).is_ok_and(|oid| reachable.contains(&oid)) {
+            conn.execute(
+                r#"
+                DELETE FROM rewritten_commits
+                WHERE repository_id = (SELECT id FROM repositories WHERE name = ?)
+                    AND commit_hash = ?
+                "#,
+                params![repository_name, commit_hash],
+            )?;
+        } else {
+            conn.execute(
+                r#"
+                INSERT OR IGNORE INTO rewritten_commits (repository_id, commit_hash, detected_at)
+                VALUES ((SELECT id FROM repositories WHERE name = ?), ?, ?);
+                "#,
+                params![repository_name, commit_hash, now],
+            )?;
+            unreachable += 1;
+        }
+    }
+    Ok(unreachable)
+}
+
+/// Commit the last complete scan of the repository with the name started from, if any.
+pub fn analyzed_commit(conn: &Connection, repository_name: &str) -> Result<Option<String>> {
+    Ok(conn
+        .query_row(
+            "SELECT analyzed_commit FROM repositories WHERE name = ?",
+            params![repository_name],
+            |row| row.get(0),
+        )
+        .optional()?
+        .flatten())
+}
+
+/// Records the commit the scan of the repository with the name started from, once its logs and
+/// summaries are stored, or clears it if they weren't.
+pub fn set_analyzed_commit(
+    conn: &Connection,
+    repository_name: &str,
+    commit_hash: Option<&str>,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE repositories SET analyzed_commit = ?2 WHERE name = ?1",
+        params![repository_name, commit_hash],
+    )?;
+    Ok(())
+}
+
+/// Flags the earliest stored commit, by commit time then generation, of each author name of the
+/// repository with the name, and unflags the others, for the logs stored by several scans.
+pub fn flag_first_commits_for_authors(conn: &Connection, repository_name: &str) -> Result<()> {
+    conn.execute(
+        r#"
+        UPDATE logs
+        SET is_first_commit_for_author = commit_hash IN (
+            SELECT commit_hash FROM (
+                SELECT
+                    commit_hash,
+                    ROW_NUMBER() OVER (
+                        PARTITION BY author_name ORDER BY commit_datetime, generation
+                    ) AS rank
+                FROM logs
+                WHERE repository_id = (SELECT id FROM repositories WHERE name = ?1)
+            )
+            WHERE rank = 1
+        )
+        WHERE repository_id = (SELECT id FROM repositories WHERE name = ?1)
+        "#,
+        params![repository_name],
+    )?;
+    Ok(())
+}
+
+/// Last commit stored by an interrupted ingest of the repository with the name, if any.
+pub fn checkpoint(conn: &Connection, repository_name: &str) -> Result<Option<String>> {
+    Ok(conn
+        .query_row(
+            r#"
+            SELECT last_commit_hash FROM ingest_checkpoints
+            WHERE repository_id = (SELECT id FROM repositories WHERE name = ?)
+            "#,
+            params![repository_name],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+/// Hashes of the stored logs of the repository with the name.
+pub fn stored_commits(conn: &Connection, repository_name: &str) -> Result<HashSet<Oid>> {
+    let mut stmt = conn.prepare(
+        "SELECT commit_hash FROM logs WHERE repository_id = (SELECT id FROM repositories WHERE name = ?)",
+    )?;
+    let hashes = stmt
+        .query_map(params![repository_name], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(hashes.iter().filter_map(|hash| Oid::from_str(hash).ok()).collect())
+}
+
+/// Reads back the stored logs of the repository with the name and their changed files, newest
+/// first, with only the fields the summaries accumulate. The other fields are left to their
+/// defaults.
+pub fn stored_logs(conn: &Connection, repository_name: &str) -> Result<Vec<GitLog>> {
+    let mut logs = conn
+        .prepare(
+            r#"
+            SELECT commit_hash, author_name, commit_datetime, over_max_churn FROM logs
+            WHERE repository_id = (SELECT id FROM repositories WHERE name = ?)
+            ORDER BY commit_datetime DESC
+            "#,
+        )?
+        .query_map(params![repository_name], |row| {
+            Ok(GitLog {
+                commit_hash: row.get(0)?,
+                author_name: row.get(1)?,
+                commit_datetime: row.get(2)?,
+                over_max_churn: row.get::<_, Option<bool>>(3)?.unwrap_or_default(),
+                ..GitLog::default()
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT file_path, status, old_path, insertions, deletions, is_new_file FROM changed_files
+        WHERE commit_hash = ?
+        ORDER BY id
+        "#,
+    )?;
+    for log in &mut logs {
+        log.changed_files = stmt
+            .query_map(params![log.commit_hash], |row| {
+                Ok(ChangedFile {
+                    path: row.get(0)?,
+                    status: row
+                        .get::<_, Option<String>>(1)?
+                        .as_deref()
+                        .and_then(ChangeStatus::from_name),
+                    old_path: row.get(2)?,
+                    insertions: row.get::<_, Option<i64>>(3)?.unwrap_or_default() as usize,
+                    deletions: row.get::<_, Option<i64>>(4)?.unwrap_or_default() as usize,
+                    is_new: row.get(5)?,
+                    ..ChangedFile::default()
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+    }
+    Ok(logs)
+}
+
+/// Records the last stored commit of the repository with the name.
+pub fn set_checkpoint(conn: &Connection, repository_name: &str, commit_hash: &str) -> Result<()> {
+    conn.execute(
+        r#"
+        INSERT OR REPLACE INTO ingest_checkpoints (repository_id, last_commit_hash)
+        VALUES ((SELECT id FROM repositories WHERE name = ?), ?)
+        "#,
+        params![repository_name, commit_hash],
+    )?;
+    Ok(())
+}
+
+/// Removes the checkpoint of the repository with the name, once its ingest is complete.
+pub fn clear_checkpoint(conn: &Connection, repository_name: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM ingest_checkpoints WHERE repository_id = (SELECT id FROM repositories WHERE name = ?)",
+        params![repository_name],
+    )?;
+    Ok(())
+}
+
+/// Deletes the repositories whose stored `path` no longer exists on disk, with all their rows,
+/// each in a single transaction. Repositories without a path, such as imported ones, are kept.
+/// Returns the names of the deleted repositories.
+pub fn prune_missing(conn: &mut Connection) -> Result<Vec<String>> {
+    let missing = conn
+        .prepare("SELECT id, name, path FROM repositories WHERE path IS NOT NULL ORDER BY name")?
+        .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get(1)?, row.get::<_, String>(2)?)))?
+        .filter_map(|row| row.ok())
+        .filter(|(_, _, path)| !Path::new(path).exists())
+        .map(|(id, name, _)| (id, name))
+        .collect::<Vec<(i64, String)>>();
+
+    for (id, _) in &missing {
+        let tx = conn.transaction()?;
+        // delete the referencing rows first to satisfy the foreign keys
+        for table in ["changed_files", "commit_parents", "commit_directories", "commit_attributes"]
+        {
+            tx.execute(
+                &format!(
+                    "DELETE FROM {table} WHERE commit_hash IN (SELECT commit_hash FROM logs WHERE repository_id = ?)"
+                ),
+                params![id],
+            )?;
+        }
+        for table in [
+            "extension_stats",
+            "file_first_seen",
+            "file_latest",
+            "repository_health",
+            "activity",
+            "author_cadence",
+            "line_ownership",
+            "rewritten_commits",
+            "collaboration",
+            "ingest_checkpoints",
+            "reflog",
+            "branches",
+            "logs",
+        ] {
+            tx.execute(&format!("DELETE FROM {table} WHERE repository_id = ?"), params![id])?;
+        }
+        tx.execute("DELETE FROM repositories WHERE id = ?", params![id])?;
+        tx.commit()?;
+    }
+    Ok(missing.into_iter().map(|(_, name)| name).collect())
+}
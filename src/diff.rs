@@ -0,0 +1,163 @@
+/// Compares the logs of two databases, e.g. a fresh scan against the previous nightly one, to
+/// report the activity in between and catch rewritten histories.
+use anyhow::Result;
+use camino::Utf8PathBuf;
+use clap::{Args, ValueEnum};
+use rusqlite::params;
+use serde::Serialize;
+
+use crate::database;
+
+#[derive(Args)]
+pub struct DiffArgs {
+    /// Path to the previous database
+    #[arg()]
+    pub old: Utf8PathBuf,
+
+    /// Path to the new database
+    #[arg()]
+    pub new: Utf8PathBuf,
+
+    /// Output format of the report
+    #[arg(long, value_enum, default_value_t)]
+    pub format: DiffFormat,
+}
+
+/// Output format of the report of a diff.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DiffFormat {
+    /// One line per repository
+    #[default]
+    Text,
+    /// JSON array of the repositories
+    Json,
+}
+
+/// Presence of a repository in the two databases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Presence {
+    /// Only in the new database
+    Added,
+    /// Only in the old database
+    Removed,
+    /// In both databases
+    Both,
+}
+
+/// Changes of a repository between the two databases.
+#[derive(Debug, Serialize)]
+pub struct RepositoryDiff {
+    /// Name of the repository
+    pub name: String,
+
+    /// Presence of the repository in the two databases
+    pub presence: Presence,
+
+    /// Number of commits only in the new database
+    pub added_commits: usize,
+
+    /// Number of commits only in the old database, e.g. rewritten by a force push
+    pub removed_commits: usize,
+
+    /// Author names of the new database who have no commit in the old one
+    pub new_authors: Vec<String>,
+
+    /// Churn (insertions + deletions) of the new database minus the old one
+    pub churn_delta: i64,
+}
+
+/// Compares the logs of each repository, by name, of the two databases, without writing to
+/// either. A repository in only one of them compares to no logs.
+pub fn diff(args: &DiffArgs) -> Result<Vec<RepositoryDiff>> {
+    anyhow::ensure!(args.old.exists(), "{} does not exist", args.old);
+    anyhow::ensure!(args.new.exists(), "{} does not exist", args.new);
+
+    let conn = database::open(&args.new)?;
+    conn.execute("ATTACH DATABASE ?1 AS previous", [args.old.as_str()])?;
+
+    let names = conn
+        .prepare(
+            r#"
+            SELECT name FROM main.repositories
+            UNION
+            SELECT name FROM previous.repositories
+            ORDER BY name
+            "#,
+        )?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    // the logs of the repository in the old (before) and the new (after) database
+    let logs = r#"
+        WITH
+            before AS (
+                SELECT logs.* FROM previous.logs
+                JOIN previous.repositories ON repositories.id = logs.repository_id
+                WHERE repositories.name = ?1
+            ),
+            after AS (
+                SELECT logs.* FROM main.logs
+                JOIN main.repositories ON repositories.id = logs.repository_id
+                WHERE repositories.name = ?1
+            )
+    "#;
+
+    let mut diffs = Vec::new();
+    for name in names {
+        let (in_old, in_new, added_commits, removed_commits, churn_delta) = conn.query_row(
+            &format!(
+                r#"
+                {logs}
+                SELECT
+                    EXISTS (SELECT 1 FROM previous.repositories WHERE name = ?1),
+                    EXISTS (SELECT 1 FROM main.repositories WHERE name = ?1),
+                    (SELECT COUNT(*) FROM after
+                        WHERE commit_hash NOT IN (SELECT commit_hash FROM before)),
+                    (SELECT COUNT(*) FROM before
+                        WHERE commit_hash NOT IN (SELECT commit_hash FROM after)),
+                    (SELECT COALESCE(SUM(COALESCE(insertions, 0) + COALESCE(deletions, 0)), 0)
+                        FROM after)
+                    - (SELECT COALESCE(SUM(COALESCE(insertions, 0) + COALESCE(deletions, 0)), 0)
+                        FROM before)
+                "#
+            ),
+            params![name],
+            |row| {
+                Ok((
+                    row.get::<_, bool>(0)?,
+                    row.get::<_, bool>(1)?,
+                    row.get::<_, usize>(2)?,
+                    row.get::<_, usize>(3)?,
+                    row.get::<_, i64>(4)?,
+                ))
+            },
+        )?;
+        let new_authors = conn
+            .prepare(&format!(
+                r#"
+                {logs}
+                SELECT DISTINCT author_name FROM after
+                WHERE author_name NOT IN (SELECT author_name FROM before)
+                ORDER BY author_name
+                "#
+            ))?
+            .query_map(params![name], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let presence = match (in_old, in_new) {
+            (true, true) => Presence::Both,
+            (false, _) => Presence::Added,
+            (_, false) => Presence::Removed,
+        };
+        diffs.push(RepositoryDiff {
+            name,
+            presence,
+            added_commits,
+            removed_commits,
+            new_authors,
+            churn_delta,
+        });
+    }
+    Ok(diffs)
+}
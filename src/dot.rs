@@ -0,0 +1,84 @@
+/// GraphViz export of the commit graph of a repository.
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::log::GitLog;
+
+/// Writes the commit graph of the logs as `<dir>/<name>.dot`.
+/// Each commit is a node labeled with its short hash and author, with an edge to each parent.
+/// Parents which are not among the logs, such as merge commits, are unlabeled nodes.
+pub fn write_dot(dir: &Path, name: &str, logs: &[GitLog]) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    // names derived from remotes and submodules contain `/`
+    let path = dir.join(format!("{}.dot", name.replace('/', "_")));
+    let mut out = io::BufWriter::new(fs::File::create(&path)?);
+
+    writeln!(out, "digraph \"{}\" {{", escape(name))?;
+    writeln!(out, "    node [shape=box];")?;
+    for log in logs {
+        let short_hash = log.short_hash.as_deref().unwrap_or(&log.commit_hash[..7]);
+        writeln!(
+            out,
+            "    \"{}\" [label=\"{}\\n{}\"];",
+            log.commit_hash,
+            short_hash,
+            escape(&log.author_name)
+        )?;
+        for parent_hash in &log.parent_hashes {
+            writeln!(out, "    \"{}\" -> \"{}\";", log.commit_hash, parent_hash)?;
+        }
+    }
+    writeln!(out, "}}")?;
+    out.flush()
+}
+
+/// Escapes the string for a quoted DOT ID.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn graph_has_a_node_per_log_and_an_edge_per_parent() {
+        let dir = tempfile::tempdir().unwrap();
+        let (root, merge) = ("a".repeat(40), "b".repeat(40));
+        let logs = [
+            GitLog {
+                commit_hash: merge.clone(),
+                short_hash: Some("bbbbbbb".into()),
+                author_name: r#"Jane "JD" Doe\"#.into(),
+                parent_hashes: vec![root.clone(), "c".repeat(40)],
+                ..Default::default()
+            },
+            GitLog {
+                commit_hash: root.clone(),
+                author_name: "Bob".into(),
+                ..Default::default()
+            },
+        ];
+
+        write_dot(dir.path(), "org/widget", &logs).unwrap();
+
+        let dot = fs::read_to_string(dir.path().join("org_widget.dot")).unwrap();
+        let c = "c".repeat(40);
+        assert_eq!(
+            dot,
+            format!(
+                r#"digraph "org/widget" {{
+    node [shape=box];
+    "{merge}" [label="bbbbbbb\nJane \"JD\" Doe\\"];
+    "{merge}" -> "{root}";
+    "{merge}" -> "{c}";
+    "{root}" [label="aaaaaaa\nBob"];
+}}
+"#
+            )
+        );
+    }
+}
@@ -0,0 +1,119 @@
+/// Copies the rows of a single repository from an existing database into a new one, to share
+/// its history without the whole database.
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+use camino::Utf8PathBuf;
+use clap::Args;
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::database;
+
+#[derive(Args)]
+pub struct ExtractArgs {
+    /// Name of the repository to extract, as stored in the repositories table
+    #[arg()]
+    pub repository: String,
+
+    /// Path to the new database, which must not exist
+    #[arg(short, long)]
+    pub output: Utf8PathBuf,
+
+    /// Path to the source database
+    #[arg(short, long, default_value = "repositories.db")]
+    pub database: Utf8PathBuf,
+}
+
+/// Creates the output database with the schema of the tool, attaches the source database, and
+/// copies the rows of the repository table by table, in a single transaction. Rows are selected by
+/// their `repository_id`, or by the `commit_hash` of the logs of the repository for the per-commit
+/// tables; the tables with neither, such as metadata, are copied as a whole, except the schema
+/// version, which is the one of the new database. Only the columns
+/// present in both databases are copied, so a database written by an older version can be
+/// extracted too. Returns the number of copied rows per table.
+pub fn extract(args: &ExtractArgs) -> Result<BTreeMap<String, usize>> {
+    anyhow::ensure!(args.database.exists(), "{} does not exist", args.database);
+    anyhow::ensure!(!args.output.exists(), "{} already exists", args.output);
+
+    let mut conn = database::open(&args.output)?;
+    conn.execute("ATTACH DATABASE ?1 AS source", [args.database.as_str()])?;
+
+    let id = conn
+        .query_row(
+            "SELECT id FROM source.repositories WHERE name = ?1",
+            [&args.repository],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()?
+        .with_context(|| format!("{} is not in {}", args.repository, args.database))?;
+
+    let logs_sql = conn
+        .query_row("SELECT sql FROM source.sqlite_master WHERE name = 'logs'", [], |row| {
+            row.get::<_, String>(0)
+        })
+        .optional()?
+        .unwrap_or_default();
+    database::create_tables(&conn, logs_sql.contains("COLLATE NOCASE"))?;
+    let has_fts = conn
+        .prepare("SELECT 1 FROM source.sqlite_master WHERE type = 'table' AND name = 'logs_fts'")?
+        .exists([])?;
+    if has_fts {
+        database::create_fts(&conn)?;
+    }
+
+    let tables = conn
+        .prepare(
+            r#"
+            SELECT name FROM source.sqlite_master
+            WHERE type = 'table' AND name NOT LIKE 'sqlite_%' AND name NOT LIKE 'logs_fts%'
+                AND name != 'schema_version'
+            ORDER BY rowid
+            "#,
+        )?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let tx = conn.transaction()?;
+    let mut copied = BTreeMap::new();
+    for table in tables {
+        let source_columns = columns(&tx, &table, "source")?;
+        let columns = columns(&tx, &table, "main")?
+            .into_iter()
+            .filter(|column| source_columns.contains(column))
+            .map(|column| format!("\"{column}\""))
+            .collect::<Vec<_>>();
+        if columns.is_empty() {
+            continue; // not a table of the tool
+        }
+        let filter = if table == "repositories" {
+            format!("WHERE id = {id}")
+        } else if source_columns.iter().any(|column| column == "repository_id") {
+            format!("WHERE repository_id = {id}")
+        } else if source_columns.iter().any(|column| column == "commit_hash") {
+            format!(
+                "WHERE commit_hash IN (SELECT commit_hash FROM source.logs WHERE repository_id = {id})"
+            )
+        } else {
+            String::new()
+        };
+        let columns = columns.join(", ");
+        let count = tx.execute(
+            &format!(
+                "INSERT INTO main.\"{table}\" ({columns}) SELECT {columns} FROM source.\"{table}\" {filter}"
+            ),
+            [],
+        )?;
+        copied.insert(table, count);
+    }
+    tx.commit()?;
+    conn.execute("DETACH DATABASE source", [])?;
+    Ok(copied)
+}
+
+/// Returns the column names of the table in the schema, empty if the table doesn't exist.
+fn columns(conn: &Connection, table: &str, schema: &str) -> Result<Vec<String>> {
+    Ok(conn
+        .prepare("SELECT name FROM pragma_table_info(?1, ?2)")?
+        .query_map(params![table, schema], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?)
+}
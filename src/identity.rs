@@ -0,0 +1,52 @@
+/// Normalization of author identities.
+use std::iter;
+
+/// Email local parts which don't look like a name of a person.
+const NON_NAME_LOCAL_PARTS: [&str; 9] =
+    ["noreply", "no-reply", "root", "admin", "git", "bot", "ci", "build", "user"];
+
+/// Normalizes the email address for grouping: trimmed and lowercased, e.g. `  Jane@Example.COM `
+/// is `jane@example.com`.
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// Collapses the runs of whitespace of the name into single spaces and trims it, keeping its case,
+/// e.g. ` Jane   Doe` is `Jane Doe`.
+pub fn collapse_whitespace(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Derives a canonical name from the local part of the email address, e.g. `jane.doe@x.com` is
+/// `Jane Doe`. Any `+tag` suffix is removed, the rest is split on `.` and `_`, and each part is
+/// title-cased. Returns `None` for obviously non-name local parts such as `noreply`, for
+/// addresses of `noreply` domains, and for local parts containing digits.
+pub fn name_from_email(email: &str) -> Option<String> {
+    let (local, domain) = email.trim().split_once('@')?;
+    let local = local.split('+').next().unwrap_or_default().to_lowercase();
+
+    if domain.to_lowercase().contains("noreply")
+        || NON_NAME_LOCAL_PARTS.contains(&local.as_str())
+        || local.chars().any(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let name = local
+        .split(['.', '_'])
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            let first = chars.next()?;
+            Some(
+                iter::once(first)
+                    .flat_map(char::to_uppercase)
+                    .chain(chars)
+                    .collect::<String>(),
+            )
+        })
+        .collect::<Option<Vec<_>>>()?
+        .join(" ");
+
+    (!name.is_empty()).then_some(name)
+}
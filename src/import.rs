@@ -0,0 +1,176 @@
+/// Imports `git log` dumps in CSV or TSV format into the same schema as the analysis, to
+/// resurrect the history of archives whose git repository is gone.
+use anyhow::{anyhow, Result};
+use camino::Utf8PathBuf;
+use clap::Args;
+use csv::{ReaderBuilder, StringRecord};
+use git2::Oid;
+
+use crate::{
+    config::WorkingHours,
+    database::{self, OnConflict},
+    log::{
+        flag_first_commits_for_authors, touched_directories, ChangedFile, EffortFormula, GitLog,
+        DEFAULT_DIR_DEPTH, DEFAULT_MAX_SUMMARY_WIDTH,
+    },
+};
+
+/// Expected columns of the dump, in order. `epoch` is the commit date time in UNIX epoch, and
+/// `files` is the list of changed file paths separated by `;`.
+pub const COLUMNS: [&str; 9] =
+    ["hash", "parent", "author", "email", "epoch", "subject", "insertions", "deletions", "files"];
+
+#[derive(Args)]
+pub struct ImportArgs {
+    /// Path to the dump. Files with the `.tsv` extension are read as TSV, others as CSV
+    #[arg()]
+    pub file: Utf8PathBuf,
+
+    /// Name of the repository. Defaults to the file name without the extension
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// URL of the repository
+    #[arg(long, default_value = "(no remote url)")]
+    pub url: String,
+
+    /// Path to the database
+    #[arg(short, long, default_value = "repositories.db")]
+    pub database: Utf8PathBuf,
+}
+
+/// Result of an import.
+pub struct ImportSummary {
+    /// Name of the repository the logs were imported into
+    pub name: String,
+
+    /// Number of imported rows
+    pub imported: usize,
+
+    /// Number of skipped malformed rows
+    pub malformed: usize,
+}
+
+/// Imports the dump into the database. The header must match [`COLUMNS`]; malformed rows are
+/// skipped and counted. As the timezone is unknown, `off_hours` is computed in UTC.
+pub fn import(args: &ImportArgs) -> Result<ImportSummary> {
+    let delimiter = if args.file.extension() == Some("tsv") { b'\t' } else { b',' };
+    let mut reader = ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_path(&args.file)?;
+
+    let headers = reader
+        .headers()?
+        .iter()
+        .map(|h| h.trim().to_lowercase())
+        .collect::<Vec<_>>();
+    if headers != COLUMNS {
+        return Err(anyhow!(
+            "unexpected header: {}, expected: {}",
+            headers.join(","),
+            COLUMNS.join(",")
+        ));
+    }
+
+    let name = match &args.name {
+        Some(name) => name.clone(),
+        None => args.file.file_stem().unwrap_or_default().to_string(),
+    };
+
+    let mut conn = database::open(&args.database)?;
+    database::create_tables(&conn, false)?;
+    database::write_metadata(&conn)?;
+    database::insert_repository(&conn, &name, &args.url, None)?;
+
+    let working_hours = WorkingHours::default();
+    let mut logs = Vec::new();
+    let mut malformed = 0;
+    for record in reader.records() {
+        match record.ok().and_then(|record| to_log(&record, &working_hours)) {
+            Some(log) => logs.push(log),
+            None => malformed += 1,
+        }
+    }
+    flag_first_commits_for_authors(&mut logs);
+
+    let tx = conn.transaction()?;
+    for log in &logs {
+        database::insert_log(
+            &tx,
+            &name,
+            log,
+            EffortFormula::default(),
+            false,
+            OnConflict::default(),
+        )?;
+    }
+    tx.commit()?;
+
+    Ok(ImportSummary { name, imported: logs.len(), malformed })
+}
+
+/// Converts a row of the dump into a `GitLog`, or `None` if the row is malformed.
+fn to_log(record: &StringRecord, working_hours: &WorkingHours) -> Option<GitLog> {
+    if record.len() != COLUMNS.len() {
+        return None;
+    }
+
+    let commit_hash = Oid::from_str(record[0].trim()).ok()?.to_string();
+    let parent = record[1].trim();
+    let parent_hashes = match parent {
+        "" => vec![],
+        _ => match Oid::from_str(parent).ok()? {
+            oid if oid.is_zero() => vec![],
+            oid => vec![oid.to_string()],
+        },
+    };
+    let commit_datetime = record[4].trim().parse().ok()?;
+    let changed_files = record[8]
+        .split(';')
+        .map(str::trim)
+        .filter(|path| !path.is_empty())
+        .map(|path| ChangedFile {
+            path: path.to_string(),
+            status: None,
+            insertions: 0,
+            deletions: 0,
+            is_new: false,
+            old_path: None,
+            large_blob_size: None,
+            large_blob_is_binary: None,
+        })
+        .collect::<Vec<_>>();
+
+    Some(GitLog {
+        commit_hash,
+        short_hash: None,
+        parent_hash: parent_hashes.first().cloned().unwrap_or(Oid::zero().to_string()),
+        parent_hashes,
+        author_name: record[2].to_string(),
+        author_email: record[3].to_string(),
+        commit_datetime,
+        timezone_offset: 0,
+        time_skew_seconds: None,
+        is_first_commit_for_author: false,
+        nearest_tag: None,
+        off_hours: working_hours.is_off_hours(commit_datetime, 0),
+        message: record[5].to_string(),
+        message_char_length: record[5].chars().count(),
+        message_line_count: 1,
+        pr_number: None,
+        message_encoding: None,
+        summary_too_long: record[5].chars().count() > DEFAULT_MAX_SUMMARY_WIDTH,
+        insertions: record[6].trim().parse().ok()?,
+        deletions: record[7].trim().parse().ok()?,
+        over_max_churn: false,
+        avg_rename_similarity: None,
+        tree_file_count: None,
+        generation: None,
+        raw_author: None,
+        raw_committer: None,
+        signer_email: None,
+        directories: touched_directories(&changed_files, DEFAULT_DIR_DEPTH),
+        changed_files,
+    })
+}
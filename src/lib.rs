@@ -1,6 +1,32 @@
-mod analyzer;
+pub mod analyzer;
+mod bundle;
+pub mod cli;
 mod config;
+pub mod database;
+mod date;
+pub mod diff;
+mod dot;
+pub mod extract;
+mod identity;
+pub mod import;
 mod log;
-mod repository;
+mod output;
+mod progress;
+pub mod remap;
+pub mod repository;
+pub mod scan;
+#[cfg(feature = "scripting")]
+mod script;
+mod signature;
+mod summary;
 
 pub use analyzer::GitRepositoryAnalyzer;
+pub use config::{ConfigFormat, Weekday, WorkingHours};
+pub use log::{
+    Branch, ChangeStatus, ChangedFile, EffortFormula, GitLog, LineOwnership, ReflogEntry,
+};
+pub use output::OutputFormat;
+pub use progress::ProgressMode;
+pub use repository::GitRepository;
+pub use scan::{analyze_all, AnalyzedRepository, ScanOptions};
+pub use summary::ActivityPeriod;
@@ -1,27 +1,204 @@
 /// A library to interact with Git logs.
-use std::fmt::Display;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+};
+
+use camino::Utf8Path;
+use clap::ValueEnum;
+use serde::Serialize;
+
+/// Default maximum width of the commit summary, following the conventional 50/72 guidance.
+pub const DEFAULT_MAX_SUMMARY_WIDTH: usize = 72;
+
+/// Default number of leading path components defining the directory touched by a changed file.
+pub const DEFAULT_DIR_DEPTH: usize = 1;
+
+/// Directory of the files at the root of the repository.
+const ROOT_DIRECTORY: &str = "(root)";
 
 /// Represents a Git log with various details from the commit.
-#[derive(Debug)]
+#[derive(Debug, Default, Serialize)]
 pub struct GitLog {
     /// Commit hash.
     pub commit_hash: String,
+    /// Shortest unambiguous abbreviation of the commit hash, unique within the repository.
+    pub short_hash: Option<String>,
     /// Parent commit hash. If the commit is the first commit, this will be the zero hash.
     pub parent_hash: String,
+    /// All parent commit hashes, in order. Empty for a root commit.
+    pub parent_hashes: Vec<String>,
     /// Name of the author.
     pub author_name: String,
     /// Email address of the author.
     pub author_email: String,
-    /// Commit date time in UNIX epoch.
+    /// Commit date time in UNIX epoch. Either the committer time (default) or the author time.
     pub commit_datetime: i64,
+    /// Timezone offset of `commit_datetime` in minutes.
+    pub timezone_offset: i32,
+    /// Committer time minus author time in seconds, regardless of which one is `commit_datetime`.
+    pub time_skew_seconds: Option<i64>,
+    /// Whether the commit was authored outside of the working hours, in its local time.
+    pub off_hours: bool,
+    /// Whether the commit is the earliest of its author among the logs of the repository.
+    pub is_first_commit_for_author: bool,
+    /// Earliest tag containing the commit, if described and contained in any tag.
+    pub nearest_tag: Option<String>,
     /// Commit message, only summary (title).
     pub message: String,
+    /// Encoding declared in the `encoding` header of the commit, if any. The message is UTF-8
+    /// without it.
+    pub message_encoding: Option<String>,
+    /// Number of characters of the full commit message (summary and body), without trailing
+    /// whitespace.
+    pub message_char_length: usize,
+    /// Number of lines of the full commit message, 1 if it has no body.
+    pub message_line_count: usize,
+    /// Number of the pull request extracted from the summary, if only pull requests are analyzed.
+    pub pr_number: Option<u64>,
+    /// Whether the summary is wider than the maximum summary width.
+    pub summary_too_long: bool,
     /// Number of insertions in the commit.
     pub insertions: usize,
     /// Number of deletions in the commit.
     pub deletions: usize,
+    /// Whether the churn exceeds the maximum churn. The churn of such a log is not stored.
+    pub over_max_churn: bool,
+    /// Average similarity (0 to 100) of the renamed and copied files, `None` if there are none.
+    pub avg_rename_similarity: Option<f64>,
+    /// Number of files in the tree of the commit, if counted.
+    pub tree_file_count: Option<usize>,
+    /// Generation number of the commit, the length of the longest path to a root commit.
+    pub generation: Option<usize>,
+    /// Author line of the raw commit header, if kept.
+    pub raw_author: Option<String>,
+    /// Committer line of the raw commit header, if kept.
+    pub raw_committer: Option<String>,
+    /// Identity of the signer declared in the signature of a signed commit, if extracted.
+    pub signer_email: Option<String>,
+    /// Directories touched by the changed files, with the number of changed files in each.
+    pub directories: BTreeMap<String, usize>,
     /// Changed files in the commit.
-    pub changed_files: Vec<String>,
+    pub changed_files: Vec<ChangedFile>,
+}
+
+/// Represents a file changed in a commit.
+#[derive(Debug, Default, Serialize)]
+pub struct ChangedFile {
+    /// Path of the file, the previous one if deleted by the commit.
+    #[serde(rename = "file_path")]
+    pub path: String,
+    /// How the file is changed by the commit, if known.
+    pub status: Option<ChangeStatus>,
+    /// Number of insertions in the file.
+    pub insertions: usize,
+    /// Number of deletions in the file.
+    pub deletions: usize,
+    /// Whether the file is added by the commit. Renamed and copied files are not.
+    pub is_new: bool,
+    /// Previous path of the file, if renamed or copied by the commit.
+    pub old_path: Option<String>,
+    /// Size of the new blob in bytes, if the file is added or modified as a large blob.
+    pub large_blob_size: Option<usize>,
+    /// Whether the new blob is binary, if the file is added or modified as a large blob.
+    pub large_blob_is_binary: Option<bool>,
+}
+
+impl ChangedFile {
+    /// Extension of the file, or `(none)` if the file has no extension.
+    pub fn extension(&self) -> &str {
+        Utf8Path::new(&self.path).extension().unwrap_or("(none)")
+    }
+
+    /// Previous path of the file, if renamed by the commit.
+    pub fn renamed_from(&self) -> Option<&str> {
+        self.old_path
+            .as_deref()
+            .filter(|_| self.status == Some(ChangeStatus::Renamed))
+    }
+}
+
+/// How a file is changed by a commit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeStatus {
+    /// Added by the commit
+    Added,
+    /// Modified, including a change of the type of the file, e.g. to a symbolic link
+    Modified,
+    /// Deleted by the commit
+    Deleted,
+    /// Moved from its old path, possibly modified too
+    Renamed,
+    /// Copied from its old path, which is kept
+    Copied,
+}
+
+impl ChangeStatus {
+    /// Name of the status, as stored in `changed_files.status`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeStatus::Added => "added",
+            ChangeStatus::Modified => "modified",
+            ChangeStatus::Deleted => "deleted",
+            ChangeStatus::Renamed => "renamed",
+            ChangeStatus::Copied => "copied",
+        }
+    }
+
+    /// Status of the name stored in `changed_files.status`, if any.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "added" => Some(ChangeStatus::Added),
+            "modified" => Some(ChangeStatus::Modified),
+            "deleted" => Some(ChangeStatus::Deleted),
+            "renamed" => Some(ChangeStatus::Renamed),
+            "copied" => Some(ChangeStatus::Copied),
+            _ => None,
+        }
+    }
+}
+
+/// Represents an entry of the reflog of a ref, i.e. an update of the ref.
+#[derive(Debug)]
+pub struct ReflogEntry {
+    /// Name of the ref, e.g. `HEAD` or `refs/heads/main`.
+    pub refname: String,
+    /// Commit hash the ref pointed to before the update. The zero hash if the ref was created.
+    pub old_oid: String,
+    /// Commit hash the ref points to after the update.
+    pub new_oid: String,
+    /// Name of the committer who updated the ref.
+    pub committer_name: String,
+    /// Email address of the committer who updated the ref.
+    pub committer_email: String,
+    /// Time of the update in UNIX epoch.
+    pub timestamp: i64,
+    /// Message of the update, e.g. `reset: moving to HEAD~1` or `commit: Fix typo`.
+    pub message: String,
+}
+
+/// Represents a local branch of a repository, with where it diverged from the default branch.
+#[derive(Debug)]
+pub struct Branch {
+    /// Short name of the branch, e.g. `feature/login`.
+    pub name: String,
+    /// Hash of the commit the branch points to.
+    pub tip_commit: String,
+    /// Hash of the best common ancestor of the tip and of the default branch. `None` if they have
+    /// no common history, or if the default branch is unknown.
+    pub merge_base: Option<String>,
+}
+
+/// Number of lines of a file at the analyzed revision last changed by an author, per git blame.
+#[derive(Debug)]
+pub struct LineOwnership {
+    /// Path of the file.
+    pub file_path: String,
+    /// Normalized name of the author.
+    pub author: String,
+    /// Number of lines of the file attributed to the author.
+    pub lines: usize,
 }
 
 impl Display for GitLog {
@@ -37,7 +214,134 @@ impl Display for GitLog {
             self.commit_datetime,
             self.insertions,
             self.deletions,
-            self.changed_files.join(", ")
+            self.changed_files.iter().map(|f| f.path.as_str()).collect::<Vec<_>>().join(", ")
         )
     }
 }
+
+impl GitLog {
+    /// Number of parents of the commit. More than one for a merge commit.
+    pub fn parent_count(&self) -> usize {
+        self.parent_hashes.len()
+    }
+
+    /// Total number of changed lines, i.e. insertions plus deletions.
+    pub fn churn(&self) -> usize {
+        self.insertions + self.deletions
+    }
+
+    /// Share of deletions in the churn, from 0 for a purely additive commit to 1 for a pure
+    /// deletion. `None` for a commit without churn, e.g. a pure rename.
+    pub fn churn_ratio(&self) -> Option<f64> {
+        let churn = self.churn();
+        (churn > 0).then(|| self.deletions as f64 / churn as f64)
+    }
+
+    /// Approximate effort of the commit, computed with the specified formula.
+    pub fn effort_score(&self, formula: EffortFormula) -> f64 {
+        formula.score(self.changed_files.len(), self.churn())
+    }
+}
+
+/// Flags the earliest log of each author, by `commit_datetime` then `generation`, as their first
+/// commit. The logs are expected newest first, like the history walk, so of two logs that can't be
+/// told apart the later one in the slice is the first. Authors are compared by their normalized
+/// name.
+pub fn flag_first_commits_for_authors(logs: &mut [GitLog]) {
+    let mut first = HashMap::new();
+    for (index, log) in logs.iter().enumerate().rev() {
+        let key = (log.commit_datetime, log.generation);
+        first
+            .entry(log.author_name.clone())
+            .and_modify(|(first_key, first_index)| {
+                if key < *first_key {
+                    (*first_key, *first_index) = (key, index);
+                }
+            })
+            .or_insert((key, index));
+    }
+    for (_, index) in first.into_values() {
+        logs[index].is_first_commit_for_author = true;
+    }
+}
+
+/// Counts the changed files per touched directory, the first `depth` directory components of their
+/// path, e.g. `src` for `src/cli/main.rs` at depth 1, or `src/cli` at depth 2. A file in a
+/// shallower directory counts for the directory itself, and the files at the root of the repository
+/// count for `(root)`.
+pub fn touched_directories(files: &[ChangedFile], depth: usize) -> BTreeMap<String, usize> {
+    let mut directories = BTreeMap::new();
+    for file in files {
+        let components = file.path.split('/').collect::<Vec<_>>();
+        let dirs = &components[..components.len() - 1];
+        let directory = match dirs.len().min(depth) {
+            0 => ROOT_DIRECTORY.to_string(),
+            n => dirs[..n].join("/"),
+        };
+        *directories.entry(directory).or_insert(0) += 1;
+    }
+    directories
+}
+
+/// Named formulas to compute an approximate effort score of a commit from its number of changed
+/// files `f` and its churn `c` (insertions + deletions). `ln` is the natural logarithm.
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum EffortFormula {
+    /// `f * ln(1 + c)`
+    #[default]
+    FilesLogChurn,
+    /// `sqrt(f * c)`
+    GeometricMean,
+    /// `ln(1 + c)`
+    LogChurn,
+    /// `c`
+    Churn,
+}
+
+impl EffortFormula {
+    /// Computes the score from the number of changed files and the churn.
+    pub fn score(self, files: usize, churn: usize) -> f64 {
+        let (f, c) = (files as f64, churn as f64);
+        match self {
+            Self::FilesLogChurn => f * c.ln_1p(),
+            Self::GeometricMean => (f * c).sqrt(),
+            Self::LogChurn => c.ln_1p(),
+            Self::Churn => c,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_score(formula: EffortFormula, files: usize, churn: usize, expected: f64) {
+        let score = formula.score(files, churn);
+        assert!((score - expected).abs() < 1e-12, "{formula:?}: {score} != {expected}");
+    }
+
+    #[test]
+    fn files_log_churn_scales_the_log_of_the_churn_by_the_files() {
+        assert_score(EffortFormula::FilesLogChurn, 2, 3, 2.0 * 4f64.ln());
+        assert_score(EffortFormula::FilesLogChurn, 2, 0, 0.0);
+    }
+
+    #[test]
+    fn geometric_mean_is_the_root_of_files_times_churn() {
+        assert_score(EffortFormula::GeometricMean, 2, 8, 4.0);
+        assert_score(EffortFormula::GeometricMean, 2, 0, 0.0);
+    }
+
+    #[test]
+    fn log_churn_ignores_the_files() {
+        assert_score(EffortFormula::LogChurn, 2, 3, 4f64.ln());
+        assert_score(EffortFormula::LogChurn, 5, 3, 4f64.ln());
+        assert_score(EffortFormula::LogChurn, 2, 0, 0.0);
+    }
+
+    #[test]
+    fn churn_is_the_churn() {
+        assert_score(EffortFormula::Churn, 2, 3, 3.0);
+        assert_score(EffortFormula::Churn, 2, 0, 0.0);
+    }
+}
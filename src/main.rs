@@ -1,28 +1,166 @@
+use std::fmt::Write;
+
 use anyhow::Result;
-use git_log_to_sqlite::GitRepositoryAnalyzer;
+use clap::Parser;
+use git_log_to_sqlite::{
+    analyzer::{AnalysisReport, Uninitialized},
+    cli::{Cli, Command},
+    diff::{diff, DiffArgs, DiffFormat, Presence},
+    extract::{extract, ExtractArgs},
+    import::{import, ImportArgs},
+    remap::{remap_authors, RemapArgs},
+    GitRepositoryAnalyzer,
+};
 
 fn main() -> Result<()> {
-    let analyzer = GitRepositoryAnalyzer::new().try_prepare()?;
-    let (duration, analyzed_repositories, skipped_directories) = analyzer.analyze()?;
-    println!("# Done in {duration} seconds\n");
+    let cli = Cli::parse();
+    match cli.command {
+        Some(Command::Import(args)) => run_import(&args),
+        Some(Command::RemapAuthors(args)) => run_remap_authors(&args),
+        Some(Command::Extract(args)) => run_extract(&args),
+        Some(Command::Diff(args)) => run_diff(&args),
+        // clap requires the scan arguments when no command is given
+        None => run_scan(cli.scan.expect("scan arguments are required")),
+    }
+}
+
+fn run_scan(options: Uninitialized) -> Result<()> {
+    let analyzer = GitRepositoryAnalyzer::from_options(options).try_prepare()?;
+    let AnalysisReport {
+        elapsed,
+        analyzed_repositories,
+        skipped_directories,
+        not_started_directories,
+        too_few_commits_directories,
+        rewritten_repositories,
+    } = analyzer.analyze()?;
+    let mut report = String::new();
+    writeln!(report, "# Done in {elapsed} seconds\n")?;
 
-    println!(
+    writeln!(
+        report,
         "# {} repositories in the table\n\n{}\n",
         analyzed_repositories.len(),
         analyzed_repositories.join(", ")
-    );
-    println!(
+    )?;
+    writeln!(
+        report,
         "# {} ignored repositories:\n\n{}\n",
         analyzer.ignored_repositories.len(),
         analyzer.ignored_repositories.join(", ")
-    );
+    )?;
+
+    if !analyzer.pruned_repositories.is_empty() {
+        writeln!(
+            report,
+            "# {} repositories were pruned as their path no longer exists:\n\n{}\n",
+            analyzer.pruned_repositories.len(),
+            analyzer.pruned_repositories.join(", ")
+        )?;
+    }
 
     if !skipped_directories.is_empty() {
-        println!(
-            "# {} directories were not stored for some reason. Maybe empty, or not a git repository?:\n\n{}",
+        writeln!(
+            report,
+            "# {} directories were not stored, with the reason:\n\n{}",
             skipped_directories.len(),
-            skipped_directories.join("\n")
+            skipped_directories
+                .iter()
+                .map(|(directory, e)| format!("{directory}: {e:#}"))
+                .collect::<Vec<_>>()
+                .join("\n")
+        )?;
+    }
+
+    if !not_started_directories.is_empty() {
+        writeln!(
+            report,
+            "# {} directories were not started as the deadline was exceeded:\n\n{}",
+            not_started_directories.len(),
+            not_started_directories.join("\n")
+        )?;
+    }
+
+    if !too_few_commits_directories.is_empty() {
+        writeln!(
+            report,
+            "# {} directories were not stored as they have fewer commits than --min-commits:\n\n{}",
+            too_few_commits_directories.len(),
+            too_few_commits_directories.join("\n")
+        )?;
+    }
+
+    if !rewritten_repositories.is_empty() {
+        writeln!(
+            report,
+            "# {} repositories have stored commits which are no longer reachable, e.g. rewritten by a force push:\n\n{}",
+            rewritten_repositories.len(),
+            rewritten_repositories
+                .iter()
+                .map(|(name, count)| format!("{name}: {count} commits"))
+                .collect::<Vec<_>>()
+                .join("\n")
+        )?;
+    }
+
+    // keep stdout clean when the database itself is written to stdout
+    if analyzer.writes_database_to_stdout() {
+        eprint!("{report}");
+    } else {
+        print!("{report}");
+    }
+    Ok(())
+}
+
+fn run_import(args: &ImportArgs) -> Result<()> {
+    let summary = import(args)?;
+    println!("# Imported {} logs into {}\n", summary.imported, summary.name);
+    if summary.malformed > 0 {
+        println!("# {} malformed rows were skipped", summary.malformed);
+    }
+    Ok(())
+}
+
+fn run_remap_authors(args: &RemapArgs) -> Result<()> {
+    let updated = remap_authors(args)?;
+    println!("# Updated logs per canonical author\n");
+    for (canonical, count) in updated {
+        println!("{canonical}: {count}");
+    }
+    Ok(())
+}
+
+fn run_extract(args: &ExtractArgs) -> Result<()> {
+    let copied = extract(args)?;
+    println!("# Extracted {} into {}\n", args.repository, args.output);
+    for (table, count) in copied {
+        println!("{table}: {count}");
+    }
+    Ok(())
+}
+
+fn run_diff(args: &DiffArgs) -> Result<()> {
+    let diffs = diff(args)?;
+    if args.format == DiffFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&diffs)?);
+        return Ok(());
+    }
+
+    println!("# Changes from {} to {}\n", args.old, args.new);
+    for diff in diffs {
+        let mut line = format!(
+            "{}: +{} -{} commits, churn {:+}",
+            diff.name, diff.added_commits, diff.removed_commits, diff.churn_delta
         );
+        if !diff.new_authors.is_empty() {
+            write!(line, ", new authors: {}", diff.new_authors.join(", "))?;
+        }
+        match diff.presence {
+            Presence::Added => write!(line, " (only in {})", args.new)?,
+            Presence::Removed => write!(line, " (only in {})", args.old)?,
+            Presence::Both => {}
+        }
+        println!("{line}");
     }
     Ok(())
 }
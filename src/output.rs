@@ -0,0 +1,105 @@
+/// JSON output of the logs, as an alternative to the database for pipelines.
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufWriter, Write},
+    sync::Mutex,
+};
+
+use anyhow::{Context, Result};
+use camino::Utf8Path;
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::log::GitLog;
+
+/// Format of the output of the logs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// SQLite database
+    #[default]
+    Sqlite,
+    /// JSON array of the logs, to stdout or the output file
+    Json,
+    /// One JSON object per line per log, to stdout or the output file
+    Jsonl,
+}
+
+/// A log with the repository it belongs to, as written to the output.
+#[derive(Serialize)]
+struct Record<'a> {
+    repository_name: &'a str,
+    repository_url: &'a str,
+    #[serde(flatten)]
+    log: &'a GitLog,
+}
+
+/// Writes the logs of the repositories as JSON to stdout or a file, shared by the repositories
+/// analyzed at once. The logs of a repository are written together, and the repositories in the
+/// order they are done.
+pub struct JsonWriter {
+    format: OutputFormat,
+    /// Whether the output is stdout, rather than a file
+    to_stdout: bool,
+    /// The output, and whether any log was written to it
+    out: Mutex<(BufWriter<Box<dyn Write + Send>>, bool)>,
+}
+
+impl fmt::Debug for JsonWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JsonWriter")
+            .field("format", &self.format)
+            .field("to_stdout", &self.to_stdout)
+            .finish_non_exhaustive()
+    }
+}
+
+impl JsonWriter {
+    /// Creates the writer to the file, replacing it, or to stdout if `None`.
+    pub fn new(format: OutputFormat, file: Option<&Utf8Path>) -> Result<Self> {
+        let out: Box<dyn Write + Send> = match file {
+            Some(file) => {
+                Box::new(File::create(file).with_context(|| format!("failed to create {file}"))?)
+            }
+            None => Box::new(io::stdout()),
+        };
+        Ok(Self {
+            format,
+            to_stdout: file.is_none(),
+            out: Mutex::new((BufWriter::new(out), false)),
+        })
+    }
+
+    /// Whether the logs are written to stdout.
+    pub fn is_stdout(&self) -> bool {
+        self.to_stdout
+    }
+
+    /// Writes the logs of the repository with the name and the URL.
+    pub fn write(&self, name: &str, url: &str, logs: &[&GitLog]) -> Result<()> {
+        let mut guard = self.out.lock().unwrap();
+        let (out, written) = &mut *guard;
+        for log in logs {
+            let record = Record { repository_name: name, repository_url: url, log };
+            if self.format == OutputFormat::Json {
+                out.write_all(if *written { b",\n" } else { b"[\n" })?;
+            }
+            serde_json::to_writer(&mut *out, &record)?;
+            if self.format == OutputFormat::Jsonl {
+                out.write_all(b"\n")?;
+            }
+            *written = true;
+        }
+        Ok(out.flush()?)
+    }
+
+    /// Closes the JSON array, once all the repositories are written, or the analysis failed.
+    pub fn finish(&self) -> Result<()> {
+        let mut guard = self.out.lock().unwrap();
+        let (out, written) = &mut *guard;
+        if self.format == OutputFormat::Json {
+            out.write_all(if *written { b"\n]\n" } else { b"[]\n" })?;
+        }
+        Ok(out.flush()?)
+    }
+}
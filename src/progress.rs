@@ -0,0 +1,155 @@
+/// Progress reporting of the analysis, either as `indicatif` bars or as plain text lines, and
+/// optionally as newline-delimited JSON events to a socket for external dashboards.
+use std::{
+    io::Write,
+    net::TcpStream,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use clap::ValueEnum;
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use serde_json::json;
+
+/// Appearance of the progress reporting.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ProgressMode {
+    /// Overall and per-repository progress bars
+    #[default]
+    Full,
+    /// Minimal templates without bars, for narrow terminals
+    Simple,
+    /// One line of text per finished repository instead of bars, for CI and log scrapers
+    Plain,
+    /// No progress reporting at all, set by --quiet
+    #[value(skip)]
+    Quiet,
+}
+
+/// Spinner of the discovery of the directories to scan, which can take a while on large trees
+/// before any repository is analyzed. Its position is the number of visited entries, and its
+/// message the number of repositories found so far. Hidden in the plain and quiet modes.
+pub fn discovery_spinner(mode: ProgressMode) -> ProgressBar {
+    if matches!(mode, ProgressMode::Plain | ProgressMode::Quiet) {
+        return ProgressBar::hidden();
+    }
+    let spinner = ProgressBar::new_spinner();
+    spinner.set_style(
+        ProgressStyle::with_template("{spinner} discovering: {pos} entries visited, {msg}")
+            .unwrap(),
+    );
+    spinner.set_message("0 repositories found");
+    spinner.enable_steady_tick(Duration::from_millis(100));
+    spinner
+}
+
+/// Destination of the progress events. Shared by all the repository tasks, and reset to `None`
+/// once writing to it fails.
+type EventSink = Arc<Mutex<Option<Box<dyn Write + Send>>>>;
+
+/// Overall progress of the analysis, shared by all the repository tasks.
+#[derive(Clone)]
+pub struct Progress {
+    multi: MultiProgress,
+    overall: ProgressBar,
+    mode: ProgressMode,
+    events: EventSink,
+}
+
+impl Progress {
+    /// Creates the overall progress for the number of repositories.
+    pub fn new(mode: ProgressMode, len: u64) -> Self {
+        let multi = match mode {
+            ProgressMode::Plain | ProgressMode::Quiet => {
+                MultiProgress::with_draw_target(ProgressDrawTarget::hidden())
+            }
+            _ => MultiProgress::new(),
+        };
+
+        let overall = multi.add(ProgressBar::new(len));
+        let template = match mode {
+            ProgressMode::Full => {
+                "{prefix:<30!.blue} [{bar:40.cyan/blue}] {pos:>3}/{len:3} [{elapsed_precise}]"
+            }
+            _ => "{prefix} {pos}/{len} [{elapsed}]",
+        };
+        overall.set_style(ProgressStyle::with_template(template).unwrap().progress_chars("=> "));
+        overall.set_prefix("OVERALL PROGRESS");
+
+        Self { multi, overall, mode, events: Default::default() }
+    }
+
+    /// Sends the progress events to the socket as well, either a TCP address (`host:port`) or a
+    /// Unix socket (`unix:/path/to/socket`). If the socket can't be reached, a warning is printed
+    /// and the events are dropped.
+    pub fn with_socket(self, addr: &str) -> Self {
+        let stream: std::io::Result<Box<dyn Write + Send>> = match addr.strip_prefix("unix:") {
+            #[cfg(unix)]
+            Some(path) => std::os::unix::net::UnixStream::connect(path).map(|s| Box::new(s) as _),
+            #[cfg(not(unix))]
+            Some(_) => Err(std::io::Error::other("Unix sockets are not supported")),
+            None => TcpStream::connect(addr).map(|s| Box::new(s) as _),
+        };
+        match stream {
+            Ok(stream) => *self.events.lock().unwrap() = Some(stream),
+            Err(e) => eprintln!("warning: failed to connect to the progress socket {addr}: {e}"),
+        }
+        self
+    }
+
+    /// Prints the progress events to stderr, e.g. to follow a single repository while debugging.
+    pub fn with_stderr(self) -> Self {
+        *self.events.lock().unwrap() = Some(Box::new(std::io::stderr()));
+        self
+    }
+
+    /// Sends a progress event of the repository, as a line of JSON such as
+    /// `{"repository":"foo","stage":"storing","commits":100}`. `commits` is the number of commits
+    /// done in the stage, if relevant.
+    pub fn event(&self, repository: &str, stage: &str, commits: Option<usize>) {
+        let mut events = self.events.lock().unwrap();
+        if let Some(sink) = events.as_mut() {
+            let event = json!({ "repository": repository, "stage": stage, "commits": commits });
+            if writeln!(sink, "{event}").and_then(|_| sink.flush()).is_err() {
+                *events = None; // the other end is gone; stop sending
+            }
+        }
+    }
+
+    /// Adds a progress bar for a repository.
+    pub fn add_repository(&self, name: &str) -> ProgressBar {
+        let pb = self.multi.add(ProgressBar::new(1));
+        let template = match self.mode {
+            ProgressMode::Full => "{prefix:<30!} [{bar:40}] {pos:>3}/{len:3} {msg}",
+            _ => "{prefix} {msg}",
+        };
+        pb.set_style(ProgressStyle::with_template(template).unwrap().progress_chars("-> "));
+        pb.set_prefix(format!("- {name}"));
+        pb
+    }
+
+    /// Marks one more repository as done.
+    pub fn inc(&self) {
+        self.overall.inc(1);
+        if self.mode == ProgressMode::Plain {
+            eprintln!(
+                "{}/{} repositories done",
+                self.overall.position(),
+                self.overall.length().unwrap_or_default()
+            );
+        }
+    }
+
+    /// Removes one repository which won't be analyzed from the overall progress, to keep the ETA
+    /// meaningful.
+    pub fn unschedule(&self) {
+        let len = self.overall.length().unwrap_or_default();
+        self.overall.set_length(len.saturating_sub(1));
+    }
+
+    /// Finishes the overall progress and returns the elapsed time in seconds.
+    pub fn finish(&self) -> f64 {
+        self.overall.finish_and_clear();
+        self.overall.elapsed().as_millis() as f64 / 1000.0
+    }
+}
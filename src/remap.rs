@@ -0,0 +1,175 @@
+/// Re-attributes the logs of an existing database to canonical authors, to consolidate
+/// identity duplicates discovered after the analysis without scanning the repositories again.
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{Context, Result};
+use camino::Utf8PathBuf;
+use clap::{Args, ValueEnum};
+use rusqlite::{params, Transaction};
+
+use crate::{
+    analyzer::parse_share,
+    config::ConfigFormat,
+    database,
+    summary::{Activity, ActivityPeriod, AuthorCadence, Collaboration, LogRow, RepositoryHealth},
+};
+
+#[derive(Args)]
+pub struct RemapArgs {
+    /// Path to the identity merge file, mapping each canonical author name to its aliases (names
+    /// or email addresses). TOML, JSON, or YAML, detected from the extension
+    #[arg()]
+    pub file: Utf8PathBuf,
+
+    /// Path to the database
+    #[arg(short, long, default_value = "repositories.db")]
+    pub database: Utf8PathBuf,
+
+    /// Share of the churn (over 0.0, up to 1.0) the authors counted in the recomputed bus factor
+    /// of each repository (repository_health) account for, as given to the scan
+    #[arg(long, default_value_t = 0.5, value_parser = parse_share)]
+    pub bus_factor_threshold: f64,
+}
+
+/// Renames the authors of the logs matching any alias, by name or by email address, to their
+/// canonical name, and sets their email address to the first alias which is an email address, if
+/// any. The author names of `file_latest` and `line_ownership` are renamed too, and the summaries
+/// computed from the author names and the first commit flags of the repositories of the renamed
+/// logs are computed again, all in a single transaction. Returns the number of updated logs per
+/// canonical name.
+pub fn remap_authors(args: &RemapArgs) -> Result<BTreeMap<String, usize>> {
+    let content = std::fs::read_to_string(&args.file)
+        .with_context(|| format!("failed to read {}", args.file))?;
+    let identities: BTreeMap<String, Vec<String>> = ConfigFormat::from_path(&args.file)
+        .deserialize(&content)
+        .with_context(|| format!("failed to parse {}", args.file))?;
+
+    anyhow::ensure!(args.database.exists(), "{} does not exist", args.database);
+    let mut conn = database::open(&args.database)?;
+    let tx = conn.transaction()?;
+    let mut updated = BTreeMap::new();
+    let mut repositories = BTreeSet::new();
+    for (canonical, aliases) in &identities {
+        let email = aliases.iter().find(|alias| alias.contains('@'));
+        let mut count = 0;
+        for alias in aliases.iter().filter(|alias| *alias != canonical) {
+            let logs = tx
+                .prepare(
+                    r#"
+                    SELECT commit_hash, author_name, author_email, repositories.name FROM logs
+                    JOIN repositories ON repositories.id = logs.repository_id
+                    WHERE author_name = ?1 OR author_email = ?1
+                    "#,
+                )?
+                .query_map(params![alias], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                    ))
+                })?
+                .collect::<rusqlite::Result<Vec<(_, _, String, String)>>>()?;
+            // the names of the alias, to rename them in the tables without email addresses
+            let mut names = BTreeSet::new();
+            if !alias.contains('@') {
+                names.insert(alias.clone());
+            }
+            for (hash, name, address, repository) in logs {
+                // the email address is set apart from the name, so that the logs which already
+                // carry the canonical name get the canonical email address too
+                let renamed = name != *canonical;
+                let readdressed = email.is_some_and(|email| *email != address);
+                if renamed {
+                    tx.execute(
+                        "UPDATE logs SET author_name = ?2 WHERE commit_hash = ?1",
+                        params![hash, canonical],
+                    )?;
+                    names.insert(name);
+                }
+                if readdressed {
+                    tx.execute(
+                        "UPDATE logs SET author_email = ?2 WHERE commit_hash = ?1",
+                        params![hash, email],
+                    )?;
+                }
+                if renamed || readdressed {
+                    count += 1;
+                    repositories.insert(repository);
+                }
+            }
+            for name in names.iter().filter(|name| *name != canonical) {
+                rename_author(&tx, name, canonical)?;
+            }
+        }
+        updated.insert(canonical.clone(), count);
+    }
+    for repository in &repositories {
+        recompute_summaries(&tx, repository, args.bus_factor_threshold)?;
+    }
+    tx.commit()?;
+    Ok(updated)
+}
+
+/// Renames the author in the tables keyed by the author name alone, merging the lines owned by
+/// both names.
+fn rename_author(tx: &Transaction, name: &str, canonical: &str) -> Result<()> {
+    tx.execute(
+        "UPDATE file_latest SET last_author = ?2 WHERE last_author = ?1",
+        params![name, canonical],
+    )?;
+    tx.execute(
+        r#"
+        INSERT INTO line_ownership (repository_id, file_path, author, lines)
+        SELECT repository_id, file_path, ?2, lines FROM line_ownership WHERE author = ?1
+        ON CONFLICT (repository_id, file_path, author) DO UPDATE SET
+            lines = lines + excluded.lines
+        "#,
+        params![name, canonical],
+    )?;
+    tx.execute("DELETE FROM line_ownership WHERE author = ?1", params![name])?;
+    Ok(())
+}
+
+/// Computes again, from the stored logs of the repository, the first commit flags and the summaries
+/// computed from the author names which were stored by the scan, with the same activity periods.
+fn recompute_summaries(tx: &Transaction, repository_name: &str, threshold: f64) -> Result<()> {
+    let stored = |table: &str| -> Result<bool> {
+        Ok(tx
+            .prepare(&format!(
+                r#"
+                SELECT 1 FROM {table}
+                WHERE repository_id = (SELECT id FROM repositories WHERE name = ?)
+                "#
+            ))?
+            .exists(params![repository_name])?)
+    };
+
+    database::flag_first_commits_for_authors(tx, repository_name)?;
+    let logs = LogRow::stored(tx, repository_name)?;
+    if stored("repository_health")? {
+        RepositoryHealth::store(tx, repository_name, &logs, threshold)?;
+    }
+    if stored("author_cadence")? {
+        AuthorCadence::store(tx, repository_name, &logs)?;
+    }
+    let periods = tx
+        .prepare(
+            r#"
+            SELECT DISTINCT period_type FROM activity
+            WHERE repository_id = (SELECT id FROM repositories WHERE name = ?)
+            "#,
+        )?
+        .query_map(params![repository_name], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?
+        .iter()
+        .filter_map(|period| ActivityPeriod::from_str(period, false).ok())
+        .collect::<Vec<_>>();
+    if !periods.is_empty() {
+        Activity::store(tx, repository_name, &logs, &periods)?;
+    }
+    if stored("collaboration")? {
+        Collaboration::store(tx, repository_name)?;
+    }
+    Ok(())
+}
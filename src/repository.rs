@@ -1,10 +1,36 @@
-use std::{collections::HashMap, ops::Deref, path::PathBuf};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, HashSet},
+    ops::{Deref, Range},
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
 
 use anyhow::{anyhow, Result};
 use camino::Utf8PathBuf;
-use git2::{DiffFindOptions, DiffOptions, Oid, Repository};
+use clap::ValueEnum;
+use encoding_rs::Encoding;
+use git2::{
+    BranchType, Commit, Delta, Diff, DiffDelta, DiffFindOptions, DiffFormat, DiffOptions,
+    ErrorCode, ObjectType, Oid, Patch, Repository, StatusOptions, Time, Tree, TreeWalkMode,
+    TreeWalkResult,
+};
+use globset::GlobSet;
+use regex::{Regex, RegexSet};
+use walkdir::WalkDir;
 
-use crate::log::GitLog;
+use crate::{
+    config::WorkingHours,
+    identity::{collapse_whitespace, name_from_email, normalize_email},
+    log::{
+        flag_first_commits_for_authors, touched_directories, Branch, ChangeStatus, ChangedFile,
+        GitLog, LineOwnership, ReflogEntry, DEFAULT_DIR_DEPTH, DEFAULT_MAX_SUMMARY_WIDTH,
+    },
+    signature::signer_identity,
+};
+
+/// Pull request number in a summary, e.g. `Merge pull request #123 from ...` or `Fix (#123)`.
+static PR_NUMBER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"#(\d+)").unwrap());
 
 /// A git repository that can be used to analyze the commit history of a git repository. To prevent
 /// the impossible operation from executing (i.e. run analysis before properly opening it, or
@@ -28,6 +54,291 @@ impl<S> Deref for GitRepository<S> {
     }
 }
 
+/// Options to control the analysis of a repository.
+#[derive(Debug, Default, Clone)]
+pub struct AnalyzeOptions {
+    /// Email address and user name map to normalize the author name
+    pub author_map: Option<HashMap<String, String>>,
+
+    /// Derive the author name from the email address when it is not in the author map
+    pub name_from_email: bool,
+
+    /// Trim and lowercase the email addresses, and collapse the whitespace of the names, before
+    /// the author map lookup and the storage. The keys of the author map are expected normalized.
+    pub normalize_emails: bool,
+
+    /// Revision (branch, tag, or commit) to start the analysis from. HEAD if `None`.
+    pub rev: Option<String>,
+
+    /// Which time of the commit populates `commit_datetime`
+    pub date: DateSource,
+
+    /// Working hours window to classify off-hours commits, based on the author time
+    pub working_hours: WorkingHours,
+
+    /// Check whether the working tree has uncommitted changes
+    pub check_dirty: bool,
+
+    /// Source of the repository name
+    pub name_from: NameSource,
+
+    /// Maximum churn (insertions + deletions) of a commit. No limit if `None`.
+    pub max_churn: Option<usize>,
+
+    /// What to do with a commit whose churn exceeds `max_churn`
+    pub max_churn_mode: MaxChurnMode,
+
+    /// Count the files in the tree of each commit
+    pub count_tree_files: bool,
+
+    /// Annotate each commit with the earliest tag containing it
+    pub describe_commits: bool,
+
+    /// Collect the commits reachable from the start, to detect the stored commits rewritten since
+    pub detect_rewrites: bool,
+
+    /// Commit analyzed by a previous scan, whose ancestors are skipped, so only the commits since
+    /// then are analyzed. Ignored if it is not the start or one of its ancestors, e.g. after a
+    /// force push, in which case the whole history is analyzed.
+    pub previous_commit: Option<Oid>,
+
+    /// Commits already stored by an interrupted ingest, which are walked but not diffed again, so
+    /// a resumed ingest only analyzes the rest of the history
+    pub stored_commits: HashSet<Oid>,
+
+    /// Only analyze the commits whose `commit_datetime` is on or after the UNIX epoch
+    pub since: Option<i64>,
+
+    /// Only analyze the commits whose `commit_datetime` is on or before the UNIX epoch
+    pub until: Option<i64>,
+
+    /// Only analyze the commits whose `commit_datetime`, shifted to its local time, is within the
+    /// range, e.g. the UNIX epochs of the midnights in UTC starting a calendar year and the next
+    pub year: Option<Range<i64>>,
+
+    /// Keep the author and committer lines of the commit header as is
+    pub store_raw_identity: bool,
+
+    /// Ref whose reflog is read, if any
+    pub reflog: Option<String>,
+
+    /// List the local branches, with the merge base of each with the default branch
+    pub branches: bool,
+
+    /// Number of context lines of the diff hunks. The git default (3) if `None`.
+    pub context_lines: Option<u32>,
+
+    /// Maximum number of lines between diff hunks to merge them. The git default (0) if `None`.
+    pub interhunk_lines: Option<u32>,
+
+    /// Ignore whitespace changes when computing the churn
+    pub ignore_whitespace: bool,
+
+    /// Similarity (0 to 100) for a file to be detected as renamed or copied. Only exact matches if
+    /// `None`.
+    pub rename_threshold: Option<u16>,
+
+    /// Extract the identity of the signer of signed commits
+    pub store_signer: bool,
+
+    /// Size in bytes above which an added or modified file is flagged as a large blob
+    pub large_blob_threshold: Option<usize>,
+
+    /// Only collect the metadata of the repository and count the commits, without analyzing them
+    pub catalog: bool,
+
+    /// Files to blame at the start commit to store their line ownership. Not blamed if `None`.
+    pub blame: Option<BlameScope>,
+
+    /// Analyze a repository without any commit, i.e. with an unborn HEAD, as having no logs,
+    /// instead of failing
+    pub include_empty: bool,
+
+    /// Width in characters above which the summary is flagged as too long.
+    /// [`DEFAULT_MAX_SUMMARY_WIDTH`] if `None`.
+    pub max_summary_width: Option<usize>,
+
+    /// Only analyze the pull requests: the merge commits, and the commits whose summary matches
+    /// `pr_pattern`
+    pub merges_only: bool,
+
+    /// Also analyze the merge commits, along with the other commits
+    pub include_merges: bool,
+
+    /// Pattern of the summary of a squash-merged pull request, whose first capture group is the
+    /// pull request number
+    pub pr_pattern: Option<Regex>,
+
+    /// Paths excluded from the changed files and the churn of each commit
+    pub excluded_paths: Option<GlobSet>,
+
+    /// Patterns of the messages of the commits to skip, if any
+    pub excluded_messages: Option<RegexSet>,
+
+    /// Match `excluded_messages` against the full message, instead of the summary only
+    pub excluded_message_body: bool,
+
+    /// Number of leading path components defining the directory touched by a changed file.
+    /// [`DEFAULT_DIR_DEPTH`] if `None`.
+    pub dir_depth: Option<usize>,
+
+    /// Measure the size of the repository on disk, covering the scope. Not measured if `None`.
+    pub measure_size: Option<SizeScope>,
+}
+
+/// Files blamed to compute the line ownership.
+#[derive(Debug, Clone)]
+pub struct BlameScope {
+    /// Globs of the paths of the files to blame. All files if `None`.
+    pub paths: Option<GlobSet>,
+    /// Maximum size in bytes of the files to blame. Larger files are skipped, as well as binary
+    /// files.
+    pub max_bytes: u64,
+}
+
+impl AnalyzeOptions {
+    /// Normalizes the email address, if enabled.
+    fn normalize_email(&self, email: &str) -> String {
+        match self.normalize_emails {
+            true => normalize_email(email),
+            false => email.to_string(),
+        }
+    }
+
+    /// Collapses the whitespace of the name, if enabled.
+    fn normalize_name(&self, name: &str) -> String {
+        match self.normalize_emails {
+            true => collapse_whitespace(name),
+            false => name.to_string(),
+        }
+    }
+
+    /// Normalizes the author name with the author map. If the email address is not in the map,
+    /// and deriving the name from the email address is enabled, the derived name is used as the
+    /// last resort.
+    fn normalize_author_name(&self, name: String, email: &str) -> String {
+        if let Some(mapped) = self.author_map.as_ref().and_then(|map| map.get(email)) {
+            return mapped.clone();
+        }
+        if self.name_from_email {
+            if let Some(derived) = name_from_email(email) {
+                return derived;
+            }
+        }
+        name
+    }
+
+    /// Whether the message of the commit matches any of the excluded message patterns.
+    fn is_excluded_message(&self, commit: &Commit) -> bool {
+        let Some(patterns) = &self.excluded_messages else {
+            return false;
+        };
+        let message = decode_message(commit);
+        let message = if self.excluded_message_body {
+            message.trim_end().to_string()
+        } else {
+            summary_of(&message)
+        };
+        patterns.is_match(&message)
+    }
+
+    /// Whether the path is excluded from the changed files and the churn.
+    fn is_excluded(&self, path: Option<&Path>) -> bool {
+        self.excluded_paths
+            .as_ref()
+            .zip(path)
+            .is_some_and(|(excluded_paths, path)| excluded_paths.is_match(path))
+    }
+
+    /// Whether the commit is a pull request: a merge commit, or a commit whose summary matches the
+    /// pull request pattern.
+    fn is_pull_request(&self, commit: &Commit) -> bool {
+        commit.parent_count() >= 2
+            || self
+                .pr_pattern
+                .as_ref()
+                .is_some_and(|pattern| pattern.is_match(&summary_of(&decode_message(commit))))
+    }
+
+    /// Extracts the pull request number from the summary, with the first capture group of the pull
+    /// request pattern, or the first `#<number>` if the pattern doesn't match.
+    fn pr_number(&self, summary: &str) -> Option<u64> {
+        self.pr_pattern
+            .as_ref()
+            .and_then(|pattern| pattern.captures(summary))
+            .or_else(|| PR_NUMBER.captures(summary))
+            .and_then(|captures| captures.get(1))
+            .and_then(|number| number.as_str().parse().ok())
+    }
+
+    /// Applies the maximum churn to the log. Returns `None` if the log should be skipped.
+    fn apply_max_churn(&self, mut log: GitLog) -> Option<GitLog> {
+        match self.max_churn {
+            Some(max) if log.churn() > max => match self.max_churn_mode {
+                MaxChurnMode::Skip => None,
+                MaxChurnMode::Flag => {
+                    log.over_max_churn = true;
+                    Some(log)
+                }
+            },
+            _ => Some(log),
+        }
+    }
+}
+
+/// What to do with a commit whose churn exceeds the maximum churn.
+#[derive(Debug, Default, Clone, Copy, ValueEnum)]
+pub enum MaxChurnMode {
+    /// Do not store the commit at all
+    #[default]
+    Skip,
+    /// Store the commit with over_max_churn set, and NULL insertions, deletions, and effort_score
+    Flag,
+}
+
+/// What the size of a repository on disk covers.
+#[derive(Debug, Default, Clone, Copy, ValueEnum)]
+pub enum SizeScope {
+    /// The git directory (`.git`) only, i.e. the history
+    #[default]
+    GitDir,
+    /// The working tree, including the git directory and untracked or ignored files
+    Worktree,
+}
+
+/// Source of the repository name. Repositories with the same name share the same row in the
+/// repositories table, so the source also decides which repositories are merged together.
+#[derive(Debug, Default, Clone, Copy, ValueEnum)]
+pub enum NameSource {
+    /// Directory name of the repository
+    #[default]
+    Dir,
+    /// `org/repo` derived from the origin remote URL, so clones of the same remote are merged
+    Remote,
+    /// Short hash of the root commit, a stable id shared by clones and forks of the same history
+    RootCommit,
+}
+
+/// Source of the commit date time.
+#[derive(Debug, Default, Clone, Copy, ValueEnum)]
+pub enum DateSource {
+    /// When the work was originally written, preserved across rebases and cherry-picks
+    Author,
+    /// When the commit was last applied, e.g. by a rebase
+    #[default]
+    Committer,
+}
+
+impl DateSource {
+    /// Time of the commit from this source.
+    fn time_of(self, commit: &Commit) -> Time {
+        match self {
+            DateSource::Author => commit.author().when(),
+            DateSource::Committer => commit.time(),
+        }
+    }
+}
+
 /// The initial state of the git repository.
 pub struct Uninitialized {
     name: String,
@@ -39,7 +350,12 @@ pub struct Uninitialized {
 pub struct Opened {
     name: String,
     repo: Repository,
-    head: Oid,
+    /// HEAD commit, `None` if HEAD is unborn, i.e. the repository has no commits yet
+    head: Option<Oid>,
+    /// Tree -> number of files in it, shared by the commits as most subtrees do not change
+    tree_file_counts: RefCell<HashMap<Oid, usize>>,
+    /// Commit -> generation number, shared by the commits as they share their ancestors
+    generations: RefCell<HashMap<Oid, usize>>,
 }
 
 /// The state of the git repository after it has been analyzed. After successful analysis, we can
@@ -47,7 +363,33 @@ pub struct Opened {
 pub struct Analyzed {
     name: String,
     url: String,
+    is_dirty: Option<bool>,
     logs: Vec<GitLog>,
+    reflog: Vec<ReflogEntry>,
+    branches: Vec<Branch>,
+    line_ownership: Vec<LineOwnership>,
+    reachable_commits: Option<HashSet<Oid>>,
+    /// Start of the analysis, `None` if no commit was analyzed
+    analyzed_commit: Option<Oid>,
+    /// Whether only the commits since the previous commit were analyzed
+    incremental: bool,
+    catalog: Catalog,
+}
+
+/// Metadata of a repository, for an inventory of repositories.
+#[derive(Debug, Clone)]
+pub struct Catalog {
+    /// Path of the working tree, or of the repository if bare
+    pub path: String,
+    /// Short name of the default branch, if any
+    pub default_branch: Option<String>,
+    /// Hash of the HEAD commit, `None` if the repository has no commits
+    pub head_commit: Option<String>,
+    /// Number of commits reachable from the start, including merges. Only counted in the catalog
+    /// mode.
+    pub commit_count: Option<usize>,
+    /// Size on disk in bytes, if measured
+    pub size_bytes: Option<u64>,
 }
 
 impl GitRepository<Uninitialized> {
@@ -75,6 +417,12 @@ impl GitRepository<Uninitialized> {
         Ok(Self { state: Uninitialized { path, name } })
     }
 
+    /// Overrides the name of the repository, which defaults to the directory name.
+    pub fn with_name(mut self, name: String) -> Self {
+        self.state.name = name;
+        self
+    }
+
     pub fn open(self) -> Result<GitRepository<Opened>> {
         self.try_into()
     }
@@ -86,116 +434,630 @@ impl TryFrom<GitRepository<Uninitialized>> for GitRepository<Opened> {
 
     fn try_from(r: GitRepository<Uninitialized>) -> Result<Self, Self::Error> {
         let repo = Repository::open(&r.path)?;
-        let head = repo
-            .head()?
-            .target()
-            .ok_or(git2::Error::from_str("failed to get OID to HEAD"))?;
-        Ok(Self { state: Opened { repo, name: r.name.clone(), head } })
+        let head = match repo.head() {
+            Ok(head) => Some(
+                head.target()
+                    .ok_or(git2::Error::from_str("failed to get OID to HEAD"))?,
+            ),
+            Err(e) if e.code() == ErrorCode::UnbornBranch => None,
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            state: Opened {
+                repo,
+                name: r.name.clone(),
+                head,
+                tree_file_counts: Default::default(),
+                generations: Default::default(),
+            },
+        })
     }
 }
 
 impl GitRepository<Opened> {
+    /// Returns a lazy iterator over the commit history of the git repository, newest first. Merge
+    /// commits, commits outside `since` and `until`, and commits over the maximum churn in the skip
+    /// mode, are skipped, and each item is produced on demand, so consumers can stop early without
+    /// paying for the rest of the history. Errors while reading a commit are propagated through the
+    /// item.
+    pub fn commits<'a>(
+        &'a self,
+        options: &'a AnalyzeOptions,
+    ) -> Result<impl Iterator<Item = Result<GitLog>> + 'a> {
+        let mut revwalk = self.repo.revwalk()?;
+        revwalk.set_sorting(git2::Sort::TIME)?;
+        revwalk.push(self.start(options)?)?;
+        if let Some(previous) = self.previous_commit(options)? {
+            revwalk.hide(previous)?;
+        }
+
+        Ok(revwalk.filter_map(move |oid| {
+            if oid.as_ref().is_ok_and(|oid| options.stored_commits.contains(oid)) {
+                return None;
+            }
+            let commit = match oid.and_then(|oid| self.repo.find_commit(oid)) {
+                Ok(commit) => commit,
+                Err(e) => return Some(Err(e.into())),
+            };
+            if options.merges_only {
+                if !options.is_pull_request(&commit) {
+                    return None;
+                }
+            } else if commit.parent_count() >= 2 && !options.include_merges {
+                return None; // ignore merge commits
+            }
+            let time = options.date.time_of(&commit).seconds();
+            if options.since.is_some_and(|since| time < since)
+                || options.until.is_some_and(|until| time > until)
+            {
+                return None;
+            }
+            if options.year.as_ref().is_some_and(|year| {
+                let time = options.date.time_of(&commit);
+                !year.contains(&(time.seconds() + time.offset_minutes() as i64 * 60))
+            }) {
+                return None;
+            }
+            if options.is_excluded_message(&commit) {
+                return None;
+            }
+            match self.to_log(&commit, options) {
+                Ok(log) => options.apply_max_churn(log).map(Ok),
+                Err(e) => Some(Err(e)),
+            }
+        }))
+    }
+
+    /// Resolves the commit to start the analysis from.
+    fn start(&self, options: &AnalyzeOptions) -> Result<Oid> {
+        match &options.rev {
+            Some(rev) => Ok(self.repo.revparse_single(rev)?.peel_to_commit()?.id()),
+            None => self.head.ok_or_else(|| anyhow!("the repository has no commits")),
+        }
+    }
+
+    /// The previous commit of the options, if it is the start or one of its ancestors. A commit
+    /// which is not in the repository anymore is not an ancestor either.
+    fn previous_commit(&self, options: &AnalyzeOptions) -> Result<Option<Oid>> {
+        let Some(previous) = options.previous_commit else {
+            return Ok(None);
+        };
+        let start = self.start(options)?;
+        let is_ancestor =
+            previous == start || self.repo.graph_descendant_of(start, previous).unwrap_or(false);
+        Ok(is_ancestor.then_some(previous))
+    }
+
+    /// Name of the repository once analyzed: the name derived from the source of the options, or
+    /// the name it was opened with.
+    pub fn name(&self, options: &AnalyzeOptions) -> String {
+        match options.name_from {
+            NameSource::Dir => None,
+            NameSource::Remote => self.remote_url().as_deref().and_then(name_from_remote_url),
+            NameSource::RootCommit => self.root_commit(options).ok().flatten(),
+        }
+        .unwrap_or_else(|| self.name.clone())
+    }
+
+    fn remote_url(&self) -> Option<String> {
+        self.repo
+            .find_remote("origin")
+            .ok()
+            .and_then(|remote| remote.url().map(|url| url.to_string()))
+    }
+
     /// Analyzes the commit history of the git repository. If successful, returns a
-    /// `GitRepository<Analyzed>`.
-    pub fn analyze(
+    /// `GitRepository<Analyzed>`. Fails if any commit can't be read or diffed, e.g. with a missing
+    /// object, rather than leaving it out.
+    pub fn analyze(&self, options: &AnalyzeOptions) -> Result<GitRepository<Analyzed>> {
+        self.analyze_with(options, |_| {})
+    }
+
+    /// Same as [`analyze`](Self::analyze), but calls `on_log` with each log before it is
+    /// collected, so that callers can enrich or override its fields, e.g. the author name from a
+    /// directory lookup. Changing `commit_hash` is discouraged, as it is the key of the log in the
+    /// database.
+    pub fn analyze_with(
         &self,
-        author_map: Option<HashMap<String, String>>,
+        options: &AnalyzeOptions,
+        mut on_log: impl FnMut(&mut GitLog),
     ) -> Result<GitRepository<Analyzed>> {
+        let mut analyzed_commit = None;
+        let mut incremental = false;
+        let (logs, commit_count) = if self.head.is_none() && options.rev.is_none() {
+            anyhow::ensure!(options.include_empty, "the repository has no commits");
+            (Vec::new(), options.catalog.then_some(0))
+        } else if options.catalog {
+            (Vec::new(), Some(self.commit_count(options)?))
+        } else {
+            analyzed_commit = Some(self.start(options)?);
+            incremental = self.previous_commit(options)?.is_some();
+            // a commit which fails to be read fails the repository, rather than missing from it
+            let mut logs = self
+                .commits(options)?
+                .map(|log| {
+                    log.map(|mut log| {
+                        on_log(&mut log);
+                        log
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            flag_first_commits_for_authors(&mut logs);
+            if options.describe_commits {
+                let nearest_tags = self.nearest_tags()?;
+                for log in &mut logs {
+                    let oid = Oid::from_str(&log.commit_hash)?;
+                    log.nearest_tag = nearest_tags.get(&oid).cloned();
+                }
+            }
+            (logs, None)
+        };
+
+        let name = self.name(options);
+        let url = self
+            .remote_url()
+            .unwrap_or("(no remote url)".to_string())
+            .replace("git@github.com:", "https://github.com/");
+
+        let is_dirty = options.check_dirty.then(|| self.is_dirty()).transpose()?;
+        let reflog = options
+            .reflog
+            .as_deref()
+            .filter(|_| !options.catalog)
+            .map(|refname| self.reflog(refname, options))
+            .unwrap_or_default();
+        let branches = match options.branches && !options.catalog {
+            true => self.branches()?,
+            false => Vec::new(),
+        };
+        let line_ownership = match &options.blame {
+            Some(scope) if !options.catalog && (self.head.is_some() || options.rev.is_some()) => {
+                self.line_ownership(options, scope)?
+            }
+            _ => Vec::new(),
+        };
+        let reachable_commits = (options.detect_rewrites
+            && !options.catalog
+            && (self.head.is_some() || options.rev.is_some()))
+        .then(|| self.reachable_commits(options))
+        .transpose()?;
+
+        let catalog = Catalog {
+            // components() drops the trailing separator of the path libgit2 returns
+            path: self
+                .repo
+                .workdir()
+                .unwrap_or(self.repo.path())
+                .components()
+                .as_path()
+                .display()
+                .to_string(),
+            default_branch: self.default_branch(),
+            head_commit: self.head.map(|head| head.to_string()),
+            commit_count,
+            size_bytes: options.measure_size.map(|scope| self.size_bytes(scope)),
+        };
+
+        Ok(GitRepository {
+            state: Analyzed {
+                name,
+                url,
+                is_dirty,
+                logs,
+                reflog,
+                branches,
+                line_ownership,
+                reachable_commits,
+                analyzed_commit,
+                incremental,
+                catalog,
+            },
+        })
+    }
+
+    /// Number of commits reachable from the start, including merges, without reading them.
+    fn commit_count(&self, options: &AnalyzeOptions) -> Result<usize> {
         let mut revwalk = self.repo.revwalk()?;
-        revwalk.set_sorting(git2::Sort::TIME)?;
-        revwalk.push(self.head)?;
+        revwalk.push(self.start(options)?)?;
+        Ok(revwalk.count())
+    }
 
-        let commits = revwalk
-            .filter_map(|oid| oid.ok())
-            .map(|oid| self.repo.find_commit(oid))
-            .filter_map(|commit| commit.ok())
-            .filter(|commit| commit.parent_count() < 2) // ignore merge commits
-            .filter(|commit| commit.tree().is_ok())
+    /// Short name of the default branch: the branch `origin/HEAD` points to in a clone, or the
+    /// current branch otherwise. `None` for a detached HEAD without `origin/HEAD`.
+    fn default_branch(&self) -> Option<String> {
+        let origin_head = self.repo.find_reference("refs/remotes/origin/HEAD").ok();
+        if let Some(target) = origin_head.as_ref().and_then(|r| r.symbolic_target()) {
+            return Some(target.trim_start_matches("refs/remotes/origin/").to_string());
+        }
+        let head = self.repo.head().ok()?;
+        head.is_branch()
+            .then(|| head.shorthand().map(|s| s.to_string()))
+            .flatten()
+    }
+
+    /// Lists the commits reachable from the start, regardless of the filters of the analysis. Only
+    /// the commit graph is walked, so it is much cheaper than the analysis itself.
+    fn reachable_commits(&self, options: &AnalyzeOptions) -> Result<HashSet<Oid>> {
+        let mut revwalk = self.repo.revwalk()?;
+        revwalk.push(self.start(options)?)?;
+        Ok(revwalk.collect::<Result<_, _>>()?)
+    }
+
+    /// Maps each commit contained in a tag to the earliest tag containing it, i.e. the first
+    /// release which shipped it. Tags are taken in the order of the time of their commits, then of
+    /// their names, and each one claims the commits reachable from it which no earlier tag
+    /// claimed, so each commit is visited once overall. Tags which don't point to a commit are
+    /// skipped.
+    fn nearest_tags(&self) -> Result<HashMap<Oid, String>> {
+        let mut tags = self
+            .repo
+            .references_glob("refs/tags/*")?
+            .filter_map(|reference| reference.ok())
+            .filter_map(|reference| {
+                let commit = reference.peel_to_commit().ok()?;
+                let name = reference.shorthand()?.to_string();
+                Some((commit.time().seconds(), name, commit.id()))
+            })
             .collect::<Vec<_>>();
+        tags.sort();
 
-        let logs = commits
-            .iter()
-            .map(|commit| {
-                let parent_oid = (commit.parent_count() != 0)
-                    .then(|| commit.parent_id(0))
-                    .transpose()
-                    .ok()
-                    .flatten(); // if commit has no parent (is a root), return None
-
-                let parent_tree = parent_oid
-                    .and_then(|oid| self.repo.find_commit(oid).ok())
-                    .and_then(|parent_commit| parent_commit.tree().ok());
-
-                let (insertions, deletions, changed_files) = self
-                    .repo
-                    .diff_tree_to_tree(
-                        parent_tree.as_ref(),
-                        Some(&commit.tree().unwrap()),
-                        Some(
-                            DiffOptions::new()
-                                .disable_pathspec_match(true)
-                                .ignore_submodules(true)
-                                .include_typechange(true),
-                        ),
-                    )
-                    .and_then(|mut diff| {
-                        diff.find_similar(Some(
-                            &mut DiffFindOptions::new()
-                                .renames(true)
-                                .copies(true)
-                                .exact_match_only(true),
-                        ))
-                        .map(|_| {
-                            let changed_files = diff
-                                .deltas()
-                                .map(|delta| delta.new_file().path().unwrap().display().to_string())
-                                .collect::<Vec<_>>();
-
-                            let (insertions, deletions) = diff
-                                .stats()
-                                .map_or((0, 0), |stats| (stats.insertions(), stats.deletions()));
-
-                            (insertions, deletions, changed_files)
-                        })
-                    })
-                    .unwrap_or((0, 0, vec![]));
-
-                let mut author_name =
-                    commit.author().name().unwrap_or("(no author name)").to_string();
-                let author_email =
-                    commit.author().email().unwrap_or("(no author email)").to_string();
-                if let Some(map) = &author_map {
-                    if let Some(name) = map.get(&author_email) {
-                        author_name = name.clone();
-                    }
+        let mut nearest = HashMap::new();
+        for (_, name, oid) in tags {
+            let mut stack = vec![oid];
+            while let Some(oid) = stack.pop() {
+                if nearest.contains_key(&oid) {
+                    continue; // claimed by an earlier tag, along with its ancestors
                 }
+                nearest.insert(oid, name.clone());
+                stack.extend(self.repo.find_commit(oid)?.parent_ids());
+            }
+        }
+        Ok(nearest)
+    }
 
-                GitLog {
-                    commit_hash: commit.id().to_string(),
-                    parent_hash: parent_oid.unwrap_or(Oid::zero()).to_string(),
-                    author_name,
-                    author_email,
-                    commit_datetime: commit.time().seconds(),
-                    message: commit.summary().unwrap_or("(no commit summary)").to_string(),
-                    insertions,
-                    deletions,
-                    changed_files,
+    /// Short hash of the first root commit reachable from the start, in topological order.
+    fn root_commit(&self, options: &AnalyzeOptions) -> Result<Option<String>> {
+        let mut revwalk = self.repo.revwalk()?;
+        revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE)?;
+        revwalk.push(self.start(options)?)?;
+        Ok(revwalk
+            .filter_map(|oid| oid.ok())
+            .next()
+            .map(|oid| oid.to_string()[..12].to_string()))
+    }
+
+    /// Local branches, sorted by name, with the merge base of each with the default branch, local
+    /// or of `origin`. A merge base is computed per branch, walking the history of both tips.
+    fn branches(&self) -> Result<Vec<Branch>> {
+        let default_tip = self.default_branch().and_then(|name| {
+            let branch = self
+                .repo
+                .find_branch(&name, BranchType::Local)
+                .or_else(|_| self.repo.find_branch(&format!("origin/{name}"), BranchType::Remote))
+                .ok()?;
+            Some(branch.get().peel_to_commit().ok()?.id())
+        });
+        let mut branches = Vec::new();
+        for branch in self.repo.branches(Some(BranchType::Local))? {
+            let (branch, _) = branch?;
+            let (Some(name), Ok(tip)) = (branch.name()?, branch.get().peel_to_commit()) else {
+                continue; // not UTF-8, or not pointing to a commit
+            };
+            branches.push(Branch {
+                name: name.to_string(),
+                tip_commit: tip.id().to_string(),
+                merge_base: default_tip
+                    .and_then(|default_tip| merge_base(&self.repo, tip.id(), default_tip))
+                    .map(|oid| oid.to_string()),
+            });
+        }
+        branches.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(branches)
+    }
+
+    /// Blames the files of the scope at the start commit, and counts the lines attributed to each
+    /// author, normalized like the authors of the logs. Submodules, binary files, and files over
+    /// the maximum size are skipped, as well as the files which fail to blame.
+    fn line_ownership(
+        &self,
+        options: &AnalyzeOptions,
+        scope: &BlameScope,
+    ) -> Result<Vec<LineOwnership>> {
+        let start = self.start(options)?;
+        let mut paths = Vec::new();
+        self.repo
+            .find_commit(start)?
+            .tree()?
+            .walk(TreeWalkMode::PreOrder, |dir, entry| {
+                let Some(path) = entry.name().map(|name| format!("{dir}{name}")) else {
+                    return TreeWalkResult::Ok;
+                };
+                let in_scope = entry.kind() == Some(ObjectType::Blob)
+                    && scope.paths.as_ref().is_none_or(|paths| paths.is_match(&path))
+                    && self.repo.find_blob(entry.id()).is_ok_and(|blob| {
+                        blob.size() as u64 <= scope.max_bytes && !blob.is_binary()
+                    });
+                if in_scope {
+                    paths.push(path);
+                }
+                TreeWalkResult::Ok
+            })?;
+
+        let mut ownership = Vec::new();
+        for path in paths {
+            let mut blame_options = git2::BlameOptions::new();
+            blame_options.newest_commit(start);
+            let Ok(blame) = self.repo.blame_file(Path::new(&path), Some(&mut blame_options)) else {
+                continue;
+            };
+            let mut lines = BTreeMap::<String, usize>::new();
+            for hunk in blame.iter() {
+                let signature = hunk.final_signature();
+                let email = options.normalize_email(signature.email().unwrap_or_default());
+                let name = options.normalize_name(signature.name().unwrap_or_default());
+                *lines.entry(options.normalize_author_name(name, &email)).or_default() +=
+                    hunk.lines_in_hunk();
+            }
+            ownership.extend(lines.into_iter().map(|(author, lines)| LineOwnership {
+                file_path: path.clone(),
+                author,
+                lines,
+            }));
+        }
+        Ok(ownership)
+    }
+
+    /// Entries of the reflog of the ref, newest first. Empty if the ref has no reflog.
+    fn reflog(&self, refname: &str, options: &AnalyzeOptions) -> Vec<ReflogEntry> {
+        let Ok(reflog) = self.repo.reflog(refname) else {
+            return Vec::new();
+        };
+        reflog
+            .iter()
+            .map(|entry| {
+                let committer = entry.committer();
+                ReflogEntry {
+                    refname: refname.to_string(),
+                    old_oid: entry.id_old().to_string(),
+                    new_oid: entry.id_new().to_string(),
+                    committer_name: options
+                        .normalize_name(&String::from_utf8_lossy(committer.name_bytes())),
+                    committer_email: options
+                        .normalize_email(&String::from_utf8_lossy(committer.email_bytes())),
+                    timestamp: committer.when().seconds(),
+                    message: entry.message().unwrap_or_default().to_string(),
                 }
             })
-            .collect::<Vec<_>>();
+            .collect()
+    }
 
-        let url = self
+    /// Whether the working tree has any modified, staged, or untracked (but not ignored) entries.
+    /// A bare repository has no working tree, so it is always clean.
+    fn is_dirty(&self) -> Result<bool> {
+        if self.repo.is_bare() {
+            return Ok(false);
+        }
+        let statuses = self.repo.statuses(Some(
+            StatusOptions::new()
+                .include_untracked(true)
+                .include_ignored(false)
+                .exclude_submodules(true),
+        ))?;
+        Ok(statuses.iter().any(|entry| !entry.status().is_ignored()))
+    }
+
+    /// Converts a single commit into a `GitLog`. The diff is always computed against the first
+    /// parent, so a merge commit (including an octopus merge with 3+ parents) reports the changes
+    /// it brings into the first-parent line, while all of its parents are recorded in order.
+    fn to_log(&self, commit: &Commit, options: &AnalyzeOptions) -> Result<GitLog> {
+        let tree = commit.tree()?;
+        let tree_file_count = options
+            .count_tree_files
+            .then(|| self.tree_file_count(&tree))
+            .transpose()?;
+        let parent_oids = commit.parent_ids().collect::<Vec<_>>();
+        let parent_oid = parent_oids.first().copied(); // if commit is a root, return None
+
+        let parent_tree = parent_oid
+            .and_then(|oid| self.repo.find_commit(oid).ok())
+            .and_then(|parent_commit| parent_commit.tree().ok());
+
+        let (insertions, deletions, changed_files, avg_rename_similarity) = self
             .repo
-            .find_remote("origin")
-            .ok()
-            .and_then(|remote| remote.url().map(|url| url.to_string()).or(None))
-            .unwrap_or("(no remote url)".to_string())
-            .replace("git@github.com:", "https://github.com/");
+            .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut diff_options(options)))
+            .and_then(|mut diff| {
+                diff.find_similar(Some(&mut diff_find_options(options))).map(|_| {
+                    let changed_files = diff
+                        .deltas()
+                        .enumerate()
+                        .filter(|(_, delta)| !options.is_excluded(path_of(delta)))
+                        .map(|(idx, delta)| {
+                            let (insertions, deletions) = Patch::from_diff(&diff, idx)
+                                .ok()
+                                .flatten()
+                                .and_then(|patch| patch.line_stats().ok())
+                                .map_or((0, 0), |(_, insertions, deletions)| {
+                                    (insertions, deletions)
+                                });
+                            let large_blob = options
+                                .large_blob_threshold
+                                .filter(|_| {
+                                    matches!(delta.status(), Delta::Added | Delta::Modified)
+                                })
+                                .and_then(|threshold| {
+                                    let blob = self.repo.find_blob(delta.new_file().id()).ok()?;
+                                    (blob.size() > threshold)
+                                        .then(|| (blob.size(), blob.is_binary()))
+                                });
+                            let old_path = matches!(delta.status(), Delta::Renamed | Delta::Copied)
+                                .then(|| delta.old_file().path())
+                                .flatten();
+                            ChangedFile {
+                                path: path_of(&delta)
+                                    .map(|path| path.display().to_string())
+                                    .unwrap_or_default(),
+                                status: Some(change_status(delta.status())),
+                                insertions,
+                                deletions,
+                                is_new: delta.status() == Delta::Added,
+                                old_path: old_path.map(|path| path.display().to_string()),
+                                large_blob_size: large_blob.map(|(size, _)| size),
+                                large_blob_is_binary: large_blob.map(|(_, is_binary)| is_binary),
+                            }
+                        })
+                        .collect::<Vec<_>>();
 
-        Ok(GitRepository {
-            state: Analyzed { name: self.name.clone(), url, logs },
+                    let (insertions, deletions) = if options.excluded_paths.is_some() {
+                        changed_files.iter().fold((0, 0), |(insertions, deletions), file| {
+                            (insertions + file.insertions, deletions + file.deletions)
+                        })
+                    } else {
+                        diff.stats()
+                            .map_or((0, 0), |stats| (stats.insertions(), stats.deletions()))
+                    };
+
+                    let avg_rename_similarity = avg_rename_similarity(&diff, options);
+
+                    (insertions, deletions, changed_files, avg_rename_similarity)
+                })
+            })
+            .unwrap_or((0, 0, vec![], None));
+
+        let author_time = commit.author().when();
+        let time = options.date.time_of(commit);
+        let full_message = decode_message(commit);
+        let full_message = full_message.trim_end();
+        let summary = Some(summary_of(full_message))
+            .filter(|summary| !summary.is_empty())
+            .unwrap_or("(no commit summary)".to_string());
+
+        let author_email =
+            options.normalize_email(commit.author().email().unwrap_or("(no author email)"));
+        let author_name = options.normalize_author_name(
+            options.normalize_name(commit.author().name().unwrap_or("(no author name)")),
+            &author_email,
+        );
+
+        Ok(GitLog {
+            commit_hash: commit.id().to_string(),
+            short_hash: commit.as_object().short_id()?.as_str().map(|s| s.to_string()),
+            parent_hash: parent_oid.unwrap_or(Oid::zero()).to_string(),
+            parent_hashes: parent_oids.iter().map(|oid| oid.to_string()).collect(),
+            author_name,
+            author_email,
+            commit_datetime: time.seconds(),
+            timezone_offset: time.offset_minutes(),
+            time_skew_seconds: Some(commit.committer().when().seconds() - author_time.seconds()),
+            is_first_commit_for_author: false, // flagged once all the logs are collected
+            nearest_tag: None,                 // described once all the logs are collected
+            off_hours: options
+                .working_hours
+                .is_off_hours(author_time.seconds(), author_time.offset_minutes()),
+            message_encoding: commit.message_encoding().map(|encoding| encoding.to_string()),
+            pr_number: options.merges_only.then(|| options.pr_number(&summary)).flatten(),
+            summary_too_long: summary.chars().count()
+                > options.max_summary_width.unwrap_or(DEFAULT_MAX_SUMMARY_WIDTH),
+            message_char_length: full_message.chars().count(),
+            message_line_count: full_message.split('\n').count(),
+            insertions,
+            deletions,
+            over_max_churn: false,
+            avg_rename_similarity,
+            tree_file_count,
+            generation: Some(self.generation(commit.id())),
+            raw_author: options
+                .store_raw_identity
+                .then(|| raw_header_field(commit, "author"))
+                .flatten(),
+            raw_committer: options
+                .store_raw_identity
+                .then(|| raw_header_field(commit, "committer"))
+                .flatten(),
+            signer_email: options.store_signer.then(|| self.signer(commit)).flatten(),
+            directories: touched_directories(
+                &changed_files,
+                options.dir_depth.unwrap_or(DEFAULT_DIR_DEPTH),
+            ),
+            changed_files,
+            message: summary,
         })
     }
+
+    /// Generation number of the commit, the length of the longest path to a root commit. A root
+    /// commit is 0, and any other commit, including merges, is the max of its parents plus one.
+    /// Parents missing from the repository, e.g. in a shallow clone, are treated as roots.
+    fn generation(&self, oid: Oid) -> usize {
+        let mut generations = self.generations.borrow_mut();
+        // Iterative DFS, as a recursive one overflows the stack on long histories
+        let mut stack = vec![oid];
+        while let Some(&top) = stack.last() {
+            if generations.contains_key(&top) {
+                stack.pop();
+                continue;
+            }
+            let parents = self
+                .repo
+                .find_commit(top)
+                .map(|commit| commit.parent_ids().collect::<Vec<_>>())
+                .unwrap_or_default();
+            let pending = parents
+                .iter()
+                .filter(|parent| !generations.contains_key(parent))
+                .copied()
+                .collect::<Vec<_>>();
+            if pending.is_empty() {
+                let generation = parents.iter().map(|parent| generations[parent] + 1).max();
+                generations.insert(top, generation.unwrap_or(0));
+                stack.pop();
+            } else {
+                stack.extend(pending);
+            }
+        }
+        generations[&oid]
+    }
+
+    /// Identity of the signer declared in the signature of the commit. `None` if the commit is not
+    /// signed, or the signature doesn't declare it.
+    fn signer(&self, commit: &Commit) -> Option<String> {
+        let (signature, _) = self.repo.extract_signature(&commit.id(), None).ok()?;
+        signer_identity(signature.as_str()?)
+    }
+
+    /// Total size in bytes of the files under the git directory or the working tree. Symbolic links
+    /// are not followed, and unreadable entries are ignored.
+    fn size_bytes(&self, scope: SizeScope) -> u64 {
+        let dir = match scope {
+            SizeScope::GitDir => self.repo.path(),
+            SizeScope::Worktree => self.repo.workdir().unwrap_or(self.repo.path()),
+        };
+        WalkDir::new(dir)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .filter_map(|entry| entry.metadata().ok())
+            .map(|metadata| metadata.len())
+            .sum()
+    }
+
+    /// Number of files in the tree, recursively. Submodules are not counted. The count of each
+    /// subtree is cached, so only the subtrees changed since an already counted commit are walked.
+    fn tree_file_count(&self, tree: &Tree) -> Result<usize> {
+        if let Some(count) = self.tree_file_counts.borrow().get(&tree.id()) {
+            return Ok(*count);
+        }
+        let mut count = 0;
+        for entry in tree.iter() {
+            match entry.kind() {
+                Some(ObjectType::Blob) => count += 1,
+                Some(ObjectType::Tree) => {
+                    count += self.tree_file_count(&self.repo.find_tree(entry.id())?)?
+                }
+                _ => {}
+            }
+        }
+        self.tree_file_counts.borrow_mut().insert(tree.id(), count);
+        Ok(count)
+    }
 }
 
 impl GitRepository<Analyzed> {
@@ -207,8 +1069,171 @@ impl GitRepository<Analyzed> {
         &self.url
     }
 
+    /// Whether the working tree has uncommitted changes, or `None` if not checked.
+    pub fn is_dirty(&self) -> Option<bool> {
+        self.is_dirty
+    }
+
     /// Finally we can get the logs! after initializing, opening, analyzing the git repository.
     pub fn logs(&self) -> &Vec<GitLog> {
         &self.logs
     }
+
+    /// Metadata of the repository.
+    pub fn catalog(&self) -> &Catalog {
+        &self.catalog
+    }
+
+    /// Entries of the reflog, if requested.
+    pub fn reflog(&self) -> &Vec<ReflogEntry> {
+        &self.reflog
+    }
+
+    /// Local branches with their merge base with the default branch, if requested.
+    pub fn branches(&self) -> &Vec<Branch> {
+        &self.branches
+    }
+
+    /// Line ownership of the blamed files, if requested.
+    pub fn line_ownership(&self) -> &Vec<LineOwnership> {
+        &self.line_ownership
+    }
+
+    /// All the commits reachable from the start, merges and filtered out commits included, if
+    /// requested.
+    pub fn reachable_commits(&self) -> Option<&HashSet<Oid>> {
+        self.reachable_commits.as_ref()
+    }
+
+    /// The commit the analysis started from, `None` if no commit was analyzed, e.g. in the
+    /// catalog mode.
+    pub fn analyzed_commit(&self) -> Option<Oid> {
+        self.analyzed_commit
+    }
+
+    /// Whether only the commits since the previous commit of the options were analyzed, so the
+    /// logs miss the earlier ones.
+    pub fn is_incremental(&self) -> bool {
+        self.incremental
+    }
+}
+
+/// Options of the diff of each commit against its first parent.
+fn diff_options(options: &AnalyzeOptions) -> DiffOptions {
+    let mut diff_options = DiffOptions::new();
+    diff_options
+        .disable_pathspec_match(true)
+        .ignore_submodules(true)
+        .include_typechange(true)
+        .ignore_whitespace(options.ignore_whitespace);
+    if let Some(lines) = options.context_lines {
+        diff_options.context_lines(lines);
+    }
+    if let Some(lines) = options.interhunk_lines {
+        diff_options.interhunk_lines(lines);
+    }
+    diff_options
+}
+
+/// Options of the rename and copy detection of the diff.
+fn diff_find_options(options: &AnalyzeOptions) -> DiffFindOptions {
+    let mut find_options = DiffFindOptions::new();
+    find_options.renames(true).copies(true);
+    match options.rename_threshold {
+        Some(threshold) => find_options.rename_threshold(threshold).copy_threshold(threshold),
+        None => find_options.exact_match_only(true),
+    };
+    find_options
+}
+
+/// Path of the file of the delta: the new path, or the old one if the file is deleted.
+fn path_of<'a>(delta: &DiffDelta<'a>) -> Option<&'a Path> {
+    delta.new_file().path().or(delta.old_file().path())
+}
+
+fn change_status(delta: Delta) -> ChangeStatus {
+    match delta {
+        Delta::Added => ChangeStatus::Added,
+        Delta::Deleted => ChangeStatus::Deleted,
+        Delta::Renamed => ChangeStatus::Renamed,
+        Delta::Copied => ChangeStatus::Copied,
+        // the other kinds of deltas are not in a diff of two trees
+        _ => ChangeStatus::Modified,
+    }
+}
+
+/// Average similarity (0 to 100) of the renamed and copied files of the diff, or `None` if it has
+/// none. Similarity is not exposed by git2, so it is read from the raw format of the diff, e.g.
+/// `:100644 100644 <old> <new> R087\told new`.
+fn avg_rename_similarity(diff: &Diff, options: &AnalyzeOptions) -> Option<f64> {
+    let mut similarities = Vec::new();
+    diff.print(DiffFormat::Raw, |delta, _, line| {
+        if matches!(delta.status(), Delta::Renamed | Delta::Copied)
+            && !options.is_excluded(path_of(&delta))
+        {
+            let content = String::from_utf8_lossy(line.content());
+            let similarity = content
+                .split('\t')
+                .next()
+                .and_then(|header| header.rsplit(' ').next())
+                .and_then(|status| status.get(1..)?.parse::<u16>().ok());
+            similarities.extend(similarity);
+        }
+        true
+    })
+    .ok()?;
+    (!similarities.is_empty())
+        .then(|| similarities.iter().map(|s| *s as f64).sum::<f64>() / similarities.len() as f64)
+}
+
+/// Best common ancestor of the two commits, e.g. where a feature branch diverged from the default
+/// branch, or `None` if they have no common history. It walks the history of both commits, so it is
+/// meant to be called once per branch tip rather than per commit.
+pub fn merge_base(repo: &Repository, a: Oid, b: Oid) -> Option<Oid> {
+    repo.merge_base(a, b).ok()
+}
+
+/// Message of the commit, decoded with the encoding declared in its `encoding` header, or as UTF-8
+/// without the header. Falls back to lossy UTF-8 if the encoding is unknown or the message is
+/// invalid in it.
+fn decode_message(commit: &Commit) -> String {
+    let bytes = commit.message_bytes();
+    commit
+        .message_encoding()
+        .and_then(|label| Encoding::for_label(label.as_bytes()))
+        .and_then(|encoding| encoding.decode_without_bom_handling_and_without_replacement(bytes))
+        .map(|message| message.into_owned())
+        .unwrap_or_else(|| String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// Summary of the message, like [`Commit::summary`]: its first paragraph, with the line breaks
+/// replaced by spaces.
+fn summary_of(message: &str) -> String {
+    message
+        .trim_start()
+        .lines()
+        .map(str::trim)
+        .take_while(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Value of the field of the raw commit header, e.g. `Jane Doe <jane@example.com> 1700000000 +0900`
+/// for `author`, exactly as stored by git.
+fn raw_header_field(commit: &Commit, field: &str) -> Option<String> {
+    commit.raw_header_bytes().split(|b| *b == b'\n').find_map(|line| {
+        line.strip_prefix(field.as_bytes())
+            .and_then(|rest| rest.strip_prefix(b" "))
+            .map(|value| String::from_utf8_lossy(value).into_owned())
+    })
+}
+
+/// Derives `org/repo` from a remote URL, such as `git@github.com:org/repo.git` or
+/// `https://github.com/org/repo`.
+fn name_from_remote_url(url: &str) -> Option<String> {
+    let path = url.trim_end_matches('/').trim_end_matches(".git");
+    let mut segments = path.rsplit(['/', ':']).filter(|s| !s.is_empty());
+    let repo = segments.next()?;
+    let org = segments.next()?;
+    Some(format!("{org}/{repo}"))
 }
@@ -0,0 +1,218 @@
+/// Discovers and analyzes the repositories under a root directory entirely in memory, without
+/// a database, for embedding the analysis into other programs.
+use std::{
+    ffi::OsStr,
+    num::NonZeroUsize,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, PoisonError,
+    },
+    thread,
+};
+
+use anyhow::{anyhow, Result};
+use git2::Repository;
+use ignore::{WalkBuilder, WalkState};
+use walkdir::WalkDir;
+
+use crate::{
+    analyzer::panic_message,
+    repository::{AnalyzeOptions, Analyzed, GitRepository},
+};
+
+/// Options of [`discover`] and [`analyze_all`].
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    /// Analyze each directory under the root as a repository, instead of the root itself
+    pub recursive: bool,
+
+    /// Maximum depth of the directories under the root, when recursive. No limit if 0.
+    pub max_depth: usize,
+
+    /// Number of threads listing the directories, when recursive. Serially, in the order of the
+    /// filesystem, if 1.
+    pub discovery_threads: NonZeroUsize,
+
+    /// Number of repositories analyzed at once
+    pub num_threads: NonZeroUsize,
+
+    /// Options of the analysis of each repository
+    pub analyze_options: AnalyzeOptions,
+}
+
+impl Default for ScanOptions {
+    /// Same as the defaults of the command line, with `--recursive`.
+    fn default() -> Self {
+        Self {
+            recursive: true,
+            max_depth: 1,
+            discovery_threads: NonZeroUsize::MIN,
+            num_threads: NonZeroUsize::new(8).unwrap(),
+            analyze_options: AnalyzeOptions::default(),
+        }
+    }
+}
+
+/// A repository analyzed by [`analyze_all`].
+pub struct AnalyzedRepository {
+    /// Path of the directory of the repository
+    pub path: PathBuf,
+
+    /// The analyzed repository, with its metadata and logs, or the error which stopped its
+    /// analysis
+    pub result: Result<GitRepository<Analyzed>>,
+}
+
+/// Lists the repositories to analyze: the root itself, or, when recursive, the directories under
+/// it which are git repositories, pruning the `.git` directories.
+pub fn discover(root: &Path, options: &ScanOptions) -> Vec<PathBuf> {
+    if !options.recursive {
+        return vec![root.to_path_buf()];
+    }
+    walk(root, options.max_depth, options.discovery_threads, |file_name| file_name != ".git")
+        .into_iter()
+        .filter(|path| Repository::open(path).is_ok())
+        .collect()
+}
+
+/// Discovers the repositories under the root and analyzes them concurrently, like the command
+/// line does minus the storage, and returns them in the order of [`discover`]. A repository which
+/// fails, or panics, doesn't stop the others: its error is returned in its
+/// [`AnalyzedRepository::result`]. The repositories are named after their directories.
+///
+/// The analysis runs on scoped threads of its own rather than on an async runtime, so it can be
+/// called from any context, including from within an async runtime.
+pub fn analyze_all(root: &Path, options: &ScanOptions) -> Vec<AnalyzedRepository> {
+    let paths = discover(root, options);
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..paths.len()).map(|_| None).collect::<Vec<_>>());
+    thread::scope(|scope| {
+        for _ in 0..options.num_threads.get().min(paths.len()) {
+            scope.spawn(|| {
+                // each thread takes the next repository once it is free
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(path) = paths.get(index) else {
+                        break;
+                    };
+                    // a panic fails the repository instead of losing the results of the others
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                        analyze(path, &options.analyze_options)
+                    }))
+                    .unwrap_or_else(|payload| {
+                        Err(anyhow!("panicked: {}", panic_message(&*payload)))
+                    });
+                    results.lock().unwrap_or_else(PoisonError::into_inner)[index] = Some(result);
+                }
+            });
+        }
+    });
+    paths
+        .into_iter()
+        .zip(results.into_inner().unwrap_or_else(PoisonError::into_inner))
+        .map(|(path, result)| AnalyzedRepository {
+            path,
+            result: result.unwrap_or_else(|| Err(anyhow!("not analyzed"))),
+        })
+        .collect()
+}
+
+fn analyze(path: &Path, options: &AnalyzeOptions) -> Result<GitRepository<Analyzed>> {
+    GitRepository::try_new(path.to_path_buf())?.open()?.analyze(options)
+}
+
+/// Lists the directories under the root, except the root itself, down to `max_depth` levels (no
+/// limit if 0). `visit` is called with the name of each visited entry, and prunes it if it
+/// returns `false`. A bare repository is listed, but not descended into, as its directories are
+/// the internals of the repository. With a single thread, the directories are in the order of the
+/// filesystem.
+pub(crate) fn walk(
+    root: &Path,
+    max_depth: usize,
+    threads: NonZeroUsize,
+    visit: impl Fn(&OsStr) -> bool + Sync,
+) -> Vec<PathBuf> {
+    match threads.get() {
+        1 => walk_serially(root, max_depth, visit),
+        threads => walk_in_parallel(root, max_depth, threads, visit),
+    }
+}
+
+fn walk_serially(root: &Path, max_depth: usize, visit: impl Fn(&OsStr) -> bool) -> Vec<PathBuf> {
+    let mut walker = WalkDir::new(root);
+    if max_depth > 0 {
+        walker = walker.max_depth(max_depth);
+    }
+    let mut entries = walker.into_iter().filter_entry(|e| visit(e.file_name()));
+    let mut directories = Vec::new();
+    while let Some(entry) = entries.next() {
+        let Ok(entry) = entry else {
+            continue;
+        };
+        if entry.depth() == 0 || !entry.file_type().is_dir() {
+            continue; // skip root directory
+        }
+        if is_bare_repository(entry.path()) {
+            entries.skip_current_dir();
+        }
+        directories.push(entry.into_path());
+    }
+    directories
+}
+
+/// Same as [`walk_serially`], but visits the directories with the threads, and sorts them by path,
+/// as the threads visit them in no particular order. All the filters of the walker, such as
+/// `.gitignore` files, are disabled, to visit the same entries.
+fn walk_in_parallel(
+    root: &Path,
+    max_depth: usize,
+    threads: usize,
+    visit: impl Fn(&OsStr) -> bool + Sync,
+) -> Vec<PathBuf> {
+    let directories = Mutex::new(Vec::new());
+    WalkBuilder::new(root)
+        .standard_filters(false)
+        .max_depth((max_depth > 0).then_some(max_depth))
+        .threads(threads)
+        .build_parallel()
+        .run(|| {
+            Box::new(|entry| {
+                let Ok(entry) = entry else {
+                    return WalkState::Continue;
+                };
+                if entry.depth() == 0 {
+                    return WalkState::Continue; // skip root directory
+                }
+                if !visit(entry.file_name()) {
+                    return WalkState::Skip;
+                }
+                if !entry.file_type().is_some_and(|file_type| file_type.is_dir()) {
+                    return WalkState::Continue;
+                }
+                let bare = is_bare_repository(entry.path());
+                directories.lock().unwrap().push(entry.into_path());
+                if bare {
+                    WalkState::Skip
+                } else {
+                    WalkState::Continue
+                }
+            })
+        });
+    let mut directories = directories.into_inner().unwrap();
+    directories.sort();
+    directories
+}
+
+/// Whether the directory looks like a repository, i.e. has a `.git` directory, or file for a
+/// submodule or a worktree, or is a bare repository, without opening it.
+pub(crate) fn is_repository(path: &Path) -> bool {
+    path.join(".git").exists() || is_bare_repository(path)
+}
+
+/// Whether the directory looks like a bare repository, i.e. has the `HEAD`, `objects` and `refs`
+/// entries git itself looks for, without opening it.
+fn is_bare_repository(path: &Path) -> bool {
+    path.join("HEAD").is_file() && path.join("objects").is_dir() && path.join("refs").is_dir()
+}
@@ -0,0 +1,81 @@
+/// Runs a user-supplied [Rhai](https://rhai.rs) script on each commit before it is stored, to
+/// derive org-specific attributes without forking the tool.
+use anyhow::{Context, Result};
+use camino::Utf8Path;
+use rhai::{Array, Dynamic, Engine, Map, Scope, AST};
+
+use crate::log::GitLog;
+
+/// A compiled script, shared by all the repositories of a scan.
+#[derive(Debug)]
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+}
+
+impl Script {
+    /// Compiles the script at the path, so a syntax error is reported before the scan starts.
+    pub fn load(path: &Utf8Path) -> Result<Self> {
+        let source =
+            std::fs::read_to_string(path).with_context(|| format!("failed to read {path}"))?;
+        let mut engine = Engine::new();
+        // stdout may be the database itself, with `--database -`
+        engine.on_print(|text| eprintln!("{text}"));
+        let ast = engine
+            .compile(source)
+            .with_context(|| format!("failed to compile {path}"))?;
+        Ok(Self { engine, ast })
+    }
+
+    /// Runs the script with the fields of the log as the `log` constant, and returns the entries
+    /// the script set in the `attributes` map, with their values as text. Entries set to `()` are
+    /// dropped, so a script can unset an attribute.
+    pub fn attributes(&self, log: &GitLog) -> Result<Vec<(String, String)>> {
+        let mut scope = Scope::new();
+        scope.push_constant("log", to_map(log));
+        scope.push("attributes", Map::new());
+        self.engine
+            .run_ast_with_scope(&mut scope, &self.ast)
+            .with_context(|| format!("script failed on commit {}", log.commit_hash))?;
+        let attributes = scope
+            .get_value::<Map>("attributes")
+            .context("the script must keep `attributes` a map")?;
+        Ok(attributes
+            .into_iter()
+            .filter(|(_, value)| !value.is_unit())
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect())
+    }
+}
+
+/// Converts the fields of the log to a map, with `()` for the missing optional values.
+fn to_map(log: &GitLog) -> Map {
+    fn optional<T: Into<Dynamic>>(value: Option<T>) -> Dynamic {
+        value.map_or(Dynamic::UNIT, Into::into)
+    }
+
+    let changed_files = log
+        .changed_files
+        .iter()
+        .map(|file| Dynamic::from(file.path.clone()))
+        .collect::<Array>();
+    let mut map = Map::new();
+    map.insert("commit_hash".into(), log.commit_hash.clone().into());
+    map.insert("short_hash".into(), optional(log.short_hash.clone()));
+    map.insert("parent_hash".into(), log.parent_hash.clone().into());
+    map.insert("parent_count".into(), (log.parent_count() as i64).into());
+    map.insert("author_name".into(), log.author_name.clone().into());
+    map.insert("author_email".into(), log.author_email.clone().into());
+    map.insert("commit_datetime".into(), log.commit_datetime.into());
+    map.insert("timezone_offset".into(), (log.timezone_offset as i64).into());
+    map.insert("off_hours".into(), log.off_hours.into());
+    map.insert("message".into(), log.message.clone().into());
+    map.insert("pr_number".into(), optional(log.pr_number.map(|number| number as i64)));
+    map.insert("insertions".into(), (log.insertions as i64).into());
+    map.insert("deletions".into(), (log.deletions as i64).into());
+    map.insert("churn_ratio".into(), optional(log.churn_ratio()));
+    map.insert("generation".into(), optional(log.generation.map(|generation| generation as i64)));
+    map.insert("is_first_commit_for_author".into(), log.is_first_commit_for_author.into());
+    map.insert("changed_files".into(), changed_files.into());
+    map
+}
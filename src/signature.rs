@@ -0,0 +1,231 @@
+/// Identity of the signer of a signed commit, extracted from the signature without verifying
+/// it.
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+/// OpenPGP packet tag of a signature packet.
+const SIGNATURE_PACKET: u8 = 2;
+
+/// OpenPGP signature subpacket type of the signer's user ID.
+const SIGNERS_USER_ID: u8 = 28;
+
+/// Identity of the signer declared in the armored signature of a commit:
+///
+/// - OpenPGP: the email address of the signer's user ID subpacket, which gpg adds with the
+///   `--sender` option. `None` if the signature doesn't declare it.
+/// - SSH: the public key, in the `<type> <base64>` form of `allowed_signers`, as SSH signatures
+///   carry no user identity.
+///
+/// The identity is self-asserted, unless verified against a keyring.
+pub fn signer_identity(signature: &str) -> Option<String> {
+    if signature.contains("-----BEGIN SSH SIGNATURE-----") {
+        ssh_public_key(&dearmor(signature)?)
+    } else {
+        openpgp_signer_user_id(&dearmor(signature)?).map(|user_id| email_of(&user_id))
+    }
+}
+
+/// Decodes the base64 body of an ASCII-armored block, skipping the armor headers and the
+/// OpenPGP checksum line.
+fn dearmor(armored: &str) -> Option<Vec<u8>> {
+    let mut lines = armored
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| !line.starts_with("-----BEGIN"));
+    lines.next()?;
+    let body = lines
+        .take_while(|line| !line.starts_with("-----END"))
+        .filter(|line| !line.contains(": ") && !line.starts_with('='))
+        .collect::<String>();
+    STANDARD.decode(body).ok()
+}
+
+/// Signer's user ID of the first OpenPGP signature packet, if any.
+fn openpgp_signer_user_id(mut data: &[u8]) -> Option<String> {
+    while let Some((tag, body, rest)) = openpgp_packet(data) {
+        if tag == SIGNATURE_PACKET {
+            return signature_subpackets(body)?
+                .into_iter()
+                .find(|(kind, _)| *kind == SIGNERS_USER_ID)
+                .map(|(_, value)| String::from_utf8_lossy(value).into_owned());
+        }
+        data = rest;
+    }
+    None
+}
+
+/// Splits the first OpenPGP packet into its tag, body, and the rest of the data. Both the old and
+/// the new packet formats are supported, except for partial and indeterminate lengths.
+fn openpgp_packet(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let header = *data.first()?;
+    if header & 0x80 == 0 {
+        return None;
+    }
+    let (tag, len, offset) = if header & 0x40 != 0 {
+        let (len, size) = new_format_length(&data[1..])?;
+        (header & 0x3f, len, 1 + size)
+    } else {
+        let size = match header & 0x03 {
+            0 => 1,
+            1 => 2,
+            2 => 4,
+            _ => return None,
+        };
+        let len = data
+            .get(1..1 + size)?
+            .iter()
+            .fold(0, |len, b| (len << 8) | *b as usize);
+        ((header >> 2) & 0x0f, len, 1 + size)
+    };
+    let body = data.get(offset..offset + len)?;
+    Some((tag, body, &data[offset + len..]))
+}
+
+/// Length of a new format packet or a subpacket, and the number of bytes it is encoded in.
+fn new_format_length(data: &[u8]) -> Option<(usize, usize)> {
+    match *data.first()? as usize {
+        len @ 0..=191 => Some((len, 1)),
+        first @ 192..=223 => Some((((first - 192) << 8) + *data.get(1)? as usize + 192, 2)),
+        255 => Some((data.get(1..5)?.iter().fold(0, |len, b| (len << 8) | *b as usize), 5)),
+        _ => None, // partial body length
+    }
+}
+
+/// Hashed and unhashed subpackets of a version 4 or 5 signature packet, as (type, value).
+fn signature_subpackets(body: &[u8]) -> Option<Vec<(u8, &[u8])>> {
+    if !matches!(body.first()?, 4 | 5) {
+        return None; // version 3 signatures have no subpackets
+    }
+    let mut subpackets = Vec::new();
+    let mut offset = 4; // version, signature type, public key algorithm, hash algorithm
+    for _ in 0..2 {
+        let len = u16::from_be_bytes([*body.get(offset)?, *body.get(offset + 1)?]) as usize;
+        let mut area = body.get(offset + 2..offset + 2 + len)?;
+        while !area.is_empty() {
+            let (len, size) = new_format_length(area)?;
+            let subpacket = area.get(size..size + len)?;
+            let (kind, value) = subpacket.split_first()?;
+            subpackets.push((kind & 0x7f, value));
+            area = &area[size + len..];
+        }
+        offset += 2 + len;
+    }
+    Some(subpackets)
+}
+
+/// Public key of an SSH signature (`SSHSIG`) blob, in the `<type> <base64>` form.
+fn ssh_public_key(blob: &[u8]) -> Option<String> {
+    let rest = blob.strip_prefix(b"SSHSIG")?.get(4..)?; // skip the version
+    let key = ssh_string(rest)?;
+    let key_type = String::from_utf8_lossy(ssh_string(key)?).into_owned();
+    Some(format!("{key_type} {}", STANDARD.encode(key)))
+}
+
+/// First length-prefixed string of the SSH wire format.
+fn ssh_string(data: &[u8]) -> Option<&[u8]> {
+    let len = u32::from_be_bytes(data.get(..4)?.try_into().ok()?) as usize;
+    data.get(4..4 + len)
+}
+
+/// Email address of a `Name <email>` user ID, or the user ID itself if it has no address.
+fn email_of(user_id: &str) -> String {
+    match (user_id.rfind('<'), user_id.rfind('>')) {
+        (Some(start), Some(end)) if start < end => user_id[start + 1..end].to_string(),
+        _ => user_id.trim().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Signed by gpg with `--sender alice@example.com`.
+    const GPG_WITH_SENDER: &str = "\
+-----BEGIN PGP SIGNATURE-----
+
+iIgEABYIADAWIQQvozzawGo725jaGIFtZIH1v+OUzQUCatIQOxIcYWxpY2VAZXhh
+bXBsZS5jb20ACgkQbWSB9b/jlM1QoAD/WHXq6Gv1p6sPWmeLXSQ8B5LO/nAHKSre
+9j0A4J2HDQsA/i87HOhd2Kq4HbnIEnzIMw7/W140ITPPAsSSbXKBMIwA
+=ffll
+-----END PGP SIGNATURE-----
+";
+
+    /// Signed by gpg with the same key, without `--sender`.
+    const GPG_WITHOUT_SENDER: &str = "\
+-----BEGIN PGP SIGNATURE-----
+
+iHUEABYIAB0WIQQvozzawGo725jaGIFtZIH1v+OUzQUCatIQOwAKCRBtZIH1v+OU
+zWz4APsHYvy19heGhhtP3+smGVS0DzHig+nuVq2mRt+4CsWr2wD6A5Vv6/COv1Y7
+dqDwBN2z6tBupkPzg6/iaeVl0RqIgwM=
+=n65N
+-----END PGP SIGNATURE-----
+";
+
+    /// Signed by `ssh-keygen -Y sign -n git` with an ed25519 key.
+    const SSH: &str = "\
+-----BEGIN SSH SIGNATURE-----
+U1NIU0lHAAAAAQAAADMAAAALc3NoLWVkMjU1MTkAAAAgVijAGFLQfMnFbVZDk6CzjWYJ2A
+63FUtAseUrdKfO83cAAAADZ2l0AAAAAAAAAAZzaGE1MTIAAABTAAAAC3NzaC1lZDI1NTE5
+AAAAQG2Nou6/oC8MXte9VmNdf181rZmIKNq+pba5ZSsPGIIbVdbTRyFLKteX9bZlhtd67z
+m3R1FhEcvBKvl1H6dTogo=
+-----END SSH SIGNATURE-----
+";
+
+    /// Public key of the SSH signature, as in its `.pub` file.
+    const SSH_PUBLIC_KEY: &str =
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIFYowBhS0HzJxW1WQ5Ogs41mCdgOtxVLQLHlK3SnzvN3";
+
+    /// Armors the data in a block of the kind, e.g. `PGP SIGNATURE`.
+    fn armor(kind: &str, data: &[u8]) -> String {
+        format!("-----BEGIN {kind}-----\n\n{}\n-----END {kind}-----\n", STANDARD.encode(data))
+    }
+
+    #[test]
+    fn openpgp_signer_is_the_sender() {
+        assert_eq!(signer_identity(GPG_WITH_SENDER).as_deref(), Some("alice@example.com"));
+    }
+
+    #[test]
+    fn openpgp_signature_without_sender_has_no_signer() {
+        assert_eq!(signer_identity(GPG_WITHOUT_SENDER), None);
+    }
+
+    #[test]
+    fn ssh_signer_is_the_public_key() {
+        assert_eq!(signer_identity(SSH).as_deref(), Some(SSH_PUBLIC_KEY));
+    }
+
+    #[test]
+    fn truncated_signatures_have_no_signer() {
+        let data = dearmor(GPG_WITH_SENDER).unwrap();
+        for len in 0..data.len() {
+            let truncated = armor("PGP SIGNATURE", &data[..len]);
+            assert_eq!(signer_identity(&truncated), None, "truncated to {len} bytes");
+        }
+        // the public key comes first, so only the signature blobs truncated before its end have
+        // no signer
+        let data = dearmor(SSH).unwrap();
+        for len in 0..data.len() {
+            let truncated = armor("SSH SIGNATURE", &data[..len]);
+            let signer = signer_identity(&truncated);
+            assert!(signer.is_none() || signer.as_deref() == Some(SSH_PUBLIC_KEY), "{signer:?}");
+        }
+        assert_eq!(signer_identity(&armor("SSH SIGNATURE", &data[..20])), None);
+    }
+
+    #[test]
+    fn garbage_signatures_have_no_signer() {
+        for garbage in [
+            "",
+            "not a signature",
+            "-----BEGIN PGP SIGNATURE-----\n\nnot base64!\n-----END PGP SIGNATURE-----\n",
+            "-----BEGIN SSH SIGNATURE-----\nU1NI\n-----END SSH SIGNATURE-----\n",
+            &armor("PGP SIGNATURE", &[0x80]),
+            &armor("PGP SIGNATURE", &[0xc2, 0xff, 0xff, 0xff, 0xff, 0xff]),
+            &armor("PGP SIGNATURE", &[0x88, 0x02, 0x04, 0x00]),
+            &armor("PGP SIGNATURE", &[0xff; 64]),
+            &armor("SSH SIGNATURE", b"SSHSIG\0\0\0\x01\xff\xff\xff\xff"),
+        ] {
+            assert_eq!(signer_identity(garbage), None, "{garbage}");
+        }
+    }
+}
@@ -0,0 +1,520 @@
+/// Repository-level rollups accumulated while storing the logs, to answer common aggregate
+/// questions without heavy `GROUP BY` queries over the raw tables.
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use rusqlite::{params, Transaction};
+
+use crate::{date, log::GitLog};
+
+/// Commit which first added each file, stored in the `file_first_seen` table.
+#[derive(Debug, Default)]
+pub struct FileFirstSeen {
+    /// File path -> (commit date time, commit hash) of the earliest addition
+    first_seen: HashMap<String, (i64, String)>,
+}
+
+impl FileFirstSeen {
+    /// Records the files added by the log, keeping the earliest addition of each path, so a file
+    /// deleted and added again is first seen at its first addition.
+    pub fn add(&mut self, log: &GitLog) {
+        for file in log.changed_files.iter().filter(|file| file.is_new) {
+            let entry = self
+                .first_seen
+                .entry(file.path.clone())
+                .or_insert((log.commit_datetime, log.commit_hash.clone()));
+            if log.commit_datetime < entry.0 {
+                *entry = (log.commit_datetime, log.commit_hash.clone());
+            }
+        }
+    }
+
+    /// Stores the first seen commit of each file for the repository, replacing the previous ones.
+    /// With `merge`, for the logs of an incremental scan, the previous ones are kept instead, as
+    /// they were seen first.
+    pub fn store(&self, tx: &Transaction, repository_name: &str, merge: bool) -> Result<()> {
+        let or = if merge { "IGNORE" } else { "REPLACE" };
+        for (file_path, (_, commit_hash)) in &self.first_seen {
+            tx.execute(
+                &format!(
+                    r#"
+                INSERT OR {or} INTO file_first_seen (repository_id, file_path, commit_hash)
+                VALUES ((SELECT id FROM repositories WHERE name = ?), ?, ?);
+                "#
+                ),
+                params![repository_name, file_path, commit_hash],
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Latest commit which changed each file, stored in the `file_latest` table.
+#[derive(Debug, Default)]
+pub struct FileLatest {
+    /// File path -> (commit date time, commit hash, author name) of the latest change
+    latest: HashMap<String, (i64, String, String)>,
+    /// Previous path of a renamed file -> commit date time of its latest rename
+    renamed: HashMap<String, i64>,
+}
+
+impl FileLatest {
+    /// Records the files changed by the log, keeping the latest change of each path. Of two changes
+    /// at the same time, the first recorded is kept, which is the newest in the history walk.
+    pub fn add(&mut self, log: &GitLog) {
+        for file in &log.changed_files {
+            let latest = (log.commit_datetime, log.commit_hash.clone(), log.author_name.clone());
+            let entry = self.latest.entry(file.path.clone()).or_insert_with(|| latest.clone());
+            if log.commit_datetime > entry.0 {
+                *entry = latest;
+            }
+            if let Some(from) = file.renamed_from() {
+                let renamed = self.renamed.entry(from.to_string()).or_insert(log.commit_datetime);
+                *renamed = (*renamed).max(log.commit_datetime);
+            }
+        }
+    }
+
+    /// Stores the latest change of each file for the repository, replacing the previous ones. A
+    /// renamed file lives on under its new path, so its previous path is left out, unless it was
+    /// changed again after the rename. With `merge`, for the logs of an incremental scan, the
+    /// previous ones are only replaced by later changes and renames.
+    pub fn store(&self, tx: &Transaction, repository_name: &str, merge: bool) -> Result<()> {
+        if !merge {
+            tx.execute(
+                r#"
+                DELETE FROM file_latest
+                WHERE repository_id = (SELECT id FROM repositories WHERE name = ?);
+                "#,
+                params![repository_name],
+            )?;
+        }
+        for (file_path, renamed) in &self.renamed {
+            tx.execute(
+                r#"
+                DELETE FROM file_latest
+                WHERE repository_id = (SELECT id FROM repositories WHERE name = ?)
+                AND file_path = ? AND last_datetime <= ?;
+                "#,
+                params![repository_name, file_path, renamed],
+            )?;
+        }
+        for (file_path, (datetime, commit_hash, author)) in &self.latest {
+            if self.renamed.get(file_path).is_some_and(|renamed| renamed >= datetime) {
+                continue;
+            }
+            tx.execute(
+                r#"
+                INSERT INTO file_latest (
+                    repository_id,
+                    file_path,
+                    last_commit_hash,
+                    last_author,
+                    last_datetime
+                )
+                VALUES ((SELECT id FROM repositories WHERE name = ?), ?, ?, ?, ?)
+                ON CONFLICT (repository_id, file_path) DO UPDATE SET
+                    last_commit_hash = excluded.last_commit_hash,
+                    last_author = excluded.last_author,
+                    last_datetime = excluded.last_datetime
+                WHERE excluded.last_datetime > file_latest.last_datetime;
+                "#,
+                params![repository_name, file_path, commit_hash, author, datetime],
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Churn aggregated by file extension, stored in the `extension_stats` table.
+#[derive(Debug, Default)]
+pub struct ExtensionStats {
+    /// Extension -> (commit count, insertions, deletions)
+    stats: HashMap<String, (usize, usize, usize)>,
+}
+
+impl ExtensionStats {
+    /// Buckets the churn of each changed file of the log by its extension. A commit touching
+    /// several files with the same extension is counted once for that extension. A log over the
+    /// maximum churn is left out, as its churn is not stored.
+    pub fn add(&mut self, log: &GitLog) {
+        if log.over_max_churn {
+            return;
+        }
+        let mut seen = Vec::new();
+        for file in &log.changed_files {
+            let extension = file.extension();
+            let entry = self.stats.entry(extension.to_string()).or_default();
+            if !seen.contains(&extension) {
+                seen.push(extension);
+                entry.0 += 1;
+            }
+            entry.1 += file.insertions;
+            entry.2 += file.deletions;
+        }
+    }
+
+    /// Stores the accumulated stats for the repository, replacing the previous ones. With `merge`,
+    /// for the logs of an incremental scan, they are added to the previous ones instead.
+    pub fn store(&self, tx: &Transaction, repository_name: &str, merge: bool) -> Result<()> {
+        let on_conflict = match merge {
+            true => {
+                r#"
+                ON CONFLICT (repository_id, extension) DO UPDATE SET
+                    commit_count = commit_count + excluded.commit_count,
+                    insertions = insertions + excluded.insertions,
+                    deletions = deletions + excluded.deletions
+                "#
+            }
+            false => {
+                r#"
+                ON CONFLICT (repository_id, extension) DO UPDATE SET
+                    commit_count = excluded.commit_count,
+                    insertions = excluded.insertions,
+                    deletions = excluded.deletions
+                "#
+            }
+        };
+        for (extension, (commit_count, insertions, deletions)) in &self.stats {
+            tx.execute(
+                &format!(
+                    r#"
+                INSERT INTO extension_stats (
+                    repository_id,
+                    extension,
+                    commit_count,
+                    insertions,
+                    deletions
+                )
+                VALUES ((SELECT id FROM repositories WHERE name = ?), ?, ?, ?, ?)
+                {on_conflict};
+                "#
+                ),
+                params![
+                    repository_name,
+                    extension,
+                    *commit_count as i64,
+                    *insertions as i64,
+                    *deletions as i64
+                ],
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Fields of a log counted by the summaries computed from all the logs of a repository.
+#[derive(Debug)]
+pub struct LogRow {
+    pub author_name: String,
+    pub commit_datetime: i64,
+    /// `None` for a log over the maximum churn, as its churn is not stored
+    pub insertions: Option<i64>,
+    pub deletions: Option<i64>,
+}
+
+impl LogRow {
+    /// Reads the stored logs of the repository, including those stored by previous scans.
+    pub fn stored(tx: &Transaction, repository_name: &str) -> Result<Vec<Self>> {
+        Ok(tx
+            .prepare(
+                r#"
+                SELECT author_name, commit_datetime, insertions, deletions FROM logs
+                WHERE repository_id = (SELECT id FROM repositories WHERE name = ?)
+                "#,
+            )?
+            .query_map(params![repository_name], |row| {
+                Ok(Self {
+                    author_name: row.get(0)?,
+                    commit_datetime: row.get(1)?,
+                    insertions: row.get(2)?,
+                    deletions: row.get(3)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?)
+    }
+
+    /// The fields of the log in memory, as they would be stored.
+    pub fn of(log: &GitLog) -> Self {
+        let has_churn = !log.over_max_churn;
+        Self {
+            author_name: log.author_name.clone(),
+            commit_datetime: log.commit_datetime,
+            insertions: has_churn.then_some(log.insertions as i64),
+            deletions: has_churn.then_some(log.deletions as i64),
+        }
+    }
+
+    fn churn(&self) -> i64 {
+        self.insertions.unwrap_or(0) + self.deletions.unwrap_or(0)
+    }
+}
+
+/// Concentration of the contributions of a repository, stored in the `repository_health` table.
+/// Computed from all the logs of the repository.
+pub struct RepositoryHealth;
+
+impl RepositoryHealth {
+    /// Computes and stores the health of the repository, replacing the previous one:
+    ///
+    /// - `bus_factor`: Minimum number of authors accounting for the `threshold` (0.0 to 1.0) of the
+    ///   churn.
+    /// - `author_count`: Number of distinct author names.
+    /// - `gini`: Gini coefficient of the churn per author, 0 when evenly spread and close to 1 when
+    ///   concentrated on a single author.
+    pub fn store(
+        tx: &Transaction,
+        repository_name: &str,
+        logs: &[LogRow],
+        threshold: f64,
+    ) -> Result<()> {
+        let mut churns = HashMap::<&str, i64>::new();
+        for log in logs {
+            *churns.entry(&log.author_name).or_default() += log.churn();
+        }
+        let mut churns = churns.into_values().collect::<Vec<_>>();
+        churns.sort_unstable_by(|a, b| b.cmp(a));
+
+        let total = churns.iter().sum::<i64>() as f64;
+        let bus_factor = if total > 0.0 {
+            let mut cumulative = 0.0;
+            churns
+                .iter()
+                .take_while(|churn| {
+                    let done = cumulative >= threshold * total;
+                    cumulative += **churn as f64;
+                    !done
+                })
+                .count()
+        } else {
+            0
+        };
+
+        // With the churns in ascending order x_1..x_n, G = 2 * sum(i * x_i) / (n * sum(x)) - (n +
+        // 1) / n
+        let n = churns.len() as f64;
+        let gini = if total > 0.0 {
+            let weighted = churns
+                .iter()
+                .rev()
+                .enumerate()
+                .map(|(i, churn)| (i + 1) as f64 * *churn as f64)
+                .sum::<f64>();
+            2.0 * weighted / (n * total) - (n + 1.0) / n
+        } else {
+            0.0
+        };
+
+        tx.execute(
+            r#"
+            INSERT OR REPLACE INTO repository_health (repository_id, bus_factor, author_count, gini)
+            VALUES ((SELECT id FROM repositories WHERE name = ?), ?, ?, ?);
+            "#,
+            params![repository_name, bus_factor as i64, churns.len() as i64, gini],
+        )?;
+        Ok(())
+    }
+}
+
+/// Rhythm of the commits of each author of a repository, stored in the `author_cadence` table.
+/// Computed from all the logs of the repository.
+pub struct AuthorCadence;
+
+impl AuthorCadence {
+    /// Computes and stores the cadence of each author of the repository, replacing the previous
+    /// ones, from the gaps between their consecutive commits in time order:
+    ///
+    /// - `median_gap_days` and `mean_gap_days`: Median and mean gap in days, NULL for an author
+    ///   with a single commit, who has no gap.
+    /// - `streak_max_days`: Longest run of consecutive calendar days (UTC) with commits, 1 for a
+    ///   single commit.
+    ///
+    /// The authors are identified by their stored name, which is already normalized.
+    pub fn store(tx: &Transaction, repository_name: &str, logs: &[LogRow]) -> Result<()> {
+        let mut times = HashMap::<&str, Vec<i64>>::new();
+        for log in logs {
+            times.entry(&log.author_name).or_default().push(log.commit_datetime);
+        }
+
+        tx.execute(
+            r#"
+            DELETE FROM author_cadence
+            WHERE repository_id = (SELECT id FROM repositories WHERE name = ?);
+            "#,
+            params![repository_name],
+        )?;
+        for (author, mut times) in times {
+            times.sort_unstable();
+            let mut gaps = times
+                .windows(2)
+                .map(|pair| (pair[1] - pair[0]) as f64 / 86400.0)
+                .collect::<Vec<_>>();
+            gaps.sort_unstable_by(f64::total_cmp);
+            let mean = (!gaps.is_empty()).then(|| gaps.iter().sum::<f64>() / gaps.len() as f64);
+            let median = (!gaps.is_empty()).then(|| match gaps.len() % 2 {
+                0 => (gaps[gaps.len() / 2 - 1] + gaps[gaps.len() / 2]) / 2.0,
+                _ => gaps[gaps.len() / 2],
+            });
+
+            let mut days = times.iter().map(|time| time.div_euclid(86400)).collect::<Vec<_>>();
+            days.dedup();
+            let (mut streak, mut streak_max) = (0, 0);
+            for (i, day) in days.iter().enumerate() {
+                streak = if i > 0 && days[i - 1] + 1 == *day { streak + 1 } else { 1 };
+                streak_max = streak_max.max(streak);
+            }
+
+            tx.execute(
+                r#"
+                INSERT INTO author_cadence (
+                    repository_id,
+                    author,
+                    median_gap_days,
+                    mean_gap_days,
+                    streak_max_days
+                )
+                VALUES ((SELECT id FROM repositories WHERE name = ?), ?, ?, ?, ?);
+                "#,
+                params![repository_name, author, median, mean, streak_max],
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Pairs of authors who changed the same files, stored in the `collaboration` table as the edges
+/// of a contribution network. Computed from the stored logs, so it must be stored after them.
+pub struct Collaboration;
+
+impl Collaboration {
+    /// Computes and stores the collaboration edges of the repository, replacing the previous ones.
+    /// Each pair is stored once, with `author_a` sorting before `author_b`, along with the number
+    /// of distinct files both of them changed. The authors are identified by their stored name,
+    /// which is already normalized, so email variants of the same person are not split.
+    pub fn store(tx: &Transaction, repository_name: &str) -> Result<()> {
+        tx.execute(
+            r#"
+            DELETE FROM collaboration
+            WHERE repository_id = (SELECT id FROM repositories WHERE name = ?);
+            "#,
+            params![repository_name],
+        )?;
+        tx.execute(
+            r#"
+            INSERT INTO collaboration (repository_id, author_a, author_b, shared_files)
+            WITH touched AS (
+                SELECT DISTINCT logs.repository_id, logs.author_name, changed_files.file_path
+                FROM changed_files JOIN logs ON changed_files.commit_hash = logs.commit_hash
+                WHERE logs.repository_id = (SELECT id FROM repositories WHERE name = ?)
+            )
+            SELECT a.repository_id, a.author_name, b.author_name, COUNT(*)
+            FROM touched AS a JOIN touched AS b
+                ON a.file_path = b.file_path AND a.author_name < b.author_name
+            GROUP BY a.author_name, b.author_name;
+            "#,
+            params![repository_name],
+        )?;
+        Ok(())
+    }
+}
+
+/// Granularity of the buckets of the `activity` table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ActivityPeriod {
+    /// Calendar days
+    Day,
+    /// Weeks starting on Monday
+    Week,
+    /// Calendar months
+    Month,
+}
+
+impl ActivityPeriod {
+    /// First day of the period of the UNIX epoch, in UTC, as `YYYY-MM-DD`.
+    fn start_of(&self, epoch: i64) -> String {
+        let days = epoch.div_euclid(86400);
+        let (year, month, day) = match self {
+            ActivityPeriod::Day => date::civil_from_days(days),
+            ActivityPeriod::Week => {
+                let since_monday = (date::weekday_from_days(days) as i64 + 6) % 7;
+                date::civil_from_days(days - since_monday)
+            }
+            ActivityPeriod::Month => {
+                let (year, month, _) = date::civil_from_days(days);
+                (year, month, 1)
+            }
+        };
+        format!("{year:04}-{month:02}-{day:02}")
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            ActivityPeriod::Day => "day",
+            ActivityPeriod::Week => "week",
+            ActivityPeriod::Month => "month",
+        }
+    }
+}
+
+/// Commits, churn, and authors of a repository per period, stored in the `activity` table, as the
+/// most common time series of the dashboards. Computed from all the logs of the repository.
+pub struct Activity;
+
+impl Activity {
+    /// Computes and stores the activity of the repository for each of the periods, replacing the
+    /// previous ones. The periods are truncated from `commit_datetime` in UTC, and `period_start`
+    /// is their first day as `YYYY-MM-DD`. The churn of the logs over the maximum churn is not
+    /// counted, as it is not stored.
+    pub fn store(
+        tx: &Transaction,
+        repository_name: &str,
+        logs: &[LogRow],
+        periods: &[ActivityPeriod],
+    ) -> Result<()> {
+        tx.execute(
+            r#"
+            DELETE FROM activity
+            WHERE repository_id = (SELECT id FROM repositories WHERE name = ?);
+            "#,
+            params![repository_name],
+        )?;
+        for period in periods {
+            // period start -> (commit count, insertions, deletions, authors)
+            let mut buckets = BTreeMap::<String, (i64, i64, i64, HashSet<&str>)>::new();
+            for log in logs {
+                let bucket = buckets.entry(period.start_of(log.commit_datetime)).or_default();
+                bucket.0 += 1;
+                bucket.1 += log.insertions.unwrap_or(0);
+                bucket.2 += log.deletions.unwrap_or(0);
+                bucket.3.insert(&log.author_name);
+            }
+            for (period_start, (commit_count, insertions, deletions, authors)) in buckets {
+                tx.execute(
+                    r#"
+                    INSERT OR REPLACE INTO activity (
+                        repository_id,
+                        period_type,
+                        period_start,
+                        commit_count,
+                        insertions,
+                        deletions,
+                        author_count
+                    )
+                    VALUES ((SELECT id FROM repositories WHERE name = ?), ?, ?, ?, ?, ?, ?);
+                    "#,
+                    params![
+                        repository_name,
+                        period.as_str(),
+                        period_start,
+                        commit_count,
+                        insertions,
+                        deletions,
+                        authors.len() as i64
+                    ],
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
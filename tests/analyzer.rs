@@ -0,0 +1,2439 @@
+mod common;
+
+use std::{io::Read, process::Command};
+
+use clap::Parser;
+use common::{TestRepo, Workspace, ALICE, BOB};
+use git2::Oid;
+use git_log_to_sqlite::{
+    analyzer::Uninitialized,
+    cli::Cli,
+    database::{TempStore, Tuning},
+    diff::{diff, DiffArgs, DiffFormat, Presence},
+    extract::{extract, ExtractArgs},
+    import::{import, ImportArgs},
+    remap::{remap_authors, RemapArgs},
+    repository::AnalyzeOptions,
+    scan::{analyze_all, ScanOptions},
+    ActivityPeriod, ConfigFormat, GitRepository, GitRepositoryAnalyzer, OutputFormat, ProgressMode,
+};
+
+#[test]
+fn initial_commit_has_no_parent() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "initial");
+    repo.write("README.md", "hello\nworld\n");
+    let oid = repo.commit("Initial commit", ALICE);
+
+    ws.scan(&[]);
+
+    assert_eq!(ws.query::<String>("SELECT name FROM repositories"), "initial");
+    assert_eq!(ws.query::<String>("SELECT commit_hash FROM logs"), oid.to_string());
+    assert_eq!(ws.query::<String>("SELECT parent_hash FROM logs"), "0".repeat(40));
+    assert_eq!(ws.query::<i64>("SELECT parent_count FROM logs"), 0);
+    assert_eq!(ws.query::<i64>("SELECT insertions FROM logs"), 2);
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM commit_parents"), 0);
+    assert_eq!(ws.query::<String>("SELECT file_path FROM changed_files"), "README.md");
+}
+
+#[test]
+fn rename_records_new_path() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "rename");
+    repo.write("old.txt", "content\n");
+    repo.commit("Add file", ALICE);
+    repo.rename("old.txt", "new.txt");
+    let oid = repo.commit("Rename file", ALICE);
+
+    ws.scan(&[]);
+
+    let paths = ws.query_all::<String>(&format!(
+        "SELECT file_path FROM changed_files WHERE commit_hash = '{oid}'"
+    ));
+    assert_eq!(paths, vec!["new.txt"]);
+    assert_eq!(
+        ws.query::<i64>(&format!("SELECT insertions FROM logs WHERE commit_hash = '{oid}'")),
+        0
+    );
+    assert_eq!(
+        ws.query_all::<Option<f64>>("SELECT avg_rename_similarity FROM logs ORDER BY generation"),
+        [None, Some(100.0)]
+    );
+}
+
+#[test]
+fn change_status_is_recorded_per_file() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "status");
+    repo.write("kept.txt", "kept\n");
+    repo.write("gone.txt", "gone\n");
+    repo.write("old.txt", "moved\n");
+    repo.commit("Add files", ALICE);
+    repo.write("kept.txt", "kept\nedited\n");
+    repo.remove("gone.txt");
+    repo.rename("old.txt", "new.txt");
+    repo.write("added.txt", "added\n");
+    let oid = repo.commit("Change files", ALICE);
+
+    ws.scan(&[]);
+    let files = ws.query_all::<String>(&format!(
+        r#"
+        SELECT file_path || ' ' || status || COALESCE(' ' || old_path, '') FROM changed_files
+        WHERE commit_hash = '{oid}' ORDER BY file_path
+        "#
+    ));
+    assert_eq!(
+        files,
+        ["added.txt added", "gone.txt deleted", "kept.txt modified", "new.txt renamed old.txt"]
+    );
+}
+
+#[test]
+fn latest_change_follows_renames() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "latest");
+    repo.write("old.txt", "content\n").write("other.txt", "other\n");
+    repo.commit("Add files", ALICE);
+    repo.rename("old.txt", "new.txt");
+    repo.commit("Rename file", BOB);
+
+    ws.scan(&[]);
+
+    let latest = ws.query_all::<String>(
+        "SELECT file_path || ' ' || last_author FROM file_latest ORDER BY file_path",
+    );
+    assert_eq!(latest, ["new.txt Bob", "other.txt Alice"]);
+}
+
+#[test]
+fn message_is_decoded_with_its_encoding() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "encoding");
+    repo.write("a.txt", "a\n");
+    let (message, _, _) = encoding_rs::EUC_JP.encode("日本語の要約\n\n本文\n");
+    repo.commit_encoded(&message, "EUC-JP", ALICE);
+
+    ws.scan(&[]);
+
+    assert_eq!(ws.query::<String>("SELECT message FROM logs"), "日本語の要約");
+    assert_eq!(ws.query::<String>("SELECT message_encoding FROM logs"), "EUC-JP");
+    assert_eq!(ws.query::<i64>("SELECT message_char_length FROM logs"), 10);
+}
+
+#[test]
+fn edited_rename_is_less_similar() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "similarity");
+    let content = (1..=10).map(|i| format!("line {i}\n")).collect::<String>();
+    repo.write("old.txt", &content);
+    repo.commit("Add file", ALICE);
+    repo.rename("old.txt", "new.txt")
+        .write("new.txt", content.replace("line 10", "edited"));
+    repo.commit("Move and edit file", ALICE);
+
+    ws.scan(&["--rename-threshold", "50"]);
+
+    let similarity = ws.query::<f64>("SELECT avg_rename_similarity FROM logs WHERE generation = 1");
+    assert!((50.0..100.0).contains(&similarity), "{similarity}");
+}
+
+#[test]
+fn merge_commits_are_ignored() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "merge");
+    repo.write("a.txt", "a\n");
+    let base = repo.commit("Base", ALICE);
+    repo.write("b.txt", "b\n");
+    let side = repo.commit_with_parents("Side", BOB, vec![base], None);
+    repo.write("c.txt", "c\n");
+    let main = repo.commit("Main", ALICE);
+    repo.commit_with_parents("Merge", ALICE, vec![main, side], Some("HEAD"));
+
+    ws.scan(&[]);
+
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM logs"), 3);
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM logs WHERE parent_count > 1"), 0);
+}
+
+#[test]
+fn octopus_merge_records_every_parent() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "octopus");
+    let (merge, parents) = repo.octopus_merge();
+
+    ws.scan(&[]);
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM logs"), 4);
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM logs WHERE parent_count > 1"), 0);
+
+    ws.scan(&["--clear", "--merges-only"]);
+    assert_eq!(ws.query::<String>("SELECT commit_hash FROM logs"), merge.to_string());
+    assert_eq!(ws.query::<i64>("SELECT parent_count FROM logs"), 3);
+    assert_eq!(
+        ws.query_all::<String>("SELECT parent_hash FROM commit_parents ORDER BY parent_index"),
+        parents.map(|parent| parent.to_string())
+    );
+}
+
+#[test]
+fn merge_commits_are_included_on_demand() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "octopus");
+    let (merge, [main, first, second]) = repo.octopus_merge();
+
+    ws.scan(&["--include-merges"]);
+
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM logs"), 5);
+    assert_eq!(
+        ws.query::<i64>(&format!("SELECT parent_count FROM logs WHERE commit_hash = '{merge}'")),
+        3
+    );
+    assert_eq!(
+        ws.query_all::<String>(&format!(
+            "SELECT parent_hash FROM commit_parents WHERE commit_hash = '{merge}' ORDER BY parent_index"
+        )),
+        [main.to_string(), first.to_string(), second.to_string()]
+    );
+}
+
+#[test]
+fn commit_parents_reconstruct_the_graph_of_a_merge() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "merged");
+    repo.write("a.txt", "a\n");
+    let root = repo.commit("Root", ALICE);
+    repo.write("b.txt", "b\n");
+    let side = repo.commit_with_parents("Side", BOB, vec![root], None);
+    repo.write("c.txt", "c\n");
+    let main = repo.commit("Main", ALICE);
+    let merge = repo.commit_with_parents("Merge", ALICE, vec![main, side], Some("HEAD"));
+
+    ws.scan(&["--include-merges"]);
+
+    assert_eq!(
+        ws.query_all::<String>(
+            "SELECT message || ' ' || parent_index || ' ' || commit_parents.parent_hash \
+             FROM commit_parents JOIN logs USING (commit_hash) ORDER BY message, parent_index"
+        ),
+        [
+            format!("Main 0 {root}"),
+            format!("Merge 0 {main}"),
+            format!("Merge 1 {side}"),
+            format!("Side 0 {root}"),
+        ]
+    );
+    // the first parent is kept in logs, and the root commit has no edge
+    assert_eq!(
+        ws.query::<String>(&format!("SELECT parent_hash FROM logs WHERE commit_hash = '{merge}'")),
+        main.to_string()
+    );
+    assert_eq!(
+        ws.query::<String>(&format!("SELECT parent_hash FROM logs WHERE commit_hash = '{root}'")),
+        Oid::zero().to_string()
+    );
+}
+
+#[test]
+fn changed_analysis_options_analyze_the_whole_history_again() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "octopus");
+    let (merge, _) = repo.octopus_merge();
+    let directories = || {
+        ws.query_all::<String>(
+            "SELECT DISTINCT directory FROM commit_directories WHERE directory LIKE 'src%'",
+        )
+    };
+    let changed = |output: std::process::Output| {
+        String::from_utf8_lossy(&output.stderr).contains("the analysis options changed")
+    };
+
+    assert!(!changed(ws.scan(&[])));
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM logs"), 4);
+    assert!(!changed(ws.scan(&[])));
+
+    assert!(changed(ws.scan(&["--include-merges"])));
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM logs"), 5);
+    assert_eq!(
+        ws.query::<i64>(&format!("SELECT COUNT(*) FROM logs WHERE commit_hash = '{merge}'")),
+        1
+    );
+
+    repo.write("src/lib/a.rs", "a\n");
+    repo.commit("Add a", ALICE);
+    ws.scan(&["--include-merges", "--dir-depth", "1"]);
+    assert_eq!(directories(), ["src"]);
+    // the stored commits are replaced, rather than kept with the previous depth
+    ws.scan(&["--include-merges", "--dir-depth", "2"]);
+    assert_eq!(directories(), ["src/lib"]);
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM logs"), 6);
+}
+
+#[test]
+fn merges_only_keeps_pull_requests() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "pulls");
+    repo.write("a.txt", "a\n");
+    let base = repo.commit("Base", ALICE);
+    repo.write("b.txt", "b\n");
+    let side = repo.commit_with_parents("Side", BOB, vec![base], None);
+    repo.remove("b.txt").write("c.txt", "c\n");
+    let main = repo.commit("Squashed (#8)", ALICE);
+    repo.write("b.txt", "b\n");
+    repo.commit_with_parents(
+        "Merge pull request #7 from bob/side",
+        ALICE,
+        vec![main, side],
+        Some("HEAD"),
+    );
+
+    ws.scan(&["--merges-only"]);
+
+    assert_eq!(ws.query::<i64>("SELECT pr_number FROM logs"), 7);
+    assert_eq!(ws.query::<i64>("SELECT insertions FROM logs"), 1); // b.txt, against the first parent
+
+    ws.scan(&["--clear", "--merges-only", "--pr-pattern", r"\(#(\d+)\)$"]);
+
+    assert_eq!(ws.query_all::<i64>("SELECT pr_number FROM logs ORDER BY pr_number"), [7, 8]);
+}
+
+#[test]
+fn generation_follows_the_longest_path() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "generation");
+    repo.write("a.txt", "a\n");
+    let base = repo.commit("Base", ALICE);
+    repo.write("b.txt", "b\n");
+    let side = repo.commit_with_parents("Side 1", BOB, vec![base], None);
+    repo.write("b.txt", "bb\n");
+    let side = repo.commit_with_parents("Side 2", BOB, vec![side], None);
+    repo.write("c.txt", "c\n");
+    let main = repo.commit("Main", ALICE);
+    repo.commit_with_parents("Merge", ALICE, vec![main, side], Some("HEAD"));
+    repo.write("d.txt", "d\n");
+    repo.commit("After merge", ALICE);
+
+    ws.scan(&[]);
+
+    let generation = |message: &str| {
+        ws.query::<i64>(&format!("SELECT generation FROM logs WHERE message = '{message}'"))
+    };
+    assert_eq!(generation("Base"), 0);
+    assert_eq!(generation("Side 2"), 2);
+    assert_eq!(generation("Main"), 1);
+    assert_eq!(generation("After merge"), 4);
+}
+
+#[test]
+fn repository_health_measures_concentration() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "health");
+    repo.write("a.txt", "1\n2\n3\n");
+    repo.commit("Alice", ALICE);
+    repo.write("b.txt", "1\n");
+    repo.commit("Bob", BOB);
+
+    ws.scan(&[]);
+
+    assert_eq!(ws.query::<i64>("SELECT bus_factor FROM repository_health"), 1);
+    assert_eq!(ws.query::<i64>("SELECT author_count FROM repository_health"), 2);
+    assert_eq!(ws.query::<f64>("SELECT gini FROM repository_health"), 0.25);
+
+    ws.scan(&["--clear", "--bus-factor-threshold", "0.9"]);
+
+    assert_eq!(ws.query::<i64>("SELECT bus_factor FROM repository_health"), 2);
+}
+
+#[test]
+fn activity_is_bucketed_per_period() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "activity");
+    repo.write("a.txt", "a\n");
+    repo.commit("Tuesday", ALICE);
+    repo.write("a.txt", "a\nb\n");
+    repo.commit("Thursday", BOB);
+
+    ws.scan(&[]);
+
+    let buckets = ws.query_all::<String>(
+        r#"
+        SELECT period_type || ' ' || period_start || ' ' || commit_count || ' ' || author_count
+        FROM activity ORDER BY period_type, period_start
+        "#,
+    );
+    assert_eq!(
+        buckets,
+        ["day 2023-11-14 1 1", "day 2023-11-16 1 1", "month 2023-11-01 2 2", "week 2023-11-13 2 2"]
+    );
+}
+
+#[test]
+fn stats_only_stores_the_summaries_without_the_logs() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "stats");
+    repo.write("a.rs", "a\n");
+    repo.commit("Tuesday", ALICE);
+    repo.write("a.rs", "a\nb\n");
+    repo.commit("Sunday", (BOB.0, BOB.1, 1_700_432_000));
+
+    ws.scan(&["--output-stats-only"]);
+
+    for table in ["logs", "changed_files", "commit_parents", "file_first_seen", "file_latest"] {
+        assert_eq!(ws.query::<i64>(&format!("SELECT COUNT(*) FROM {table}")), 0, "{table}");
+    }
+    assert_eq!(ws.query_all::<String>("SELECT name FROM repositories"), ["stats"]);
+    assert_eq!(
+        ws.query_all::<String>(
+            "SELECT period_start || ' ' || commit_count || ' ' || insertions FROM activity \
+             WHERE period_type = 'week'"
+        ),
+        ["2023-11-13 2 2"]
+    );
+    assert_eq!(ws.query::<i64>("SELECT author_count FROM repository_health"), 2);
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM author_cadence"), 2);
+    assert_eq!(ws.query::<i64>("SELECT commit_count FROM extension_stats"), 2);
+}
+
+#[test]
+fn author_cadence_measures_gaps_and_streaks() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "cadence");
+    for (i, days) in [0, 1, 3].into_iter().enumerate() {
+        repo.write("a.txt", i.to_string());
+        repo.commit("Alice", ("Alice", "alice@example.com", 1_700_000_000 + days * 86400));
+    }
+    repo.write("b.txt", "b\n");
+    repo.commit("Bob", BOB);
+
+    ws.scan(&[]);
+
+    let conn = ws.connection();
+    let cadence = |author: &str| {
+        conn.query_row(
+            "SELECT median_gap_days, mean_gap_days, streak_max_days FROM author_cadence WHERE author = ?",
+            [author],
+            |row| Ok((row.get::<_, Option<f64>>(0)?, row.get::<_, Option<f64>>(1)?, row.get::<_, i64>(2)?)),
+        )
+        .unwrap()
+    };
+    assert_eq!(cadence("Alice"), (Some(1.5), Some(1.5), 2));
+    assert_eq!(cadence("Bob"), (None, None, 1));
+}
+
+#[test]
+fn blame_counts_line_ownership() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "blame");
+    repo.write("a.txt", "one\ntwo\nthree\n");
+    repo.commit("Add a", ALICE);
+    repo.write("a.txt", "one\n2\nthree\n").write("b.rs", "fn main() {}\n");
+    repo.commit("Edit a, add b", BOB);
+
+    ws.scan(&["--compute-blame", "--blame-path", "*.txt"]);
+
+    let ownership = ws.query_all::<String>(
+        "SELECT file_path || ' ' || author || ' ' || lines FROM line_ownership ORDER BY author",
+    );
+    assert_eq!(ownership, ["a.txt Alice 2", "a.txt Bob 1"]);
+}
+
+#[test]
+fn collaboration_counts_shared_files() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "collaboration");
+    repo.write("a.txt", "1\n").write("b.txt", "1\n");
+    repo.commit("Alice", ALICE);
+    repo.write("a.txt", "2\n").write("b.txt", "2\n");
+    repo.commit("Bob", BOB);
+    repo.write("a.txt", "3\n").write("c.txt", "1\n");
+    repo.commit("Alice again", ("Alice", "alice@work.example.com", 1_700_200_000));
+
+    ws.scan(&[]);
+
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM collaboration"), 0);
+
+    ws.scan(&["--clear", "--build-collaboration"]);
+
+    assert_eq!(
+        ws.query_all::<String>(
+            "SELECT author_a || '-' || author_b || ':' || shared_files FROM collaboration"
+        ),
+        ["Alice-Bob:2"]
+    );
+}
+
+#[test]
+fn churn_ratio_is_the_share_of_deletions() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "ratio");
+    repo.write("a.txt", "1\n2\n3\n4\n");
+    repo.commit("Add a", ALICE);
+    repo.write("a.txt", "1\n5\n");
+    repo.commit("Trim a", ALICE);
+    repo.rename("a.txt", "b.txt");
+    repo.commit("Rename a", ALICE);
+
+    ws.scan(&[]);
+
+    assert_eq!(
+        ws.query_all::<Option<f64>>("SELECT churn_ratio FROM logs ORDER BY generation"),
+        [Some(0.0), Some(0.75), None]
+    );
+}
+
+#[cfg(feature = "scripting")]
+#[test]
+fn script_sets_commit_attributes() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "script");
+    repo.write("a.txt", "a\n");
+    repo.commit("Add a", ALICE);
+    repo.write("a.txt", "b\n");
+    repo.commit("fix: typo", BOB);
+    let script = ws.dir.path().join("classify.rhai");
+    std::fs::write(
+        &script,
+        r#"
+        if log.message.starts_with("fix") { attributes.kind = "fix"; }
+        attributes.files = log.changed_files.len();
+        attributes.unset = ();
+        "#,
+    )
+    .unwrap();
+
+    ws.scan(&["--script", script.to_str().unwrap()]);
+
+    assert_eq!(
+        ws.query_all::<String>(
+            "SELECT logs.message || ':' || key || '=' || value FROM commit_attributes \
+             JOIN logs USING (commit_hash) ORDER BY logs.generation, key"
+        ),
+        ["Add a:files=1", "fix: typo:files=1", "fix: typo:kind=fix"]
+    );
+}
+
+#[test]
+fn since_and_until_limit_the_commits_to_the_range() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "range");
+    // 2023-11-13T12:00:00Z, 2023-11-14T22:13:20Z, and 2023-11-16T01:46:40Z
+    for (message, time) in [("Before", 1_699_876_800), ("During", ALICE.2), ("After", BOB.2)] {
+        repo.write("a.txt", message);
+        repo.commit(message, (ALICE.0, ALICE.1, time));
+    }
+
+    ws.scan(&["--since", "2023-11-14", "--until", "2023-11-15"]);
+    assert_eq!(ws.query_all::<String>("SELECT message FROM logs"), ["During"]);
+
+    // 2023-11-13T12:00:00Z is on or after, and 2023-11-14T22:13:20Z is after
+    ws.scan(&[
+        "--clear",
+        "--since",
+        "2023-11-13T21:00:00+09:00",
+        "--until",
+        "2023-11-14T22:13:19Z",
+    ]);
+    assert_eq!(ws.query_all::<String>("SELECT message FROM logs"), ["Before"]);
+
+    let output = ws.run_unchecked(&[ws.repos().to_str().unwrap(), "--since", "2023-11-14T25:00"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("expected YYYY-MM-DD or RFC 3339"));
+    let output = ws.run_unchecked(&[
+        ws.repos().to_str().unwrap(),
+        "--since",
+        "2023-11-15",
+        "--until",
+        "2023-11-14",
+    ]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--since must not be after --until"));
+}
+
+#[test]
+fn year_limits_the_commits_to_the_calendar_year() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "annual");
+    // 2022-12-31T23:59:59Z, 2023-11-14, and 2024-01-01T00:00:00Z
+    for (message, time) in
+        [("Before", 1_672_531_199), ("During", ALICE.2), ("After", 1_704_067_200)]
+    {
+        repo.write("a.txt", message);
+        repo.commit(message, (ALICE.0, ALICE.1, time));
+    }
+
+    ws.scan(&["--year", "2023"]);
+
+    assert_eq!(ws.query_all::<String>("SELECT message FROM logs"), ["During"]);
+}
+
+#[test]
+fn tree_files_are_counted_on_demand() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "tree");
+    repo.write("a.txt", "a\n").write("src/b.rs", "b\n");
+    repo.commit("Add a and b", ALICE);
+    repo.write("src/nested/c.rs", "c\n");
+    repo.commit("Add c", ("Alice", "alice@example.com", 1_700_050_000));
+    repo.remove("a.txt");
+    repo.commit("Remove a", BOB);
+
+    ws.scan(&[]);
+    assert_eq!(ws.query_all::<Option<i64>>("SELECT tree_file_count FROM logs"), [None, None, None]);
+
+    ws.scan(&["--clear", "--count-tree-files"]);
+    assert_eq!(
+        ws.query_all::<i64>("SELECT tree_file_count FROM logs ORDER BY commit_datetime"),
+        [2, 3, 2]
+    );
+}
+
+#[test]
+fn commits_are_described_by_their_earliest_tag() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "tags");
+    repo.write("a.txt", "1\n");
+    repo.commit("First", ALICE);
+    repo.write("a.txt", "2\n");
+    let first_release = repo.commit("Second", ALICE);
+    repo.write("a.txt", "3\n");
+    let second_release = repo.commit("Third", BOB);
+    repo.write("a.txt", "4\n");
+    repo.commit("Unreleased", BOB);
+    let signature = git2::Signature::new(ALICE.0, ALICE.1, &git2::Time::new(ALICE.2, 0)).unwrap();
+    let object = repo.repo.find_object(first_release, None).unwrap();
+    repo.repo
+        .tag("v1.0", &object, &signature, "Release 1.0", false)
+        .unwrap();
+    let object = repo.repo.find_object(second_release, None).unwrap();
+    repo.repo.tag_lightweight("v1.1", &object, false).unwrap();
+
+    ws.scan(&[]);
+    assert_eq!(ws.query::<Option<String>>("SELECT nearest_tag FROM logs LIMIT 1"), None);
+
+    ws.scan(&["--clear", "--describe-commits"]);
+    assert_eq!(
+        ws.query_all::<Option<String>>("SELECT nearest_tag FROM logs ORDER BY generation"),
+        [Some("v1.0".to_string()), Some("v1.0".to_string()), Some("v1.1".to_string()), None]
+    );
+}
+
+#[test]
+fn churn_is_split_per_changed_file() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "split");
+    repo.write("a.txt", "1\n2\n3\n");
+    repo.write("b.txt", "1\n2\n");
+    repo.commit("Add a and b", ALICE);
+    repo.write("a.txt", "1\n3\n4\n5\n");
+    repo.write("b.txt", "2\n");
+    repo.commit("Edit a and b", ALICE);
+
+    ws.scan(&[]);
+    assert_eq!(
+        ws.query_all::<String>(
+            r#"
+            SELECT file_path || ' +' || changed_files.insertions || ' -' || changed_files.deletions
+            FROM changed_files JOIN logs USING (commit_hash)
+            WHERE message = 'Edit a and b' ORDER BY file_path
+            "#
+        ),
+        ["a.txt +2 -1", "b.txt +0 -1"]
+    );
+    assert_eq!(
+        ws.query::<i64>(
+            r#"
+            SELECT COUNT(*) FROM logs
+            WHERE insertions = (SELECT SUM(insertions) FROM changed_files
+                    WHERE changed_files.commit_hash = logs.commit_hash)
+                AND deletions = (SELECT SUM(deletions) FROM changed_files
+                    WHERE changed_files.commit_hash = logs.commit_hash)
+            "#
+        ),
+        2
+    );
+}
+
+#[test]
+fn commits_over_max_churn_are_skipped_or_flagged() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "vendored");
+    repo.write("a.txt", "a\n");
+    repo.commit("Small", ALICE);
+    repo.write("vendor.txt", "line\n".repeat(10));
+    let large = repo.commit("Vendor a dependency", BOB);
+
+    ws.scan(&["--max-churn", "5"]);
+    assert_eq!(ws.query_all::<String>("SELECT message FROM logs"), ["Small"]);
+
+    ws.scan(&["--clear", "--max-churn", "5", "--max-churn-mode", "flag"]);
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM logs"), 2);
+    let flagged = ws.query_all::<String>("SELECT commit_hash FROM logs WHERE over_max_churn");
+    assert_eq!(flagged, [large.to_string()]);
+    assert_eq!(
+        ws.query::<Option<i64>>(&format!(
+            "SELECT insertions FROM logs WHERE commit_hash = '{large}'"
+        )),
+        None
+    );
+    assert_eq!(ws.query::<i64>("SELECT SUM(insertions) FROM extension_stats"), 1);
+}
+
+#[test]
+fn excluded_paths_have_no_churn() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "exclude");
+    repo.write("main.rs", "fn main() {}\n")
+        .write("vendor/lib.js", "1\n2\n3\n");
+    repo.commit("Vendor", ALICE);
+    let exclude = ws.dir.path().join("exclude.txt");
+    std::fs::write(&exclude, "# vendored\n\nvendor/**\n").unwrap();
+
+    ws.scan(&["--exclude-paths-from", exclude.to_str().unwrap()]);
+
+    assert_eq!(ws.query_all::<String>("SELECT file_path FROM changed_files"), ["main.rs"]);
+    assert_eq!(ws.query::<i64>("SELECT insertions FROM logs"), 1);
+}
+
+#[test]
+fn changed_files_are_counted_per_directory() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "directories");
+    repo.write("README.md", "readme\n")
+        .write("src/lib.rs", "lib\n")
+        .write("src/cli/main.rs", "main\n");
+    repo.commit("Initial commit", ALICE);
+    let directories =
+        "SELECT directory || ':' || file_count FROM commit_directories ORDER BY directory";
+
+    ws.scan(&[]);
+
+    assert_eq!(ws.query_all::<String>(directories), ["(root):1", "src:2"]);
+
+    ws.scan(&["--clear", "--dir-depth", "2"]);
+
+    assert_eq!(ws.query_all::<String>(directories), ["(root):1", "src:1", "src/cli:1"]);
+}
+
+#[test]
+fn binary_files_have_no_churn() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "binary");
+    repo.write("image.bin", [0u8, 159, 146, 150, 0, 1, 2]);
+    repo.commit("Add binary", ALICE);
+
+    ws.scan(&[]);
+
+    assert_eq!(ws.query::<String>("SELECT file_path FROM changed_files"), "image.bin");
+    assert_eq!(ws.query::<i64>("SELECT insertions + deletions FROM logs"), 0);
+}
+
+#[test]
+fn author_map_normalizes_names() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "authors");
+    repo.write("a.txt", "a\n");
+    repo.commit("By Alice", ALICE);
+    repo.write("b.txt", "b\n");
+    repo.commit("By Bob", BOB);
+    repo.write("c.txt", "c\n");
+    repo.commit("By Jane", ("jd", "jane.doe@example.com", 1_700_200_000));
+    ws.write_config(
+        r#"
+        name_from_email = true
+
+        [author_map]
+        "bob@example.com" = "Robert"
+        "#,
+    );
+
+    ws.scan(&[]);
+
+    let names = ws.query_all::<String>("SELECT author_name FROM logs ORDER BY commit_datetime");
+    assert_eq!(names, vec!["Alice", "Robert", "Jane Doe"]);
+}
+
+#[test]
+fn emails_and_names_are_normalized() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "emails");
+    repo.write("a.txt", "a\n");
+    repo.commit("Upper case", ("Jane  Doe", "Jane@Example.COM", 1_700_000_000));
+    repo.write("b.txt", "b\n");
+    repo.commit("Mapped", ("jd", "JD@Example.com", 1_700_100_000));
+    ws.write_config(
+        r#"
+        [author_map]
+        "JD@example.com" = "Jane Doe"
+        "#,
+    );
+
+    ws.scan(&[]);
+
+    let authors = ws.query_all::<String>(
+        "SELECT author_name || ' <' || author_email || '>' FROM logs ORDER BY commit_datetime",
+    );
+    assert_eq!(authors, ["Jane Doe <jane@example.com>", "Jane Doe <jd@example.com>"]);
+
+    ws.write_config("normalize_emails = false");
+    ws.scan(&["--clear"]);
+    let emails = ws.query_all::<String>("SELECT author_email FROM logs ORDER BY commit_datetime");
+    assert_eq!(emails, ["Jane@Example.COM", "JD@Example.com"]);
+}
+
+#[test]
+fn raw_identity_is_stored_on_demand() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "raw");
+    repo.write("a.txt", "a\n");
+    repo.commit("Add a", ("Jane  Doe", "Jane@Example.COM", 1_700_000_000));
+
+    ws.scan(&[]);
+    assert_eq!(ws.query::<Option<String>>("SELECT raw_author FROM logs"), None);
+
+    ws.scan(&["--clear", "--store-raw-identity"]);
+    let raw = "Jane  Doe <Jane@Example.COM> 1700000000 +0000";
+    assert_eq!(ws.query::<String>("SELECT raw_author FROM logs"), raw);
+    assert_eq!(ws.query::<String>("SELECT raw_committer FROM logs"), raw);
+    assert_eq!(
+        ws.query::<String>("SELECT author_name || ' ' || author_email FROM logs"),
+        "Jane Doe jane@example.com"
+    );
+}
+
+#[test]
+fn excluded_message_patterns_skip_commits() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "messages");
+    repo.write("a.txt", "a\n");
+    repo.commit("Fix parser", ALICE);
+    repo.write("b.txt", "b\n");
+    repo.commit("chore(release): 1.0.0", BOB);
+    ws.write_config(r#"excluded_message_patterns = ["chore\\(release\\)", "Fix"]"#);
+
+    ws.scan(&[]);
+
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM logs"), 0);
+
+    ws.write_config(
+        r#"
+        excluded_message_patterns = ["chore\\(release\\): .*", "Fix"]
+        excluded_message_anchored = true
+        "#,
+    );
+    ws.scan(&["--clear"]);
+
+    let messages = ws.query_all::<String>("SELECT message FROM logs");
+    assert_eq!(messages, vec!["Fix parser"]);
+}
+
+#[test]
+fn remap_authors_consolidates_aliases() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "remap");
+    repo.write("a.txt", "a\n");
+    repo.commit("By Alice", ALICE);
+    repo.write("b.txt", "b\n");
+    repo.commit("By Bob", BOB);
+    repo.write("c.txt", "c\n");
+    repo.commit("By Ally", ("ally", "ally@example.com", 1_700_200_000));
+    ws.scan(&[]);
+    let file = ws.dir.path().join("identities.toml");
+    std::fs::write(&file, r#""Alice" = ["ally", "alice@example.com"]"#).unwrap();
+
+    let updated = remap_authors(&RemapArgs {
+        file: file.try_into().unwrap(),
+        database: ws.database().try_into().unwrap(),
+        bus_factor_threshold: 0.5,
+    })
+    .unwrap();
+
+    assert_eq!(updated["Alice"], 1);
+    let names = ws.query_all::<String>(
+        "SELECT author_name || ' ' || author_email FROM logs ORDER BY commit_datetime",
+    );
+    assert_eq!(
+        names,
+        ["Alice alice@example.com", "Bob bob@example.com", "Alice alice@example.com"]
+    );
+    assert_eq!(
+        ws.query_all::<bool>(
+            "SELECT is_first_commit_for_author FROM logs ORDER BY commit_datetime"
+        ),
+        [true, true, false]
+    );
+    assert_eq!(
+        ws.query_all::<String>("SELECT author FROM author_cadence ORDER BY author"),
+        ["Alice", "Bob"]
+    );
+    assert_eq!(ws.query::<i64>("SELECT author_count FROM repository_health"), 2);
+    assert_eq!(ws.query::<i64>("SELECT author_count FROM activity WHERE period_type = 'month'"), 2);
+    assert_eq!(
+        ws.query::<String>("SELECT last_author FROM file_latest WHERE file_path = 'c.txt'"),
+        "Alice"
+    );
+}
+
+#[test]
+fn remap_authors_consolidates_mixed_name_and_email_aliases() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "remap");
+    let commits = [
+        ("Jane Doe", "jdoe@old.com"),
+        ("jd", "jane@new.com"),
+        ("J. Doe", "jdoe@old.com"),
+        ("Bob", "bob@example.com"),
+        ("bobby", "bob@example.com"),
+    ];
+    for (i, (name, email)) in commits.into_iter().enumerate() {
+        repo.write("a.txt", i.to_string());
+        repo.commit(name, (name, email, 1_700_000_000 + i as i64 * 1_000));
+    }
+    ws.scan(&[]);
+    let file = ws.dir.path().join("identities.toml");
+    std::fs::write(
+        &file,
+        "\"Jane Doe\" = [\"jane@new.com\", \"jdoe@old.com\", \"J. Doe\"]\nBob = [\"bobby\"]\n",
+    )
+    .unwrap();
+
+    let updated = remap_authors(&RemapArgs {
+        file: file.try_into().unwrap(),
+        database: ws.database().try_into().unwrap(),
+        bus_factor_threshold: 0.5,
+    })
+    .unwrap();
+
+    assert_eq!(updated, [("Bob".to_string(), 1), ("Jane Doe".to_string(), 3)].into());
+    assert_eq!(
+        ws.query_all::<String>(
+            "SELECT author_name || ' ' || author_email FROM logs ORDER BY commit_datetime"
+        ),
+        [
+            "Jane Doe jane@new.com",
+            "Jane Doe jane@new.com",
+            "Jane Doe jane@new.com",
+            "Bob bob@example.com",
+            "Bob bob@example.com",
+        ]
+    );
+}
+
+#[test]
+fn commit_failing_to_be_diffed_fails_the_repository() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "corrupt");
+    repo.write("a.txt", "a\n");
+    let first = repo.commit("Add a", ALICE);
+    repo.write("a.txt", "b\n");
+    repo.commit("Change a", BOB);
+    // the tree of the first commit is needed to diff both commits, but not to open HEAD
+    let tree = repo.repo.find_commit(first).unwrap().tree_id().to_string();
+    let (dir, file) = tree.split_at(2);
+    std::fs::remove_file(repo.path().join(".git/objects").join(dir).join(file)).unwrap();
+
+    let output = ws.scan(&[]);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let reason = stdout.lines().find(|line| line.contains("corrupt")).unwrap();
+    assert!(reason.contains(": failed to analyze"), "{reason}");
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM logs"), 0);
+}
+
+#[test]
+fn single_repository_fails_with_its_error() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "corrupt");
+    repo.write("a.txt", "a\n");
+    let first = repo.commit("Add a", ALICE);
+    let tree = repo.repo.find_commit(first).unwrap().tree_id().to_string();
+    let (dir, file) = tree.split_at(2);
+    std::fs::remove_file(repo.path().join(".git/objects").join(dir).join(file)).unwrap();
+
+    let output = ws.run_unchecked(&["--single", repo.path().to_str().unwrap()]);
+
+    assert!(!output.status.success());
+    // the error of git itself, not only that the repository failed
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(&format!("no match for id ({tree})")), "{stderr}");
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM repositories"), 0);
+}
+
+#[test]
+fn checkpoints_are_committed_and_cleared() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "huge");
+    for i in 0..5 {
+        repo.write("a.txt", format!("{i}\n"));
+        repo.commit(&format!("Change {i}"), ALICE);
+    }
+
+    ws.scan(&["--checkpoint-every", "2"]);
+
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM logs"), 5);
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM ingest_checkpoints"), 0);
+}
+
+#[test]
+fn interrupted_ingest_resumes_without_analyzing_the_stored_commits() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "interrupted");
+    repo.write("a.txt", "a\n");
+    let first = repo.commit("Add a", ALICE);
+    repo.write("a.txt", "b\n");
+    let second = repo.commit("Change a", BOB);
+    ws.scan(&[]);
+    repo.write("b.md", "b\n");
+    repo.commit("Add b", ALICE);
+    repo.write("a.txt", "c\n");
+    let last = repo.commit("Change a again", BOB);
+    // the ingest was interrupted after the checkpoint of the second commit
+    let conn = ws.connection();
+    conn.execute("UPDATE repositories SET analyzed_commit = NULL", [])
+        .unwrap();
+    conn.execute(
+        "INSERT INTO ingest_checkpoints VALUES ((SELECT id FROM repositories), ?)",
+        [second.to_string()],
+    )
+    .unwrap();
+    // the first commit can't be diffed anymore, so it must not be analyzed again
+    let tree = repo.repo.find_commit(first).unwrap().tree_id().to_string();
+    let (dir, file) = tree.split_at(2);
+    std::fs::remove_file(repo.path().join(".git/objects").join(dir).join(file)).unwrap();
+
+    ws.scan(&[]);
+
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM logs"), 4);
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM ingest_checkpoints"), 0);
+    assert_eq!(
+        ws.query::<i64>("SELECT commit_count FROM extension_stats WHERE extension = 'txt'"),
+        3
+    );
+    assert_eq!(
+        ws.query::<String>("SELECT commit_hash FROM file_first_seen WHERE file_path = 'a.txt'"),
+        first.to_string()
+    );
+    assert_eq!(
+        ws.query::<String>("SELECT last_commit_hash FROM file_latest WHERE file_path = 'a.txt'"),
+        last.to_string()
+    );
+    assert_eq!(
+        ws.query_all::<String>(
+            "SELECT author_name FROM logs WHERE is_first_commit_for_author ORDER BY author_name"
+        ),
+        ["Alice", "Bob"]
+    );
+}
+
+#[test]
+fn config_is_read_in_every_format() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "formats");
+    repo.write("a.txt", "a\n");
+    repo.commit("Add a", ALICE);
+    let author = || ws.query::<String>("SELECT author_name FROM logs");
+
+    ws.write_config(r#"{"author_map": {"alice@example.com": "Alice Json"}}"#);
+    ws.scan(&["--clear", "--config-format", "json"]);
+    assert_eq!(author(), "Alice Json");
+
+    ws.write_config("author_map:\n  alice@example.com: Alice Yaml\n");
+    ws.scan(&["--clear", "--config-format", "yaml"]);
+    assert_eq!(author(), "Alice Yaml");
+
+    ws.write_config("[author_map]\n\"alice@example.com\" = \"Alice Toml\"\n");
+    ws.scan(&["--clear"]);
+    assert_eq!(author(), "Alice Toml");
+}
+
+#[test]
+fn working_hours_are_validated() {
+    let ws = Workspace::new();
+    TestRepo::init(&ws.repos(), "empty");
+    for (hours, error) in [
+        ("start = 24", "not an hour of the day (0-23)"),
+        ("end = 25", "not an hour of the day (1-24)"),
+        ("start = 18\nend = 8", "not after their start"),
+    ] {
+        ws.write_config(&format!("[working_hours]\n{hours}\n"));
+        let output = ws.run_unchecked(&[ws.repos().to_str().unwrap(), "--recursive"]);
+        assert!(!output.status.success(), "{hours}");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(error), "{stderr}");
+    }
+
+    ws.write_config("[working_hours]\nstart = 0\nend = 24\n");
+    ws.scan(&[]);
+}
+
+/// Signed by gpg with `--sender alice@example.com`.
+const GPG_SIGNATURE: &str = "\
+-----BEGIN PGP SIGNATURE-----
+
+iIgEABYIADAWIQQvozzawGo725jaGIFtZIH1v+OUzQUCatIQOxIcYWxpY2VAZXhh
+bXBsZS5jb20ACgkQbWSB9b/jlM1QoAD/WHXq6Gv1p6sPWmeLXSQ8B5LO/nAHKSre
+9j0A4J2HDQsA/i87HOhd2Kq4HbnIEnzIMw7/W140ITPPAsSSbXKBMIwA
+=ffll
+-----END PGP SIGNATURE-----";
+
+#[test]
+fn signer_is_stored_on_demand() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "signed");
+    repo.write("a.txt", "a\n");
+    let signed = repo.commit_signed("Add a", ALICE, GPG_SIGNATURE);
+    repo.write("a.txt", "b\n");
+    let unsigned = repo.commit("Change a", BOB);
+    let signer = |hash: Oid| {
+        ws.query::<Option<String>>(&format!(
+            "SELECT signer_email FROM logs WHERE commit_hash = '{hash}'"
+        ))
+    };
+
+    ws.scan(&[]);
+    assert_eq!(signer(signed), None);
+
+    ws.scan(&["--clear", "--store-signer"]);
+    assert_eq!(signer(signed).as_deref(), Some("alice@example.com"));
+    assert_eq!(signer(unsigned), None);
+}
+
+#[test]
+fn changed_files_are_stored_as_json_on_demand() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "json");
+    repo.write("a.txt", "a\n");
+    repo.commit("Add a", ALICE);
+    repo.write("a.txt", "b\nc\n").write("b.txt", "b\n");
+    repo.commit("Change a, add b", BOB);
+    let json = || {
+        ws.query_all::<Option<String>>(
+            "SELECT changed_files_json FROM logs ORDER BY commit_datetime",
+        )
+    };
+
+    ws.scan(&[]);
+    assert_eq!(json(), [None, None]);
+
+    ws.scan(&["--clear", "--files-as-json"]);
+    let files = json()
+        .into_iter()
+        .map(|json| serde_json::from_str::<serde_json::Value>(&json.unwrap()).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        files[0],
+        serde_json::json!([{"path": "a.txt", "insertions": 1, "deletions": 0, "is_new": true}])
+    );
+    assert_eq!(
+        files[1],
+        serde_json::json!([
+            {"path": "a.txt", "insertions": 2, "deletions": 1, "is_new": false},
+            {"path": "b.txt", "insertions": 1, "deletions": 0, "is_new": true},
+        ])
+    );
+    // the JSON functions of SQLite see the same files as the changed_files table
+    assert_eq!(
+        ws.query::<i64>("SELECT COUNT(*) FROM logs, json_each(logs.changed_files_json)"),
+        ws.query::<i64>("SELECT COUNT(*) FROM changed_files")
+    );
+}
+
+#[test]
+fn tuning_pragmas_are_applied_to_the_connections() {
+    let ws = Workspace::new();
+    let pragmas = |tuning: Tuning| {
+        let mut conn = rusqlite::Connection::open(ws.database()).unwrap();
+        tuning.init_connection(&mut conn).unwrap();
+        let pragma = |name: &str| -> i64 {
+            conn.query_row(&format!("PRAGMA {name}"), [], |row| row.get(0))
+                .unwrap()
+        };
+        (pragma("mmap_size"), pragma("temp_store"), pragma("foreign_keys"))
+    };
+
+    assert_eq!(pragmas(Tuning::default()), (0, 0, 1));
+    let tuning = Tuning {
+        mmap_size: Some(1 << 20),
+        temp_store: TempStore::Memory,
+    };
+    // temp_store 2 is MEMORY
+    assert_eq!(pragmas(tuning), (1 << 20, 2, 1));
+
+    let repo = TestRepo::init(&ws.repos(), "tuned");
+    repo.write("a.txt", "a\n");
+    repo.commit("Add a", ALICE);
+    ws.scan(&["--mmap-size", "1048576", "--temp-store", "memory"]);
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM logs"), 1);
+
+    let args = ["git-log-to-sqlite", ".", "--mmap-size", "9223372036854775808"];
+    assert!(Uninitialized::try_parse_from(args).is_err());
+}
+
+#[test]
+fn options_can_be_built_by_embedders() {
+    let args = [
+        "git-log-to-sqlite",
+        ".",
+        "--config-format",
+        "yaml",
+        "--output-format",
+        "jsonl",
+        "--progress-style",
+        "plain",
+        "--activity-periods",
+        "month",
+    ];
+    let mut options = Uninitialized::try_parse_from(args).unwrap();
+    assert_eq!(options.config_format, Some(ConfigFormat::Yaml));
+    assert_eq!(options.output_format, OutputFormat::Jsonl);
+    assert_eq!(options.progress_style, ProgressMode::Plain);
+    assert_eq!(options.activity_periods, [ActivityPeriod::Month]);
+
+    // the fields are public, so they can be set without parsing arguments
+    options.output_format = OutputFormat::Sqlite;
+    options.progress_style = ProgressMode::Simple;
+    GitRepositoryAnalyzer::from_options(options);
+}
+
+#[test]
+fn quiet_hides_the_progress_reporting() {
+    let options = Uninitialized::try_parse_from(["git-log-to-sqlite", ".", "--quiet"]).unwrap();
+    assert!(options.quiet);
+    assert!(Uninitialized::try_parse_from([
+        "git-log-to-sqlite",
+        ".",
+        "-q",
+        "--progress-style",
+        "plain"
+    ])
+    .is_err());
+    assert!(Uninitialized::try_parse_from(["git-log-to-sqlite", ".", "--progress-style", "quiet"])
+        .is_err());
+}
+
+/// Parses the command line with the date argument, returning the parsed bounds.
+fn parse_dates(args: &[&str]) -> Result<(Option<i64>, Option<i64>), clap::Error> {
+    let args = ["git-log-to-sqlite", "."].iter().chain(args);
+    Uninitialized::try_parse_from(args).map(|options| (options.since, options.until))
+}
+
+#[test]
+fn dates_are_validated() {
+    assert_eq!(parse_dates(&["--since", "2024-02-29"]).unwrap(), (Some(1_709_164_800), None));
+    assert_eq!(parse_dates(&["--since", "1969-12-31"]).unwrap(), (Some(-86_400), None));
+    assert_eq!(parse_dates(&["--since", "2000-02-29"]).unwrap(), (Some(951_782_400), None));
+    for invalid in
+        ["2023-02-29", "1900-02-29", "2024-04-31", "2024-13-01", "2024-00-10", "2024-01-00"]
+    {
+        assert!(parse_dates(&["--since", invalid]).is_err(), "{invalid}");
+    }
+    for invalid in ["2024/01/31", "2024-01", "20240131", "yesterday", ""] {
+        assert!(parse_dates(&["--since", invalid]).is_err(), "{invalid}");
+    }
+    assert!(parse_dates(&["--since", "2024-01-01", "--since-days", "30"]).is_err());
+}
+
+#[test]
+fn bus_factor_threshold_counts_at_least_one_author() {
+    let parse = |threshold| {
+        let args = ["git-log-to-sqlite", ".", "--bus-factor-threshold", threshold];
+        Uninitialized::try_parse_from(args).map(|options| options.bus_factor_threshold)
+    };
+    assert!(parse("0").is_err());
+    assert!(parse("0.0").is_err());
+    assert!(parse("-0.1").is_err());
+    assert!(parse("1.01").is_err());
+    assert_eq!(parse("0.001").unwrap(), 0.001);
+    assert_eq!(parse("1").unwrap(), 1.0);
+    let remap = ["git-log-to-sqlite", "remap-authors", "ids.toml", "--bus-factor-threshold", "0"];
+    assert!(Cli::try_parse_from(remap).is_err());
+
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "tiny");
+    repo.write("a.txt", "a\n");
+    repo.commit("Add a", ALICE);
+    ws.scan(&["--bus-factor-threshold", "0.001"]);
+    assert_eq!(ws.query::<i64>("SELECT bus_factor FROM repository_health"), 1);
+}
+
+#[test]
+fn uncommitted_changes_make_a_repository_dirty() {
+    let ws = Workspace::new();
+    for name in ["clean", "dirty"] {
+        let repo = TestRepo::init(&ws.repos(), name);
+        repo.write("a.txt", "a\n");
+        repo.commit("Add a", ALICE);
+    }
+    std::fs::write(ws.repos().join("dirty/untracked.txt"), "b\n").unwrap();
+    let dirty = "SELECT name || ' ' || IFNULL(is_dirty, 'NULL') FROM repositories ORDER BY name";
+
+    ws.scan(&[]);
+    assert_eq!(ws.query_all::<String>(dirty), ["clean 0", "dirty 1"]);
+
+    ws.scan(&["--clear", "--skip-dirty-check"]);
+    assert_eq!(ws.query_all::<String>(dirty), ["clean NULL", "dirty NULL"]);
+}
+
+#[test]
+fn short_hash_abbreviates_the_commit_hash() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "short");
+    repo.write("a.txt", "a\n");
+    let oid = repo.commit("Add a", ALICE);
+
+    ws.scan(&[]);
+
+    let short_hash = ws.query::<String>("SELECT short_hash FROM logs");
+    assert!(short_hash.len() >= 7 && short_hash.len() < 40, "{short_hash}");
+    assert!(oid.to_string().starts_with(&short_hash), "{short_hash}");
+}
+
+#[test]
+fn reflog_entries_of_the_ref_are_stored() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "reflog");
+    repo.write("a.txt", "a\n");
+    let first = repo.commit("Add a", ALICE);
+    repo.write("a.txt", "b\n");
+    let second = repo.commit("Change a", BOB);
+    // a reset, which the commit graph doesn't show
+    repo.repo
+        .reference("HEAD", first, true, "reset: moving to HEAD~1")
+        .unwrap();
+
+    ws.scan(&["--reflog", "HEAD"]);
+
+    assert_eq!(
+        ws.query_all::<String>(
+            "SELECT old_oid || ' ' || new_oid || ' ' || message FROM reflog \
+             WHERE ref = 'HEAD' ORDER BY timestamp, rowid"
+        ),
+        [
+            format!("{} {first} commit (initial): Add a", Oid::zero()),
+            format!("{first} {second} commit: Change a"),
+            format!("{second} {first} reset: moving to HEAD~1"),
+        ]
+    );
+}
+
+#[test]
+fn nocase_authors_group_emails_case_insensitively() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "nocase");
+    repo.write("a.txt", "a\n");
+    repo.commit("Add a", ("Jane", "Jane@Example.com", 1_700_000_000));
+    repo.write("a.txt", "b\n");
+    repo.commit("Change a", ("Jane", "jane@example.com", 1_700_100_000));
+    ws.write_config("normalize_emails = false");
+    let emails = "SELECT COUNT(DISTINCT author_email) FROM logs";
+
+    ws.scan(&[]);
+    assert_eq!(ws.query::<i64>(emails), 2);
+
+    std::fs::remove_file(ws.database()).unwrap();
+    ws.scan(&["--nocase-authors"]);
+    assert_eq!(ws.query::<i64>(emails), 1);
+    // the stored values are kept as is
+    assert_eq!(
+        ws.query_all::<String>("SELECT author_email FROM logs ORDER BY commit_datetime"),
+        ["Jane@Example.com", "jane@example.com"]
+    );
+}
+
+#[test]
+fn file_first_seen_records_the_earliest_addition() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "first-seen");
+    repo.write("a.txt", "a\n");
+    let added = repo.commit("Add a", ALICE);
+    repo.write("b.txt", "b\n");
+    repo.remove("a.txt");
+    let b = repo.commit("Replace a with b", BOB);
+    repo.write("a.txt", "again\n");
+    repo.commit("Add a again", (ALICE.0, ALICE.1, BOB.2 + 1));
+
+    ws.scan(&[]);
+
+    assert_eq!(
+        ws.query_all::<String>(
+            "SELECT message || ' ' || file_path || ' ' || is_new_file FROM changed_files \
+             JOIN logs USING (commit_hash) ORDER BY commit_datetime, file_path"
+        ),
+        [
+            "Add a a.txt 1",
+            "Replace a with b a.txt 0",
+            "Replace a with b b.txt 1",
+            "Add a again a.txt 1"
+        ]
+    );
+    assert_eq!(
+        ws.query_all::<String>("SELECT commit_hash FROM file_first_seen ORDER BY file_path"),
+        [added.to_string(), b.to_string()]
+    );
+}
+
+#[test]
+fn large_blobs_are_flagged_with_their_size() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "bloat");
+    repo.write("small.txt", "a\n");
+    repo.write("large.txt", "a\n".repeat(100));
+    repo.write("large.bin", [0u8; 300]);
+    repo.commit("Add files", ALICE);
+
+    ws.scan(&["--flag-large-blobs", "100"]);
+
+    assert_eq!(
+        ws.query_all::<String>(
+            "SELECT file_path || ' ' || large_blob || ' ' || IFNULL(blob_size, 'NULL') || ' ' || \
+             IFNULL(is_binary, 'NULL') FROM changed_files ORDER BY file_path"
+        ),
+        ["large.bin 1 300 1", "large.txt 1 200 0", "small.txt 0 NULL NULL"]
+    );
+}
+
+#[test]
+fn catalog_stores_only_the_repository_metadata() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "cataloged");
+    repo.write("a.txt", "a\n");
+    repo.commit("Add a", ALICE);
+    repo.write("a.txt", "b\n");
+    let head = repo.commit("Change a", BOB);
+    let branch = repo.repo.head().unwrap().shorthand().unwrap().to_string();
+
+    ws.scan(&["--catalog"]);
+
+    assert_eq!(
+        ws.query::<String>(
+            "SELECT default_branch || ' ' || head_commit || ' ' || commit_count FROM repositories"
+        ),
+        format!("{branch} {head} 2")
+    );
+    let path = std::path::PathBuf::from(ws.query::<String>("SELECT path FROM repositories"));
+    assert_eq!(path.canonicalize().unwrap(), repo.path().canonicalize().unwrap());
+    for table in ["logs", "changed_files"] {
+        assert_eq!(ws.query::<i64>(&format!("SELECT COUNT(*) FROM {table}")), 0, "{table}");
+    }
+}
+
+#[test]
+fn size_bytes_measures_the_git_directory_or_the_worktree() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "sized");
+    repo.write("a.txt", "a".repeat(1_000));
+    repo.commit("Add a", ALICE);
+    std::fs::write(repo.path().join("untracked.txt"), "b".repeat(500)).unwrap();
+    let size = "SELECT size_bytes FROM repositories";
+
+    ws.scan(&[]);
+    assert_eq!(ws.query::<Option<i64>>(size), None);
+
+    ws.scan(&["--clear", "--measure-size"]);
+    let git_dir = ws.query::<i64>(size);
+    assert!(git_dir > 0);
+
+    ws.scan(&["--clear", "--measure-size", "worktree"]);
+    // the worktree adds the checked out and untracked files to the git directory
+    assert_eq!(ws.query::<i64>(size) - git_dir, 1_500);
+}
+
+#[test]
+fn metadata_records_the_versions_and_arguments() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "stamped");
+    repo.write("a.txt", "a\n");
+    repo.commit("Add a", ALICE);
+    let value =
+        |key: &str| ws.query::<String>(&format!("SELECT value FROM metadata WHERE key = '{key}'"));
+
+    ws.scan(&["--fts"]);
+
+    assert_eq!(value("tool_version"), env!("CARGO_PKG_VERSION"));
+    let (major, minor, patch) = git2::Version::get().libgit2_version();
+    assert_eq!(value("libgit2_version"), format!("{major}.{minor}.{patch}"));
+    let args = serde_json::from_str::<Vec<String>>(&value("args")).unwrap();
+    assert_eq!(args[1..3], [ws.repos().to_str().unwrap(), "--recursive"]);
+    assert!(args.contains(&"--fts".to_string()), "{args:?}");
+    let created_at = value("created_at");
+
+    ws.scan(&[]);
+    // the creation time is kept, while the arguments are the ones of the last run
+    assert_eq!(value("created_at"), created_at);
+    assert!(!value("args").contains("--fts"));
+}
+
+#[test]
+fn incremental_scan_analyzes_only_new_commits() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "incremental");
+    repo.write("a.rs", "a\n");
+    repo.commit("First", ALICE);
+    ws.scan(&[]);
+    repo.write("a.rs", "a\nb\n");
+    repo.commit("Second", ALICE);
+    repo.write("b.rs", "b\n");
+    let head = repo.commit("Third", BOB);
+
+    // the stored commit is not analyzed again, so it doesn't conflict
+    ws.scan(&["--on-conflict", "fail"]);
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM logs"), 3);
+    assert_eq!(ws.query::<String>("SELECT analyzed_commit FROM repositories"), head.to_string());
+    assert_eq!(
+        ws.query_all::<String>("SELECT message FROM logs WHERE is_first_commit_for_author"),
+        ["First", "Third"]
+    );
+    assert_eq!(
+        ws.query::<i64>("SELECT commit_count FROM extension_stats WHERE extension = 'rs'"),
+        3
+    );
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM file_latest"), 2);
+
+    let output = ws.run_unchecked(&[
+        ws.repos().to_str().unwrap(),
+        "--recursive",
+        "--full",
+        "--on-conflict",
+        "fail",
+    ]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("UNIQUE constraint failed"));
+    ws.scan(&["--full"]);
+    assert_eq!(
+        ws.query::<i64>("SELECT commit_count FROM extension_stats WHERE extension = 'rs'"),
+        3
+    );
+}
+
+#[test]
+fn extension_stats_are_summed_across_incremental_scans() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "incremental");
+    repo.write("a.rs", "a\nb\n");
+    repo.write("a.md", "a\n");
+    repo.commit("First", ALICE);
+    ws.scan(&[]);
+    repo.write("a.rs", "a\nc\nd\n");
+    repo.commit("Second", BOB);
+    repo.write("b.md", "b\n");
+    repo.commit("Third", BOB);
+
+    ws.scan(&[]);
+
+    assert_eq!(
+        ws.query_all::<String>(
+            "SELECT extension || ' ' || commit_count || ' ' || insertions || ' ' || deletions \
+             FROM extension_stats ORDER BY extension"
+        ),
+        ["md 2 2 0", "rs 2 4 1"]
+    );
+}
+
+#[test]
+fn fts_index_follows_inserted_and_replaced_logs() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "fts");
+    repo.write("a.txt", "a\n");
+    let widget = repo.commit("Fix the widget renderer", ALICE);
+    repo.write("a.txt", "b\n");
+    repo.commit("Update the gadget", BOB);
+    let matching = "SELECT commit_hash FROM logs_fts WHERE logs_fts MATCH 'widget'";
+
+    ws.scan(&["--fts"]);
+    assert_eq!(ws.query_all::<String>(matching), [widget.to_string()]);
+
+    // each log is deleted and inserted again, with a new rowid
+    ws.scan(&["--fts", "--full", "--on-conflict", "replace"]);
+    assert_eq!(ws.query_all::<String>(matching), [widget.to_string()]);
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM logs_fts WHERE logs_fts MATCH 'the'"), 2);
+    ws.connection()
+        .execute("INSERT INTO logs_fts (logs_fts) VALUES ('integrity-check')", [])
+        .unwrap();
+}
+
+#[test]
+fn replace_keeps_only_the_new_changed_files() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "replace");
+    repo.write("main.rs", "fn main() {}\n").write("vendor/lib.js", "1\n");
+    repo.commit("Vendor", ALICE);
+    ws.scan(&[]);
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM changed_files"), 2);
+
+    // the same commit now has fewer changed files, and the changed options replace it
+    let exclude = ws.dir.path().join("exclude.txt");
+    std::fs::write(&exclude, "vendor/**\n").unwrap();
+    let exclude = exclude.to_str().unwrap();
+    ws.scan(&["--exclude-paths-from", exclude]);
+    assert_eq!(ws.query_all::<String>("SELECT file_path FROM changed_files"), ["main.rs"]);
+
+    ws.scan(&["--exclude-paths-from", exclude, "--on-conflict", "replace"]);
+    assert_eq!(ws.query_all::<String>("SELECT file_path FROM changed_files"), ["main.rs"]);
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM logs"), 1);
+}
+
+#[test]
+fn force_pushed_commits_are_recorded_as_rewritten() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "rewritten");
+    repo.write("a.txt", "a\n");
+    let first = repo.commit("First", ALICE);
+    repo.write("b.txt", "b\n");
+    let second = repo.commit("Second", BOB);
+    ws.scan(&["--detect-rewrites"]);
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM rewritten_commits"), 0);
+
+    // amend the second commit, as before a force push
+    let branch = repo.repo.head().unwrap().name().unwrap().to_string();
+    repo.write("b.txt", "amended\n");
+    let amended = repo.commit_with_parents("Second", BOB, vec![first], None);
+    repo.repo.reference(&branch, amended, true, "amend").unwrap();
+    let output = ws.scan(&["--detect-rewrites"]);
+
+    assert_eq!(
+        ws.query_all::<String>("SELECT commit_hash FROM rewritten_commits"),
+        [second.to_string()]
+    );
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM logs"), 3);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("rewritten: 1 commits"));
+
+    // reachable again
+    repo.repo.reference(&branch, second, true, "revert").unwrap();
+    ws.scan(&["--detect-rewrites"]);
+    assert_eq!(
+        ws.query_all::<String>("SELECT commit_hash FROM rewritten_commits"),
+        [amended.to_string()]
+    );
+}
+
+#[test]
+fn diff_reports_changes_between_databases() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "tracked");
+    repo.write("a.txt", "a\n");
+    repo.commit("First", ALICE);
+    ws.scan(&[]);
+    let old = ws.dir.path().join("old.db");
+    std::fs::copy(ws.database(), &old).unwrap();
+    repo.write("a.txt", "a\nb\nc\n");
+    repo.commit("Second", BOB);
+    ws.scan(&[]);
+
+    let diffs = diff(&DiffArgs {
+        old: old.try_into().unwrap(),
+        new: ws.database().try_into().unwrap(),
+        format: DiffFormat::Text,
+    })
+    .unwrap();
+
+    assert_eq!(diffs.len(), 1);
+    assert_eq!(diffs[0].presence, Presence::Both);
+    assert_eq!((diffs[0].added_commits, diffs[0].removed_commits), (1, 0));
+    assert_eq!(diffs[0].new_authors, ["Bob"]);
+    assert_eq!(diffs[0].churn_delta, 2);
+}
+
+#[test]
+fn parallel_discovery_finds_nested_repositories() {
+    let ws = Workspace::new();
+    for (parent, name) in [("", "top"), ("team", "nested"), ("team/deep", "deeper")] {
+        let repo = TestRepo::init(&ws.repos().join(parent), name);
+        repo.write("a.txt", "a\n");
+        repo.commit("Add a", ALICE);
+    }
+
+    ws.scan(&["--max-depth", "0", "--discovery-threads", "4"]);
+    assert_eq!(
+        ws.query_all::<String>("SELECT name FROM repositories ORDER BY name"),
+        ["deeper", "nested", "top"]
+    );
+
+    ws.scan(&["--clear", "--max-depth", "2", "--discovery-threads", "4"]);
+    assert_eq!(
+        ws.query_all::<String>("SELECT name FROM repositories ORDER BY name"),
+        ["nested", "top"]
+    );
+}
+
+#[test]
+fn bare_repositories_are_scanned_without_their_internals() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(ws.dir.path(), "work");
+    repo.write("a.txt", "a\n");
+    repo.commit("Add a", ALICE);
+    let bare = ws.repos().join("mirror.git");
+    let git = |args: &[&str]| {
+        let status = Command::new("git").args(args).status().unwrap();
+        assert!(status.success(), "git {args:?} failed");
+    };
+    git(&["init", "--bare", "--quiet", bare.to_str().unwrap()]);
+    git(&["-C", bare.to_str().unwrap(), "symbolic-ref", "HEAD", "refs/heads/main"]);
+    git(&[
+        "-C",
+        repo.path().to_str().unwrap(),
+        "push",
+        "--quiet",
+        bare.to_str().unwrap(),
+        "HEAD:main",
+    ]);
+
+    for threads in ["1", "4"] {
+        let output = ws.scan(&["--clear", "--max-depth", "0", "--discovery-threads", threads]);
+        assert!(!String::from_utf8_lossy(&output.stdout).contains("not stored"));
+        assert_eq!(ws.query_all::<String>("SELECT name FROM repositories"), ["mirror.git"]);
+        assert_eq!(ws.query_all::<String>("SELECT message FROM logs"), ["Add a"]);
+    }
+}
+
+#[test]
+fn bundle_is_analyzed_and_kept_by_prune_missing() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "widget");
+    repo.write("a.txt", "a\n");
+    repo.commit("Add a", ALICE);
+    repo.write("b.txt", "b\n");
+    repo.commit("Add b", BOB);
+    let bundle = ws.dir.path().join("widget.bundle");
+    let status = Command::new("git")
+        .args(["-C", repo.path().to_str().unwrap(), "bundle", "create", "--quiet"])
+        .arg(&bundle)
+        .arg("--all")
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    ws.run(&[bundle.to_str().unwrap()]);
+    assert_eq!(ws.query_all::<String>("SELECT name FROM repositories"), ["widget"]);
+    assert_eq!(
+        ws.query::<String>("SELECT path FROM repositories"),
+        bundle.canonicalize().unwrap().to_str().unwrap()
+    );
+    assert_eq!(
+        ws.query_all::<String>("SELECT message FROM logs ORDER BY commit_datetime"),
+        ["Add a", "Add b"]
+    );
+
+    // the temporary clone is gone, but not the bundle
+    let output = ws.run(&[bundle.to_str().unwrap(), "--prune-missing"]);
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("pruned"));
+    assert_eq!(ws.query_all::<String>("SELECT name FROM repositories"), ["widget"]);
+}
+
+#[test]
+fn analyze_all_returns_the_repositories_in_memory() {
+    let ws = Workspace::new();
+    TestRepo::init(&ws.repos(), "empty");
+    let repo = TestRepo::init(&ws.repos(), "full");
+    repo.write("a.txt", "a\n");
+    repo.commit("Add a", ALICE);
+    repo.write("b.txt", "b\n");
+    repo.commit("Add b", BOB);
+    std::fs::create_dir(ws.repos().join("not-a-repository")).unwrap();
+
+    let analyzed = analyze_all(&ws.repos(), &ScanOptions::default());
+
+    let mut names = analyzed
+        .iter()
+        .map(|repository| repository.path.file_name().unwrap().to_str().unwrap())
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, ["empty", "full"]);
+    for repository in &analyzed {
+        match &repository.result {
+            Ok(repo) => {
+                assert_eq!(repo.name(), "full");
+                assert_eq!(repo.logs().len(), 2);
+            }
+            Err(e) => assert_eq!(e.to_string(), "the repository has no commits"),
+        }
+    }
+    assert!(!ws.database().exists());
+}
+
+#[test]
+fn logs_can_be_enriched_before_they_are_collected() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "enriched");
+    repo.write("a.txt", "a\n");
+    repo.commit("Add a", ("jdoe", "jdoe@corp.example", 1_700_000_000));
+    repo.write("b.txt", "b\n");
+    repo.commit("Add b", BOB);
+
+    let analyzed = GitRepository::try_new(repo.path().to_path_buf())
+        .unwrap()
+        .open()
+        .unwrap()
+        .analyze_with(&AnalyzeOptions::default(), |log| {
+            // e.g. a lookup in a directory of the company
+            if log.author_email == "jdoe@corp.example" {
+                log.author_name = "Jane Doe".to_string();
+            }
+        })
+        .unwrap();
+
+    let mut authors = analyzed
+        .logs()
+        .iter()
+        .map(|log| log.author_name.as_str())
+        .collect::<Vec<_>>();
+    authors.sort();
+    assert_eq!(authors, ["Bob", "Jane Doe"]);
+}
+
+#[test]
+fn analyzer_can_be_built_from_options() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "embedded");
+    repo.write("a.txt", "a\n");
+    repo.commit("Add a", ALICE);
+
+    let options = Uninitialized::try_parse_from([
+        "embedder".as_ref(),
+        ws.repos().as_os_str(),
+        "--recursive".as_ref(),
+        "--database".as_ref(),
+        ws.database().as_os_str(),
+        "--config".as_ref(),
+        ws.config().as_os_str(),
+    ])
+    .unwrap();
+    let report = GitRepositoryAnalyzer::from_options(options)
+        .try_prepare()
+        .unwrap()
+        .analyze()
+        .unwrap();
+    assert_eq!(report.analyzed_repositories, ["embedded"]);
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM logs"), 1);
+}
+
+#[test]
+fn concurrent_repositories_can_be_capped() {
+    let ws = Workspace::new();
+    for name in ["one", "two", "three"] {
+        let repo = TestRepo::init(&ws.repos(), name);
+        repo.write("a.txt", name);
+        repo.commit("Add a", ALICE);
+    }
+
+    ws.scan(&["--num-threads", "4", "--max-concurrent-repos", "1"]);
+
+    assert_eq!(
+        ws.query_all::<String>("SELECT name FROM repositories ORDER BY name"),
+        ["one", "three", "two"]
+    );
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM logs"), 3);
+}
+
+#[test]
+fn branches_are_stored_with_their_merge_base() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "forked");
+    repo.write("a.txt", "a\n");
+    let fork = repo.commit("Add a", ALICE);
+    repo.write("b.txt", "b\n");
+    let mainline = repo.commit("Add b", ALICE);
+    repo.write("c.txt", "c\n");
+    let feature = repo.commit_with_parents("Add c", BOB, vec![fork], Some("refs/heads/feature"));
+    let orphan = repo.commit_with_parents("Start over", BOB, vec![], Some("refs/heads/orphan"));
+    let default = repo.repo.head().unwrap().shorthand().unwrap().to_string();
+
+    ws.scan(&["--store-branches"]);
+
+    let branches = ws.query_all::<String>(
+        "SELECT name || ' ' || tip_commit || ' ' || COALESCE(merge_base, '-') FROM branches ORDER BY name",
+    );
+    let mut expected = vec![
+        format!("feature {feature} {fork}"),
+        format!("{default} {mainline} {mainline}"),
+        format!("orphan {orphan} -"),
+    ];
+    expected.sort();
+    assert_eq!(branches, expected);
+}
+
+#[test]
+fn overall_progress_counts_every_repository_outcome() {
+    let ws = Workspace::new();
+    TestRepo::init(&ws.repos(), "empty");
+    let small = TestRepo::init(&ws.repos(), "small");
+    small.write("a.txt", "a\n");
+    small.commit("Initial commit", ALICE);
+    let large = TestRepo::init(&ws.repos(), "large");
+    large.write("a.txt", "a\n");
+    large.commit("Initial commit", ALICE);
+    large.write("a.txt", "b\n");
+    large.commit("Second commit", BOB);
+    std::fs::create_dir(ws.repos().join("not-a-repository")).unwrap();
+
+    let output = ws.scan(&["--min-commits", "2", "--progress-style", "plain"]);
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let mut done = stderr.lines().filter(|line| line.ends_with("repositories done"));
+    assert_eq!(done.next_back(), Some("3/3 repositories done"), "{stderr}");
+}
+
+#[test]
+fn progress_events_are_sent_to_the_socket() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "watched");
+    repo.write("a.txt", "a\n");
+    repo.commit("Add a", ALICE);
+    repo.write("b.txt", "b\n");
+    repo.commit("Add b", BOB);
+    TestRepo::init(&ws.repos(), "empty");
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let events = std::thread::spawn(move || {
+        let mut events = String::new();
+        listener.accept().unwrap().0.read_to_string(&mut events).unwrap();
+        events
+    });
+
+    ws.scan(&["--progress-socket", &addr]);
+
+    let events = events
+        .join()
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .collect::<Vec<_>>();
+    let stages = |repository: &str| {
+        events
+            .iter()
+            .filter(|event| event["repository"] == repository)
+            .map(|event| (event["stage"].as_str().unwrap(), event["commits"].as_u64()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        stages("watched"),
+        [("opening", None), ("analyzing", None), ("storing", Some(0)), ("done", Some(2))]
+    );
+    assert_eq!(stages("empty"), [("opening", None), ("analyzing", None), ("failed", None)]);
+}
+
+#[test]
+fn sample_selects_a_deterministic_fraction() {
+    let ws = Workspace::new();
+    for i in 0..10 {
+        let repo = TestRepo::init(&ws.repos(), &format!("repo-{i}"));
+        repo.write("a.txt", "a\n");
+        repo.commit("Add a", ALICE);
+    }
+    let sampled = |args: &[&str]| {
+        ws.scan(&[&["--clear", "--sample"], args].concat());
+        ws.query_all::<String>("SELECT name FROM repositories ORDER BY name")
+    };
+
+    assert_eq!(sampled(&["0"]).len(), 0);
+    assert_eq!(sampled(&["1"]).len(), 10);
+    let half = sampled(&["0.5"]);
+    assert!((1..10).contains(&half.len()), "{half:?}");
+    assert_eq!(sampled(&["0.5"]), half);
+    assert_eq!(sampled(&["0.5", "--sample-seed", "0"]), half);
+}
+
+#[test]
+fn repositories_are_split_across_shards() {
+    let ws = Workspace::new();
+    for i in 0..6 {
+        let repo = TestRepo::init(&ws.repos(), &format!("repo-{i}"));
+        repo.write("a.txt", "a\n");
+        repo.commit("Add a", ALICE);
+    }
+
+    ws.scan(&["--shards", "3"]);
+
+    assert!(!ws.database().exists());
+    let mut names = Vec::new();
+    for i in 0..3 {
+        let shard = ws.dir.path().join(format!("repositories.{i}.db"));
+        let conn = rusqlite::Connection::open(&shard).unwrap();
+        let mut stmt = conn.prepare("SELECT name FROM repositories").unwrap();
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0)).unwrap();
+        names.extend(rows.map(|row| row.unwrap()));
+    }
+    names.sort();
+    let expected: Vec<_> = (0..6).map(|i| format!("repo-{i}")).collect();
+    assert_eq!(names, expected);
+}
+
+#[test]
+fn existing_database_is_handled_as_requested() {
+    let ws = Workspace::new();
+    let first = TestRepo::init(&ws.repos(), "first");
+    first.write("a.txt", "a\n");
+    first.commit("Add a", ALICE);
+    let second = TestRepo::init(&ws.repos(), "second");
+    second.write("b.txt", "b\n");
+    second.commit("Add b", BOB);
+    let first = first.path().to_str().unwrap();
+    let second = second.path().to_str().unwrap();
+    let names = || ws.query_all::<String>("SELECT name FROM repositories ORDER BY name");
+
+    ws.run(&[first]);
+    ws.run(&[second, "--on-exists", "append"]);
+    assert_eq!(names(), ["first", "second"]);
+
+    let output = ws.run_unchecked(&[first, "--on-exists", "fail"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("already exists"));
+    assert_eq!(names(), ["first", "second"]);
+
+    ws.run(&[first, "--on-exists", "clear"]);
+    assert_eq!(names(), ["first"]);
+
+    ws.run(&[second, "--on-exists", "backup"]);
+    assert_eq!(names(), ["second"]);
+    let backups: Vec<_> = std::fs::read_dir(ws.dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.to_string_lossy().ends_with(".bak"))
+        .collect();
+    assert_eq!(backups.len(), 1);
+    let backup = rusqlite::Connection::open(&backups[0]).unwrap();
+    let name: String = backup
+        .query_row("SELECT name FROM repositories", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(name, "first");
+}
+
+#[test]
+fn no_repository_is_started_past_the_deadline() {
+    let ws = Workspace::new();
+    for name in ["one", "two"] {
+        let repo = TestRepo::init(&ws.repos(), name);
+        repo.write("a.txt", name);
+        repo.commit(name, ALICE);
+    }
+
+    let output = ws.scan(&["--deadline", "0"]);
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM repositories"), 0);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("2 directories were not started"), "{stdout}");
+
+    ws.scan(&["--deadline", "3600"]);
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM repositories"), 2);
+}
+
+#[test]
+fn empty_repositories_are_included_on_demand() {
+    let ws = Workspace::new();
+    TestRepo::init(&ws.repos(), "empty");
+    let repo = TestRepo::init(&ws.repos(), "full");
+    repo.write("a.txt", "a\n");
+    repo.commit("Add a", ALICE);
+
+    let output = ws.scan(&[]);
+    assert_eq!(ws.query_all::<String>("SELECT name FROM repositories"), ["full"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("the repository has no commits"));
+
+    let output = ws.scan(&["--clear", "--include-empty-repos"]);
+    assert_eq!(
+        ws.query_all::<String>("SELECT name FROM repositories ORDER BY name"),
+        ["empty", "full"]
+    );
+    assert_eq!(
+        ws.query::<Option<String>>("SELECT head_commit FROM repositories WHERE name = 'empty'"),
+        None
+    );
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("not stored"));
+}
+
+#[test]
+fn missing_repositories_are_pruned() {
+    let ws = Workspace::new();
+    for name in ["kept", "deleted"] {
+        let repo = TestRepo::init(&ws.repos(), name);
+        repo.write("a.txt", name);
+        repo.commit(name, ALICE);
+    }
+    ws.scan(&[]);
+    std::fs::remove_dir_all(ws.repos().join("deleted")).unwrap();
+
+    let output = ws.scan(&["--prune-missing"]);
+
+    assert_eq!(ws.query_all::<String>("SELECT name FROM repositories"), ["kept"]);
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM logs"), 1);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("1 repositories were pruned"));
+}
+
+#[test]
+fn repository_name_comes_from_the_chosen_source() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "checkout");
+    repo.repo.remote("origin", "git@github.com:acme/widget.git").unwrap();
+    repo.write("a.txt", "a\n");
+    let root = repo.commit("Initial commit", ALICE);
+    let local = TestRepo::init(&ws.repos(), "local");
+    local.write("a.txt", "local\n");
+    let local_root = local.commit("Initial commit", BOB);
+
+    let names = |source: &str| {
+        ws.scan(&["--clear", "--name-from", source]);
+        ws.query_all::<String>("SELECT name FROM repositories ORDER BY name")
+    };
+
+    assert_eq!(names("dir"), ["checkout", "local"]);
+    // without a remote, the directory name is kept
+    assert_eq!(names("remote"), ["acme/widget", "local"]);
+    assert_eq!(
+        ws.query::<String>("SELECT url FROM repositories WHERE name = 'acme/widget'"),
+        "https://github.com/acme/widget.git"
+    );
+    let mut roots = [root, local_root].map(|oid| oid.to_string()[..12].to_string());
+    roots.sort();
+    assert_eq!(names("root-commit"), roots);
+}
+
+#[test]
+fn import_skips_malformed_rows_and_stores_the_parents() {
+    let ws = Workspace::new();
+    let (root, child) = ("a".repeat(40), "b".repeat(40));
+    let dump = ws.dir.path().join("archive.tsv");
+    let rows = [
+        "HASH\tParent\tauthor\temail\tepoch\tsubject\tinsertions\tdeletions\tfiles".to_string(),
+        format!("{root}\t\tAlice\talice@example.com\t1700000000\tInitial\t2\t0\ta.txt;b.rs"),
+        format!("{child}\t{root}\tBob\tbob@example.com\t1700100000\tChange\t1\t1\ta.txt"),
+        // not a hash, not an epoch, and a missing column
+        "nothex\t\tBob\tbob@example.com\t1700100000\tBad\t1\t1\ta.txt".to_string(),
+        format!("{}\t\tBob\tbob@example.com\tyesterday\tBad\t1\t1\ta.txt", "c".repeat(40)),
+        format!("{}\t\tBob\tbob@example.com\t1700100000\tBad\t1\t1", "d".repeat(40)),
+    ];
+    std::fs::write(&dump, rows.join("\n")).unwrap();
+    let args = |file: &std::path::Path| ImportArgs {
+        file: file.to_path_buf().try_into().unwrap(),
+        name: None,
+        url: "(no remote url)".into(),
+        database: ws.database().try_into().unwrap(),
+    };
+
+    let summary = import(&args(&dump)).unwrap();
+
+    assert_eq!((summary.name.as_str(), summary.imported, summary.malformed), ("archive", 2, 3));
+    assert_eq!(
+        ws.query_all::<String>("SELECT commit_hash || ' ' || parent_hash FROM commit_parents"),
+        [format!("{child} {root}")]
+    );
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM changed_files"), 3);
+
+    let wrong = ws.dir.path().join("wrong.csv");
+    std::fs::write(&wrong, "hash,author,email\n").unwrap();
+    let e = import(&args(&wrong)).err().unwrap();
+    assert!(e.to_string().starts_with("unexpected header: hash,author,email"), "{e}");
+}
+
+#[test]
+fn extract_copies_a_single_repository() {
+    let ws = Workspace::new();
+    for name in ["kept", "other"] {
+        let repo = TestRepo::init(&ws.repos(), name);
+        repo.write("a.txt", name);
+        repo.commit(name, ALICE);
+        repo.write("b.txt", name);
+        repo.commit(name, BOB);
+    }
+    ws.scan(&[]);
+    let output = ws.dir.path().join("kept.db");
+
+    let copied = extract(&ExtractArgs {
+        repository: "kept".into(),
+        output: output.clone().try_into().unwrap(),
+        database: ws.database().try_into().unwrap(),
+    })
+    .unwrap();
+
+    assert_eq!((copied["repositories"], copied["logs"], copied["changed_files"]), (1, 2, 2));
+    let conn = git_log_to_sqlite::database::open(&output).unwrap();
+    let names = conn
+        .prepare("SELECT DISTINCT repositories.name FROM logs JOIN repositories ON repositories.id = logs.repository_id")
+        .unwrap()
+        .query_map([], |row| row.get::<_, String>(0))
+        .unwrap()
+        .map(|name| name.unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["kept"]);
+    let violations = conn.prepare("PRAGMA foreign_key_check").unwrap().exists([]).unwrap();
+    assert!(!violations);
+}
+
+#[test]
+fn rerun_inserts_only_new_commits() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "rerun");
+    repo.write("a.txt", "a\n");
+    repo.commit("First", ALICE);
+    ws.scan(&[]);
+    repo.write("b.txt", "b\n");
+    repo.commit("Second", BOB);
+
+    ws.scan(&[]);
+
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM logs"), 2);
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM changed_files"), 2);
+
+    ws.scan(&["--on-conflict", "replace"]);
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM changed_files"), 2);
+}
+
+#[test]
+fn write_order_sets_the_insertion_order() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "ordered");
+    for (i, message) in ["First", "Second", "Third"].into_iter().enumerate() {
+        repo.write("a.txt", message);
+        repo.commit(message, (ALICE.0, ALICE.1, ALICE.2 + i as i64));
+    }
+    let inserted = "SELECT message FROM logs ORDER BY rowid";
+
+    ws.scan(&["--clear"]);
+    assert_eq!(ws.query_all::<String>(inserted), ["Third", "Second", "First"]);
+
+    ws.scan(&["--clear", "--write-order", "oldest-first"]);
+    assert_eq!(ws.query_all::<String>(inserted), ["First", "Second", "Third"]);
+}
+
+#[test]
+fn multiple_repositories_are_stored() {
+    let ws = Workspace::new();
+    for name in ["one", "two"] {
+        let repo = TestRepo::init(&ws.repos(), name);
+        repo.write("a.txt", name);
+        repo.commit(name, ALICE);
+    }
+    std::fs::create_dir(ws.repos().join("not-a-repository")).unwrap();
+
+    let output = ws.scan(&[]);
+
+    let names = ws.query_all::<String>("SELECT name FROM repositories ORDER BY name");
+    assert_eq!(names, vec!["one", "two"]);
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM logs"), 2);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let reason = stdout.lines().find(|line| line.contains("not-a-repository")).unwrap();
+    assert!(reason.contains(": failed to analyze"), "{reason}");
+    assert!(reason.contains("could not find repository"), "{reason}");
+}
+
+#[test]
+fn logs_are_written_as_json_lines() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "json");
+    repo.write("a.txt", "a\n");
+    let first = repo.commit("Initial commit", ALICE);
+    repo.write("a.txt", "b\n");
+    let second = repo.commit("Second commit", BOB);
+
+    let output = ws.scan(&["--output-format", "jsonl"]);
+
+    let logs = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(logs.len(), 2);
+    assert_eq!(logs[0]["repository_name"], "json");
+    assert_eq!(logs[0]["commit_hash"], second.to_string());
+    assert_eq!(logs[0]["changed_files"][0]["file_path"], "a.txt");
+    assert_eq!(logs[0]["changed_files"][0]["status"], "modified");
+    assert_eq!(logs[1]["commit_hash"], first.to_string());
+    assert!(!ws.database().exists());
+
+    let output = ws.scan(&["--output-format", "json"]);
+    let logs = serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap();
+    assert_eq!(logs.as_array().unwrap().len(), 2);
+
+    // the report goes to stdout, as the logs go to the file
+    let file = ws.dir.path().join("logs.jsonl");
+    let output = ws.scan(&["--output-format", "jsonl", "--output", file.to_str().unwrap()]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("json"));
+    assert_eq!(std::fs::read_to_string(&file).unwrap().lines().count(), 2);
+    let output = ws.run_unchecked(&[".", "--output", file.to_str().unwrap()]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn json_output_is_closed_when_failing_fast() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "corrupt");
+    repo.write("a.txt", "a\n");
+    let first = repo.commit("Add a", ALICE);
+    let tree = repo.repo.find_commit(first).unwrap().tree_id().to_string();
+    let (dir, file) = tree.split_at(2);
+    std::fs::remove_file(repo.path().join(".git/objects").join(dir).join(file)).unwrap();
+
+    let repos = ws.repos();
+    let args = [repos.to_str().unwrap(), "--recursive", "--output-format", "json", "--fail-fast"];
+    let output = ws.run_unchecked(&args);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("failed to analyze"));
+    let logs = serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap();
+    assert_eq!(logs, serde_json::json!([]));
+}
+
+#[test]
+fn date_times_are_validated() {
+    for (since, expected) in [
+        ("2024-01-31T12:00:00Z", 1_706_702_400),
+        ("2024-01-31t12:00:00z", 1_706_702_400),
+        ("2024-01-31 12:00:00+00:00", 1_706_702_400),
+        ("2024-01-31T12:00:00.5+09:00", 1_706_670_000),
+        ("2024-01-31T12:00:00-05:00", 1_706_720_400),
+        ("2024-01-31T23:30:00-01:00", 1_706_747_400),
+        ("2016-12-31T23:59:60Z", 1_483_228_800),
+    ] {
+        assert_eq!(parse_dates(&["--since", since]).unwrap(), (Some(expected), None), "{since}");
+    }
+    assert_eq!(parse_dates(&["--until", "2024-01-31"]).unwrap(), (None, Some(1_706_745_599)));
+    assert_eq!(
+        parse_dates(&["--until", "2024-01-31T00:00:00Z"]).unwrap(),
+        (None, Some(1_706_659_200))
+    );
+    for invalid in [
+        "2024-01-31T12:00:00",
+        "2024-01-31T24:00:00Z",
+        "2024-01-31T12:60:00Z",
+        "2024-01-31T12:00:61Z",
+        "2024-01-31T12:00Z",
+        "2024-01-31T12:00:00+24:00",
+        "2024-01-31T12:00:00+09:60",
+        "2024-01-31T12:00:00+0900",
+        "2024-01-31T12:00:00 UTC",
+        "2024-02-30T12:00:00Z",
+    ] {
+        assert!(parse_dates(&["--since", invalid]).is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn repositories_with_too_few_commits_are_skipped() {
+    let ws = Workspace::new();
+    let small = TestRepo::init(&ws.repos(), "small");
+    small.write("a.txt", "a\n");
+    small.commit("Initial commit", ALICE);
+    let large = TestRepo::init(&ws.repos(), "large");
+    large.write("a.txt", "a\n");
+    large.commit("Initial commit", ALICE);
+    large.write("a.txt", "b\n");
+    large.commit("Second commit", BOB);
+
+    let output = ws.scan(&["--min-commits", "2"]);
+
+    assert_eq!(ws.query_all::<String>("SELECT name FROM repositories"), ["large"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("fewer commits than --min-commits"));
+}
+
+#[test]
+fn first_commit_of_each_author_is_flagged() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "newcomers");
+    repo.write("a.txt", "a\n");
+    repo.commit("Alice first", ALICE);
+    repo.write("a.txt", "b\n");
+    repo.commit("Bob first", BOB);
+    repo.write("a.txt", "c\n");
+    repo.commit("Alice again", ALICE);
+
+    ws.scan(&[]);
+
+    assert_eq!(
+        ws.query_all::<String>(
+            "SELECT message FROM logs WHERE is_first_commit_for_author ORDER BY message"
+        ),
+        ["Alice first", "Bob first"]
+    );
+}
+
+#[test]
+fn database_of_an_earlier_version_is_upgraded() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "upgraded");
+    repo.write("a.txt", "a\n");
+    repo.commit("Add a", ALICE);
+
+    // the first schema of the tool, without a version
+    ws.connection()
+        .execute_batch(
+            r#"
+            CREATE TABLE repositories (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                url TEXT
+            );
+            CREATE TABLE logs (
+                commit_hash TEXT PRIMARY KEY,
+                author_name TEXT NOT NULL,
+                author_email TEXT NOT NULL,
+                message TEXT,
+                commit_datetime DATETIME NOT NULL,
+                insertions INTEGER,
+                deletions INTEGER,
+                repository_id INTEGER,
+                parent_hash TEXT,
+                FOREIGN KEY (repository_id) REFERENCES repositories (id)
+            );
+            CREATE TABLE changed_files (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                commit_hash TEXT NOT NULL,
+                file_path TEXT,
+                FOREIGN KEY (commit_hash) REFERENCES logs (commit_hash)
+            );
+            INSERT INTO repositories (name, url) VALUES ('old', 'https://example.com/old');
+            "#,
+        )
+        .unwrap();
+
+    ws.scan(&[]);
+    assert_eq!(
+        ws.query_all::<String>("SELECT name FROM repositories ORDER BY id"),
+        ["old", "upgraded"]
+    );
+    assert_eq!(ws.query::<String>("SELECT status FROM changed_files"), "added");
+    let version = ws.query::<i64>("SELECT version FROM schema_version");
+    assert!(version > 0);
+
+    ws.connection()
+        .execute("UPDATE schema_version SET version = version + 1", [])
+        .unwrap();
+    let output = ws.run_unchecked(&[ws.repos().to_str().unwrap(), "--recursive"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("schema version"));
+}
+
+#[test]
+fn foreign_keys_are_indexed() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "indexed");
+    repo.write("a.txt", "a\n");
+    repo.commit("Add a", ALICE);
+
+    ws.scan(&[]);
+    let conn = ws.connection();
+    for (query, index) in [
+        ("SELECT * FROM logs WHERE repository_id = 1", "idx_logs_repository_id"),
+        ("SELECT * FROM logs WHERE author_email = 'a'", "idx_logs_author_email"),
+        ("SELECT * FROM logs WHERE commit_datetime > 0", "idx_logs_commit_datetime"),
+        ("SELECT * FROM changed_files WHERE commit_hash = 'a'", "idx_changed_files_commit_hash"),
+    ] {
+        let plan = conn
+            .query_row(&format!("EXPLAIN QUERY PLAN {query}"), [], |row| row.get::<_, String>(3))
+            .unwrap();
+        assert!(plan.contains(index), "{query} doesn't use {index}: {plan}");
+    }
+}
+
+#[test]
+fn foreign_keys_are_enforced() {
+    let ws = Workspace::new();
+    let repo = TestRepo::init(&ws.repos(), "integrity");
+    repo.write("a.txt", "a\n");
+    repo.commit("Add a", ALICE);
+
+    ws.scan(&[]);
+    ws.scan(&["--clear"]);
+    assert_eq!(ws.query::<i64>("SELECT COUNT(*) FROM logs"), 1);
+
+    let conn = git_log_to_sqlite::database::open(ws.database()).unwrap();
+    let orphan = conn.execute(
+        "INSERT INTO changed_files (commit_hash, file_path) VALUES ('0000', 'orphan.txt')",
+        [],
+    );
+    assert!(orphan.is_err(), "changed file without its log must be rejected");
+    let orphan = conn.execute(
+        "INSERT INTO commit_parents (commit_hash, parent_hash, parent_index) VALUES ('0000', '1111', 0)",
+        [],
+    );
+    assert!(orphan.is_err(), "parent edge without its log must be rejected");
+}
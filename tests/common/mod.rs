@@ -0,0 +1,258 @@
+//! Helpers to create temporary git repositories and run the analyzer against them.
+#![allow(dead_code)]
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+use git2::{ObjectType, Oid, Repository, Signature, Time};
+use rusqlite::Connection;
+use tempfile::TempDir;
+
+/// Author of a commit: name, email, and time in UNIX epoch.
+pub type Author<'a> = (&'a str, &'a str, i64);
+
+pub const ALICE: Author = ("Alice", "alice@example.com", 1_700_000_000);
+pub const BOB: Author = ("Bob", "bob@example.com", 1_700_100_000);
+
+/// A temporary git repository. Files are written to the working directory and staged before each
+/// commit, so each commit snapshots everything written so far.
+pub struct TestRepo {
+    pub repo: Repository,
+}
+
+impl TestRepo {
+    /// Initializes a repository in `parent/name`.
+    pub fn init(parent: &Path, name: &str) -> Self {
+        let repo = Repository::init(parent.join(name)).unwrap();
+        Self { repo }
+    }
+
+    pub fn path(&self) -> &Path {
+        self.repo.workdir().unwrap()
+    }
+
+    /// Writes the file to the working directory and stages it.
+    pub fn write(&self, path: &str, content: impl AsRef<[u8]>) -> &Self {
+        let full = self.path().join(path);
+        fs::create_dir_all(full.parent().unwrap()).unwrap();
+        fs::write(full, content).unwrap();
+        let mut index = self.repo.index().unwrap();
+        index.add_path(Path::new(path)).unwrap();
+        index.write().unwrap();
+        self
+    }
+
+    /// Removes the file from the working directory and the index.
+    pub fn remove(&self, path: &str) -> &Self {
+        fs::remove_file(self.path().join(path)).unwrap();
+        let mut index = self.repo.index().unwrap();
+        index.remove_path(Path::new(path)).unwrap();
+        index.write().unwrap();
+        self
+    }
+
+    /// Renames the file, keeping its content.
+    pub fn rename(&self, from: &str, to: &str) -> &Self {
+        let content = fs::read(self.path().join(from)).unwrap();
+        self.remove(from).write(to, content)
+    }
+
+    /// Commits the staged files on top of HEAD and moves HEAD.
+    pub fn commit(&self, message: &str, author: Author) -> Oid {
+        let parents = self
+            .repo
+            .head()
+            .ok()
+            .and_then(|head| head.target())
+            .into_iter()
+            .collect();
+        self.commit_with_parents(message, author, parents, Some("HEAD"))
+    }
+
+    /// Commits the staged files with explicit parents, updating `update_ref` if any.
+    pub fn commit_with_parents(
+        &self,
+        message: &str,
+        (name, email, time): Author,
+        parents: Vec<Oid>,
+        update_ref: Option<&str>,
+    ) -> Oid {
+        let signature = Signature::new(name, email, &Time::new(time, 0)).unwrap();
+        let tree = self
+            .repo
+            .find_tree(self.repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let parents = parents
+            .iter()
+            .map(|oid| self.repo.find_commit(*oid).unwrap())
+            .collect::<Vec<_>>();
+        self.repo
+            .commit(
+                update_ref,
+                &signature,
+                &signature,
+                message,
+                &tree,
+                &parents.iter().collect::<Vec<_>>(),
+            )
+            .unwrap()
+    }
+
+    /// Builds an octopus merge on HEAD: two side commits forked from a base commit, and a commit on
+    /// the mainline, merged together by a commit with the 3 of them as parents, in this order.
+    /// Returns the merge and its parents.
+    pub fn octopus_merge(&self) -> (Oid, [Oid; 3]) {
+        self.write("a.txt", "a\n");
+        let base = self.commit("Base", ALICE);
+        self.write("b.txt", "b\n");
+        let first = self.commit_with_parents("First", BOB, vec![base], None);
+        self.write("c.txt", "c\n");
+        let second = self.commit_with_parents("Second", BOB, vec![base], None);
+        self.write("d.txt", "d\n");
+        let main = self.commit("Main", ALICE);
+        let parents = [main, first, second];
+        let merge = self.commit_with_parents("Octopus", ALICE, parents.to_vec(), Some("HEAD"));
+        (merge, parents)
+    }
+
+    /// Commits the staged files on top of HEAD with the armored signature in the `gpgsig` header,
+    /// as git does with `commit.gpgSign`, and moves HEAD. The signature is not verified.
+    pub fn commit_signed(
+        &self,
+        message: &str,
+        (name, email, time): Author,
+        signature: &str,
+    ) -> Oid {
+        let author = Signature::new(name, email, &Time::new(time, 0)).unwrap();
+        let tree = self
+            .repo
+            .find_tree(self.repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let parent = self.repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+        let buffer = self
+            .repo
+            .commit_create_buffer(
+                &author,
+                &author,
+                message,
+                &tree,
+                &parent.iter().collect::<Vec<_>>(),
+            )
+            .unwrap();
+        let oid = self
+            .repo
+            .commit_signed(buffer.as_str().unwrap(), signature, None)
+            .unwrap();
+        let head = self.repo.find_reference("HEAD").unwrap();
+        let branch = head.symbolic_target().unwrap();
+        self.repo.reference(branch, oid, true, "commit").unwrap();
+        oid
+    }
+
+    /// Commits the staged files on top of HEAD with a message in the encoding, declared in the
+    /// `encoding` header, as git does with `i18n.commitEncoding`, and moves HEAD.
+    pub fn commit_encoded(
+        &self,
+        message: &[u8],
+        encoding: &str,
+        (name, email, time): Author,
+    ) -> Oid {
+        let tree = self.repo.index().unwrap().write_tree().unwrap();
+        let mut buffer = format!("tree {tree}\n");
+        if let Some(parent) = self.repo.head().ok().and_then(|head| head.target()) {
+            buffer.push_str(&format!("parent {parent}\n"));
+        }
+        let signature = format!("{name} <{email}> {time} +0000");
+        buffer.push_str(&format!("author {signature}\ncommitter {signature}\n"));
+        buffer.push_str(&format!("encoding {encoding}\n\n"));
+        let mut buffer = buffer.into_bytes();
+        buffer.extend_from_slice(message);
+
+        let oid = self.repo.odb().unwrap().write(ObjectType::Commit, &buffer).unwrap();
+        let head = self.repo.find_reference("HEAD").unwrap();
+        let branch = head.symbolic_target().unwrap();
+        self.repo.reference(branch, oid, true, "commit").unwrap();
+        oid
+    }
+}
+
+/// A temporary workspace holding repositories, a database, and a configuration file.
+pub struct Workspace {
+    pub dir: TempDir,
+}
+
+impl Workspace {
+    pub fn new() -> Self {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("repos")).unwrap();
+        Self { dir }
+    }
+
+    /// Directory to create the repositories in.
+    pub fn repos(&self) -> PathBuf {
+        self.dir.path().join("repos")
+    }
+
+    pub fn database(&self) -> PathBuf {
+        self.dir.path().join("repositories.db")
+    }
+
+    pub fn config(&self) -> PathBuf {
+        self.dir.path().join("config.toml")
+    }
+
+    pub fn write_config(&self, content: &str) {
+        fs::write(self.config(), content).unwrap();
+    }
+
+    /// Runs the analyzer with the database and the configuration file of the workspace, and
+    /// asserts it succeeds.
+    pub fn run(&self, args: &[&str]) -> Output {
+        let output = self.run_unchecked(args);
+        assert!(
+            output.status.success(),
+            "analyzer failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        output
+    }
+
+    pub fn run_unchecked(&self, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_git-log-to-sqlite"))
+            .args(args)
+            .arg("--database")
+            .arg(self.database())
+            .arg("--config")
+            .arg(self.config())
+            .output()
+            .unwrap()
+    }
+
+    /// Scans all the repositories of the workspace.
+    pub fn scan(&self, args: &[&str]) -> Output {
+        let repos = self.repos();
+        let mut all = vec![repos.to_str().unwrap(), "--recursive"];
+        all.extend_from_slice(args);
+        self.run(&all)
+    }
+
+    pub fn connection(&self) -> Connection {
+        Connection::open(self.database()).unwrap()
+    }
+
+    /// Runs the query and returns the first column of the first row.
+    pub fn query<T: rusqlite::types::FromSql>(&self, sql: &str) -> T {
+        self.connection().query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    /// Runs the query and returns the first column of all the rows.
+    pub fn query_all<T: rusqlite::types::FromSql>(&self, sql: &str) -> Vec<T> {
+        let conn = self.connection();
+        let mut stmt = conn.prepare(sql).unwrap();
+        let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+        rows.map(|row| row.unwrap()).collect()
+    }
+}